@@ -44,6 +44,7 @@ typedef enum adaptics_engine_ffi_error
     ADAPTICS_ENGINE_FFI_ERROR_CASTERROR = 17,
     ADAPTICS_ENGINE_FFI_ERROR_ADAPTICSERROR = 18,
     ADAPTICS_ENGINE_FFI_ERROR_ERRMSGBUFFERNULL = 19,
+    ADAPTICS_ENGINE_FFI_ERROR_OUTPUTBUFFERTOOSMALL = 20,
     } adaptics_engine_ffi_error;
 
 /// !NOTE: y and z are swapped for Unity
@@ -126,6 +127,11 @@ ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_deinit(const adaptics_
 /// For further information, see [`PatternEvalUpdate::Pattern`].
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_pattern(const adaptics_engine_ffi_handle* context, const char* pattern_json);
 
+/// Updates the pattern to be played, blending from the currently playing pattern.
+/// The intensity is crossfaded over `crossfade_ms`, and the position is interpolated over `path_interpolation_ms` (0 to disable either).
+/// For further information, see [`PatternEvalUpdate::Pattern`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_pattern_with_crossfade(const adaptics_engine_ffi_handle* context, const char* pattern_json, double crossfade_ms, double path_interpolation_ms);
+
 /// Alias for [`crate::adaptics_engine_update_pattern()`]
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_tacton(const adaptics_engine_ffi_handle* context, const char* pattern_json);
 
@@ -136,6 +142,26 @@ ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_tacton(const ad
 /// Use [`adaptics_engine_update_time()`] or [`adaptics_engine_update_parameters()`] to set the time parameter.
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_playstart(const adaptics_engine_ffi_handle* context, double playstart, double playstart_offset);
 
+/// Starts playback at `pattern_time` (in milliseconds).
+/// For further information, see [`PatternEvalUpdate::Play`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_play(const adaptics_engine_ffi_handle* context, double pattern_time);
+
+/// Pauses playback, freezing the pattern time.
+/// For further information, see [`PatternEvalUpdate::Pause`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_pause(const adaptics_engine_ffi_handle* context);
+
+/// Resumes playback from the pattern time it was paused at.
+/// For further information, see [`PatternEvalUpdate::Resume`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_resume(const adaptics_engine_ffi_handle* context);
+
+/// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused.
+/// For further information, see [`PatternEvalUpdate::Seek`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_seek(const adaptics_engine_ffi_handle* context, double pattern_time);
+
+/// Stops playback and rewinds to the start of the pattern.
+/// For further information, see [`PatternEvalUpdate::Stop`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_stop(const adaptics_engine_ffi_handle* context);
+
 /// Used to update all `evaluator_params`.
 ///
 /// Accepts a JSON string representing the evaluator parameters. See [`PatternEvaluatorParameters`].
@@ -165,6 +191,17 @@ ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_user_parameters
 /// For further information, see [`PatternEvalUpdate::UserParameters`].
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_user_parameter(const adaptics_engine_ffi_handle* context, const char* name, double value);
 
+/// Updates the user parameter automation tracks applied on top of the current pattern.
+/// Accepts a JSON string in the format `{ [key: string]: { points: { time: double, value: double, transition: MAHTransition }[] } }`.
+/// For further information, see [`PatternEvalUpdate::UserParameterAutomation`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_user_parameter_automation(const adaptics_engine_ffi_handle* context, const char* automation_json);
+
+/// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+/// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+///
+/// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_get_user_parameter_definitions(const adaptics_engine_ffi_handle* context, const char* pattern_json, adaptics_engine_slice_mutu8 definitions_json);
+
 /// Updates `geo_matrix`, a 4x4 matrix in row-major order, where `data[3]` is the fourth element of the first row (translate x).
 /// For further information, see [`PatternEvalUpdate::GeoTransformMatrix`].
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_geo_transform_matrix(const adaptics_engine_ffi_handle* context, const adaptics_engine_geo_matrix* geo_matrix);
@@ -178,6 +215,57 @@ ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_geo_transform_m
 /// `num_evals` must be a valid pointer to a u32
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_get_playback_updates(const adaptics_engine_ffi_handle* context, adaptics_engine_slice_mut_unity_eval_result* eval_results, uint32_t* num_evals);
 
+/// Adds a tacton to the end of the playback queue.
+/// Accepts a JSON string representing the queue entry. See [`QueueEntry`].
+/// For further information, see [`PatternEvalUpdate::QueueEnqueue`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_queue_enqueue(const adaptics_engine_ffi_handle* context, const char* entry_json);
+
+/// Removes all tactons from the playback queue and stops the current queue entry.
+/// For further information, see [`PatternEvalUpdate::QueueClear`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_queue_clear(const adaptics_engine_ffi_handle* context);
+
+/// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+/// `has_event` will be set to false if there are no pending events.
+///
+/// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+///
+/// # Safety
+/// `has_event` must be a valid pointer to a bool
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_poll_event(const adaptics_engine_ffi_handle* context, adaptics_engine_slice_mutu8 event_json, bool* has_event);
+
+/// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+/// See [`TelemetrySnapshot`] for the format.
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_get_telemetry(const adaptics_engine_ffi_handle* context, adaptics_engine_slice_mutu8 telemetry_json);
+
+/// Starts playing a tacton on a new voice, concurrently with the default voice and any other voices.
+/// `voice_id` will be set to the id of the new voice, which can be used with [`adaptics_engine_stop_voice()`] and [`adaptics_engine_update_voice_user_parameter()`].
+///
+/// The focal point is time-multiplexed between all playing voices, `priority` (>= 1) weights the share of the device samples this voice gets.
+/// For further information, see [`PatternEvalUpdate::VoicePlay`].
+///
+/// # Safety
+/// `voice_id` must be a valid pointer to a u32
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_play_voice(const adaptics_engine_ffi_handle* context, const char* tacton_json, uint32_t priority, uint32_t* voice_id);
+
+/// Stops a voice started with [`adaptics_engine_play_voice()`]. Voice 0 stops the default voice.
+/// For further information, see [`PatternEvalUpdate::VoiceStop`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_stop_voice(const adaptics_engine_ffi_handle* context, uint32_t voice_id);
+
+/// Updates a single user parameter of a voice. Voice 0 updates the default voice.
+/// For further information, see [`PatternEvalUpdate::VoiceUserParameters`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_update_voice_user_parameter(const adaptics_engine_ffi_handle* context, uint32_t voice_id, const char* name, double value);
+
+/// Loads the tactons (`.adaptics` files) in `tacton_dir` into the tacton library, replacing the previous directory.
+/// Edited tactons are reloaded automatically, including the one that is playing.
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_set_tacton_dir(const adaptics_engine_ffi_handle* context, const char* tacton_dir);
+
+/// Plays a tacton from the tacton library (see [`adaptics_engine_set_tacton_dir()`]) from the start, by file name or by the name stored in the tacton.
+/// For further information, see [`PatternEvalUpdate::PlayTacton`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_play_tacton(const adaptics_engine_ffi_handle* context, const char* name);
+
+/// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_get_tactons(const adaptics_engine_ffi_handle* context, adaptics_engine_slice_mutu8 tactons_json);
+
 /// Higher level function to load a new pattern and instantly start playback.
 ADAPTICS_EXPORT adaptics_engine_ffi_error adaptics_engine_adaptics_engine_play_tacton_immediate(const adaptics_engine_ffi_handle* context, const char* tacton_json);
 
@@ -17,9 +17,9 @@ namespace com.github.AdaptiveHaptics
         static AdapticsEngineInterop()
         {
             var api_version = AdapticsEngineInterop.ffi_api_guard();
-            if (api_version != 11963767314292576480ul)
+            if (api_version != 11462396898779153634ul)
             {
-                throw new TypeLoadException($"API reports hash {api_version} which differs from hash in bindings (11963767314292576480). You probably forgot to update / copy either the bindings or the library.");
+                throw new TypeLoadException($"API reports hash {api_version} which differs from hash in bindings (11462396898779153634). You probably forgot to update / copy either the bindings or the library.");
             }
         }
 
@@ -147,6 +147,24 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Updates the pattern to be played, blending from the currently playing pattern.
+        /// The intensity is crossfaded over `crossfade_ms`, and the position is interpolated over `path_interpolation_ms` (0 to disable either).
+        /// For further information, see [`PatternEvalUpdate::Pattern`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_update_pattern_with_crossfade")]
+        public static extern FFIError adaptics_engine_update_pattern_with_crossfade(IntPtr context, string pattern_json, double crossfade_ms, double path_interpolation_ms);
+
+        /// Updates the pattern to be played, blending from the currently playing pattern.
+        /// The intensity is crossfaded over `crossfade_ms`, and the position is interpolated over `path_interpolation_ms` (0 to disable either).
+        /// For further information, see [`PatternEvalUpdate::Pattern`].
+        public static void adaptics_engine_update_pattern_with_crossfade_checked(IntPtr context, string pattern_json, double crossfade_ms, double path_interpolation_ms)
+        {
+            var rval = adaptics_engine_update_pattern_with_crossfade(context, pattern_json, crossfade_ms, path_interpolation_ms);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
         /// Alias for [`crate::adaptics_engine_update_pattern()`]
         [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_update_tacton")]
         public static extern FFIError adaptics_engine_update_tacton(IntPtr context, string pattern_json);
@@ -183,6 +201,86 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Starts playback at `pattern_time` (in milliseconds).
+        /// For further information, see [`PatternEvalUpdate::Play`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_play")]
+        public static extern FFIError adaptics_engine_play(IntPtr context, double pattern_time);
+
+        /// Starts playback at `pattern_time` (in milliseconds).
+        /// For further information, see [`PatternEvalUpdate::Play`].
+        public static void adaptics_engine_play_checked(IntPtr context, double pattern_time)
+        {
+            var rval = adaptics_engine_play(context, pattern_time);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Pauses playback, freezing the pattern time.
+        /// For further information, see [`PatternEvalUpdate::Pause`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_pause")]
+        public static extern FFIError adaptics_engine_pause(IntPtr context);
+
+        /// Pauses playback, freezing the pattern time.
+        /// For further information, see [`PatternEvalUpdate::Pause`].
+        public static void adaptics_engine_pause_checked(IntPtr context)
+        {
+            var rval = adaptics_engine_pause(context);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Resumes playback from the pattern time it was paused at.
+        /// For further information, see [`PatternEvalUpdate::Resume`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_resume")]
+        public static extern FFIError adaptics_engine_resume(IntPtr context);
+
+        /// Resumes playback from the pattern time it was paused at.
+        /// For further information, see [`PatternEvalUpdate::Resume`].
+        public static void adaptics_engine_resume_checked(IntPtr context)
+        {
+            var rval = adaptics_engine_resume(context);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused.
+        /// For further information, see [`PatternEvalUpdate::Seek`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_seek")]
+        public static extern FFIError adaptics_engine_seek(IntPtr context, double pattern_time);
+
+        /// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused.
+        /// For further information, see [`PatternEvalUpdate::Seek`].
+        public static void adaptics_engine_seek_checked(IntPtr context, double pattern_time)
+        {
+            var rval = adaptics_engine_seek(context, pattern_time);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Stops playback and rewinds to the start of the pattern.
+        /// For further information, see [`PatternEvalUpdate::Stop`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_stop")]
+        public static extern FFIError adaptics_engine_stop(IntPtr context);
+
+        /// Stops playback and rewinds to the start of the pattern.
+        /// For further information, see [`PatternEvalUpdate::Stop`].
+        public static void adaptics_engine_stop_checked(IntPtr context)
+        {
+            var rval = adaptics_engine_stop(context);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
         /// Used to update all `evaluator_params`.
         ///
         /// Accepts a JSON string representing the evaluator parameters. See [`PatternEvaluatorParameters`].
@@ -281,6 +379,53 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Updates the user parameter automation tracks applied on top of the current pattern.
+        /// Accepts a JSON string in the format `{ [key: string]: { points: { time: double, value: double, transition: MAHTransition }[] } }`.
+        /// For further information, see [`PatternEvalUpdate::UserParameterAutomation`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_update_user_parameter_automation")]
+        public static extern FFIError adaptics_engine_update_user_parameter_automation(IntPtr context, string automation_json);
+
+        /// Updates the user parameter automation tracks applied on top of the current pattern.
+        /// Accepts a JSON string in the format `{ [key: string]: { points: { time: double, value: double, transition: MAHTransition }[] } }`.
+        /// For further information, see [`PatternEvalUpdate::UserParameterAutomation`].
+        public static void adaptics_engine_update_user_parameter_automation_checked(IntPtr context, string automation_json)
+        {
+            var rval = adaptics_engine_update_user_parameter_automation(context, automation_json);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+        /// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+        ///
+        /// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_get_user_parameter_definitions")]
+        public static extern FFIError adaptics_engine_get_user_parameter_definitions(IntPtr context, string pattern_json, SliceMutu8 definitions_json);
+
+        /// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+        /// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+        ///
+        /// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+        public static void adaptics_engine_get_user_parameter_definitions(IntPtr context, string pattern_json, byte[] definitions_json)
+        {
+            var definitions_json_pinned = GCHandle.Alloc(definitions_json, GCHandleType.Pinned);
+            var definitions_json_slice = new SliceMutu8(definitions_json_pinned, (ulong) definitions_json.Length);
+            try
+            {
+                var rval = adaptics_engine_get_user_parameter_definitions(context, pattern_json, definitions_json_slice);;
+                if (rval != FFIError.Ok)
+                {
+                    throw new InteropException<FFIError>(rval);
+                }
+            }
+            finally
+            {
+                definitions_json_pinned.Free();
+            }
+        }
+
         /// Updates `geo_matrix`, a 4x4 matrix in row-major order, where `data[3]` is the fourth element of the first row (translate x).
         /// For further information, see [`PatternEvalUpdate::GeoTransformMatrix`].
         [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_update_geo_transform_matrix")]
@@ -332,6 +477,215 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Adds a tacton to the end of the playback queue.
+        /// Accepts a JSON string representing the queue entry. See [`QueueEntry`].
+        /// For further information, see [`PatternEvalUpdate::QueueEnqueue`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_queue_enqueue")]
+        public static extern FFIError adaptics_engine_queue_enqueue(IntPtr context, string entry_json);
+
+        /// Adds a tacton to the end of the playback queue.
+        /// Accepts a JSON string representing the queue entry. See [`QueueEntry`].
+        /// For further information, see [`PatternEvalUpdate::QueueEnqueue`].
+        public static void adaptics_engine_queue_enqueue_checked(IntPtr context, string entry_json)
+        {
+            var rval = adaptics_engine_queue_enqueue(context, entry_json);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Removes all tactons from the playback queue and stops the current queue entry.
+        /// For further information, see [`PatternEvalUpdate::QueueClear`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_queue_clear")]
+        public static extern FFIError adaptics_engine_queue_clear(IntPtr context);
+
+        /// Removes all tactons from the playback queue and stops the current queue entry.
+        /// For further information, see [`PatternEvalUpdate::QueueClear`].
+        public static void adaptics_engine_queue_clear_checked(IntPtr context)
+        {
+            var rval = adaptics_engine_queue_clear(context);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+        /// `has_event` will be set to false if there are no pending events.
+        ///
+        /// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+        ///
+        /// # Safety
+        /// `has_event` must be a valid pointer to a bool
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_poll_event")]
+        public static extern FFIError adaptics_engine_poll_event(IntPtr context, SliceMutu8 event_json, out bool has_event);
+
+        /// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+        /// `has_event` will be set to false if there are no pending events.
+        ///
+        /// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+        ///
+        /// # Safety
+        /// `has_event` must be a valid pointer to a bool
+        public static void adaptics_engine_poll_event(IntPtr context, byte[] event_json, out bool has_event)
+        {
+            var event_json_pinned = GCHandle.Alloc(event_json, GCHandleType.Pinned);
+            var event_json_slice = new SliceMutu8(event_json_pinned, (ulong) event_json.Length);
+            try
+            {
+                var rval = adaptics_engine_poll_event(context, event_json_slice, out has_event);;
+                if (rval != FFIError.Ok)
+                {
+                    throw new InteropException<FFIError>(rval);
+                }
+            }
+            finally
+            {
+                event_json_pinned.Free();
+            }
+        }
+
+        /// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+        /// See [`TelemetrySnapshot`] for the format.
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_get_telemetry")]
+        public static extern FFIError adaptics_engine_get_telemetry(IntPtr context, SliceMutu8 telemetry_json);
+
+        /// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+        /// See [`TelemetrySnapshot`] for the format.
+        public static void adaptics_engine_get_telemetry(IntPtr context, byte[] telemetry_json)
+        {
+            var telemetry_json_pinned = GCHandle.Alloc(telemetry_json, GCHandleType.Pinned);
+            var telemetry_json_slice = new SliceMutu8(telemetry_json_pinned, (ulong) telemetry_json.Length);
+            try
+            {
+                var rval = adaptics_engine_get_telemetry(context, telemetry_json_slice);;
+                if (rval != FFIError.Ok)
+                {
+                    throw new InteropException<FFIError>(rval);
+                }
+            }
+            finally
+            {
+                telemetry_json_pinned.Free();
+            }
+        }
+
+        /// Starts playing a tacton on a new voice, concurrently with the default voice and any other voices.
+        /// `voice_id` will be set to the id of the new voice, which can be used with [`adaptics_engine_stop_voice()`] and [`adaptics_engine_update_voice_user_parameter()`].
+        ///
+        /// The focal point is time-multiplexed between all playing voices, `priority` (>= 1) weights the share of the device samples this voice gets.
+        /// For further information, see [`PatternEvalUpdate::VoicePlay`].
+        ///
+        /// # Safety
+        /// `voice_id` must be a valid pointer to a u32
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_play_voice")]
+        public static extern FFIError adaptics_engine_play_voice(IntPtr context, string tacton_json, uint priority, out uint voice_id);
+
+        /// Starts playing a tacton on a new voice, concurrently with the default voice and any other voices.
+        /// `voice_id` will be set to the id of the new voice, which can be used with [`adaptics_engine_stop_voice()`] and [`adaptics_engine_update_voice_user_parameter()`].
+        ///
+        /// The focal point is time-multiplexed between all playing voices, `priority` (>= 1) weights the share of the device samples this voice gets.
+        /// For further information, see [`PatternEvalUpdate::VoicePlay`].
+        ///
+        /// # Safety
+        /// `voice_id` must be a valid pointer to a u32
+        public static void adaptics_engine_play_voice_checked(IntPtr context, string tacton_json, uint priority, out uint voice_id)
+        {
+            var rval = adaptics_engine_play_voice(context, tacton_json, priority, out voice_id);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Stops a voice started with [`adaptics_engine_play_voice()`]. Voice 0 stops the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceStop`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_stop_voice")]
+        public static extern FFIError adaptics_engine_stop_voice(IntPtr context, uint voice_id);
+
+        /// Stops a voice started with [`adaptics_engine_play_voice()`]. Voice 0 stops the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceStop`].
+        public static void adaptics_engine_stop_voice_checked(IntPtr context, uint voice_id)
+        {
+            var rval = adaptics_engine_stop_voice(context, voice_id);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Updates a single user parameter of a voice. Voice 0 updates the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceUserParameters`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_update_voice_user_parameter")]
+        public static extern FFIError adaptics_engine_update_voice_user_parameter(IntPtr context, uint voice_id, string name, double value);
+
+        /// Updates a single user parameter of a voice. Voice 0 updates the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceUserParameters`].
+        public static void adaptics_engine_update_voice_user_parameter_checked(IntPtr context, uint voice_id, string name, double value)
+        {
+            var rval = adaptics_engine_update_voice_user_parameter(context, voice_id, name, value);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Loads the tactons (`.adaptics` files) in `tacton_dir` into the tacton library, replacing the previous directory.
+        /// Edited tactons are reloaded automatically, including the one that is playing.
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_set_tacton_dir")]
+        public static extern FFIError adaptics_engine_set_tacton_dir(IntPtr context, string tacton_dir);
+
+        /// Loads the tactons (`.adaptics` files) in `tacton_dir` into the tacton library, replacing the previous directory.
+        /// Edited tactons are reloaded automatically, including the one that is playing.
+        public static void adaptics_engine_set_tacton_dir_checked(IntPtr context, string tacton_dir)
+        {
+            var rval = adaptics_engine_set_tacton_dir(context, tacton_dir);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Plays a tacton from the tacton library (see [`adaptics_engine_set_tacton_dir()`]) from the start, by file name or by the name stored in the tacton.
+        /// For further information, see [`PatternEvalUpdate::PlayTacton`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_play_tacton")]
+        public static extern FFIError adaptics_engine_play_tacton(IntPtr context, string name);
+
+        /// Plays a tacton from the tacton library (see [`adaptics_engine_set_tacton_dir()`]) from the start, by file name or by the name stored in the tacton.
+        /// For further information, see [`PatternEvalUpdate::PlayTacton`].
+        public static void adaptics_engine_play_tacton_checked(IntPtr context, string name)
+        {
+            var rval = adaptics_engine_play_tacton(context, name);;
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+        [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_get_tactons")]
+        public static extern FFIError adaptics_engine_get_tactons(IntPtr context, SliceMutu8 tactons_json);
+
+        /// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+        public static void adaptics_engine_get_tactons(IntPtr context, byte[] tactons_json)
+        {
+            var tactons_json_pinned = GCHandle.Alloc(tactons_json, GCHandleType.Pinned);
+            var tactons_json_slice = new SliceMutu8(tactons_json_pinned, (ulong) tactons_json.Length);
+            try
+            {
+                var rval = adaptics_engine_get_tactons(context, tactons_json_slice);;
+                if (rval != FFIError.Ok)
+                {
+                    throw new InteropException<FFIError>(rval);
+                }
+            }
+            finally
+            {
+                tactons_json_pinned.Free();
+            }
+        }
+
         /// Higher level function to load a new pattern and instantly start playback.
         [DllImport(NativeLib, CallingConvention = CallingConvention.Cdecl, EntryPoint = "adaptics_engine_adaptics_engine_play_tacton_immediate")]
         public static extern FFIError adaptics_engine_adaptics_engine_play_tacton_immediate(IntPtr context, string tacton_json);
@@ -422,6 +776,7 @@ namespace com.github.AdaptiveHaptics
         CastError = 17,
         AdapticsError = 18,
         ErrMsgBufferNull = 19,
+        OutputBufferTooSmall = 20,
     }
 
     ///A pointer to an array of data someone else owns which may be modified.
@@ -642,6 +997,18 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Updates the pattern to be played, blending from the currently playing pattern.
+        /// The intensity is crossfaded over `crossfade_ms`, and the position is interpolated over `path_interpolation_ms` (0 to disable either).
+        /// For further information, see [`PatternEvalUpdate::Pattern`].
+        public void UpdatePatternWithCrossfade(string pattern_json, double crossfade_ms, double path_interpolation_ms)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_update_pattern_with_crossfade(_context, pattern_json, crossfade_ms, path_interpolation_ms);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
         /// Alias for [`crate::adaptics_engine_update_pattern()`]
         public void UpdateTacton(string pattern_json)
         {
@@ -666,6 +1033,61 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Starts playback at `pattern_time` (in milliseconds).
+        /// For further information, see [`PatternEvalUpdate::Play`].
+        public void Play(double pattern_time)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_play(_context, pattern_time);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Pauses playback, freezing the pattern time.
+        /// For further information, see [`PatternEvalUpdate::Pause`].
+        public void Pause()
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_pause(_context);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Resumes playback from the pattern time it was paused at.
+        /// For further information, see [`PatternEvalUpdate::Resume`].
+        public void Resume()
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_resume(_context);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused.
+        /// For further information, see [`PatternEvalUpdate::Seek`].
+        public void Seek(double pattern_time)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_seek(_context, pattern_time);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Stops playback and rewinds to the start of the pattern.
+        /// For further information, see [`PatternEvalUpdate::Stop`].
+        public void Stop()
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_stop(_context);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
         /// Used to update all `evaluator_params`.
         ///
         /// Accepts a JSON string representing the evaluator parameters. See [`PatternEvaluatorParameters`].
@@ -730,6 +1152,40 @@ namespace com.github.AdaptiveHaptics
             }
         }
 
+        /// Updates the user parameter automation tracks applied on top of the current pattern.
+        /// Accepts a JSON string in the format `{ [key: string]: { points: { time: double, value: double, transition: MAHTransition }[] } }`.
+        /// For further information, see [`PatternEvalUpdate::UserParameterAutomation`].
+        public void UpdateUserParameterAutomation(string automation_json)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_update_user_parameter_automation(_context, automation_json);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+        /// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+        ///
+        /// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+        public void GetUserParameterDefinitions(string pattern_json, SliceMutu8 definitions_json)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_get_user_parameter_definitions(_context, pattern_json, definitions_json);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+        /// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+        ///
+        /// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+        public void GetUserParameterDefinitions(string pattern_json, byte[] definitions_json)
+        {
+            AdapticsEngineInterop.adaptics_engine_get_user_parameter_definitions(_context, pattern_json, definitions_json);
+        }
+
         /// Updates `geo_matrix`, a 4x4 matrix in row-major order, where `data[3]` is the fourth element of the first row (translate x).
         /// For further information, see [`PatternEvalUpdate::GeoTransformMatrix`].
         public void UpdateGeoTransformMatrix(ref GeoMatrix geo_matrix)
@@ -769,6 +1225,152 @@ namespace com.github.AdaptiveHaptics
             AdapticsEngineInterop.adaptics_engine_get_playback_updates(_context, eval_results, out num_evals);
         }
 
+        /// Adds a tacton to the end of the playback queue.
+        /// Accepts a JSON string representing the queue entry. See [`QueueEntry`].
+        /// For further information, see [`PatternEvalUpdate::QueueEnqueue`].
+        public void QueueEnqueue(string entry_json)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_queue_enqueue(_context, entry_json);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Removes all tactons from the playback queue and stops the current queue entry.
+        /// For further information, see [`PatternEvalUpdate::QueueClear`].
+        public void QueueClear()
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_queue_clear(_context);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+        /// `has_event` will be set to false if there are no pending events.
+        ///
+        /// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+        ///
+        /// # Safety
+        /// `has_event` must be a valid pointer to a bool
+        public void PollEvent(SliceMutu8 event_json, out bool has_event)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_poll_event(_context, event_json, out has_event);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+        /// `has_event` will be set to false if there are no pending events.
+        ///
+        /// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+        ///
+        /// # Safety
+        /// `has_event` must be a valid pointer to a bool
+        public void PollEvent(byte[] event_json, out bool has_event)
+        {
+            AdapticsEngineInterop.adaptics_engine_poll_event(_context, event_json, out has_event);
+        }
+
+        /// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+        /// See [`TelemetrySnapshot`] for the format.
+        public void GetTelemetry(SliceMutu8 telemetry_json)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_get_telemetry(_context, telemetry_json);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+        /// See [`TelemetrySnapshot`] for the format.
+        public void GetTelemetry(byte[] telemetry_json)
+        {
+            AdapticsEngineInterop.adaptics_engine_get_telemetry(_context, telemetry_json);
+        }
+
+        /// Starts playing a tacton on a new voice, concurrently with the default voice and any other voices.
+        /// `voice_id` will be set to the id of the new voice, which can be used with [`adaptics_engine_stop_voice()`] and [`adaptics_engine_update_voice_user_parameter()`].
+        ///
+        /// The focal point is time-multiplexed between all playing voices, `priority` (>= 1) weights the share of the device samples this voice gets.
+        /// For further information, see [`PatternEvalUpdate::VoicePlay`].
+        ///
+        /// # Safety
+        /// `voice_id` must be a valid pointer to a u32
+        public void PlayVoice(string tacton_json, uint priority, out uint voice_id)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_play_voice(_context, tacton_json, priority, out voice_id);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Stops a voice started with [`adaptics_engine_play_voice()`]. Voice 0 stops the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceStop`].
+        public void StopVoice(uint voice_id)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_stop_voice(_context, voice_id);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Updates a single user parameter of a voice. Voice 0 updates the default voice.
+        /// For further information, see [`PatternEvalUpdate::VoiceUserParameters`].
+        public void UpdateVoiceUserParameter(uint voice_id, string name, double value)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_update_voice_user_parameter(_context, voice_id, name, value);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Loads the tactons (`.adaptics` files) in `tacton_dir` into the tacton library, replacing the previous directory.
+        /// Edited tactons are reloaded automatically, including the one that is playing.
+        public void SetTactonDir(string tacton_dir)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_set_tacton_dir(_context, tacton_dir);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Plays a tacton from the tacton library (see [`adaptics_engine_set_tacton_dir()`]) from the start, by file name or by the name stored in the tacton.
+        /// For further information, see [`PatternEvalUpdate::PlayTacton`].
+        public void PlayTacton(string name)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_play_tacton(_context, name);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+        public void GetTactons(SliceMutu8 tactons_json)
+        {
+            var rval = AdapticsEngineInterop.adaptics_engine_get_tactons(_context, tactons_json);
+            if (rval != FFIError.Ok)
+            {
+                throw new InteropException<FFIError>(rval);
+            }
+        }
+
+        /// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+        public void GetTactons(byte[] tactons_json)
+        {
+            AdapticsEngineInterop.adaptics_engine_get_tactons(_context, tactons_json);
+        }
+
         /// Higher level function to load a new pattern and instantly start playback.
         public void PlayTactonImmediate(string tacton_json)
         {
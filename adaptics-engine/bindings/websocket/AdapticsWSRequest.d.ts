@@ -0,0 +1,328 @@
+/* eslint-disable */
+/**
+ * This file was automatically generated by json-schema-to-typescript.
+ * DO NOT MODIFY IT BY HAND. Instead, modify the source JSONSchema file,
+ * and run json-schema-to-typescript to regenerate this file.
+ */
+
+export type AdapticsWSRequestBody = AdapticsWSQuery | AdapticsWSClientMessage | PatternEvalUpdate;
+/**
+ * Requests for the current state of the engine
+ */
+export type AdapticsWSQuery =
+  | {
+      cmd: "get_pattern";
+      data: {};
+    }
+  | {
+      cmd: "get_parameters";
+      data: {};
+    }
+  | {
+      cmd: "get_playback_state";
+      data: {};
+    }
+  | {
+      cmd: "get_version";
+      data: {};
+    }
+  | {
+      cmd: "get_tactons";
+      data: {};
+    };
+/**
+ * Messages from websocket clients that configure their own connection, instead of being sent to the pattern-eval thread as a [`PatternEvalUpdate`]
+ */
+export type AdapticsWSClientMessage =
+  | {
+      cmd: "subscribe";
+      data: {
+        topics: {
+          [k: string]: WsTopicOptions;
+        };
+      };
+    }
+  | {
+      cmd: "auth";
+      data: {
+        token: string;
+      };
+    };
+export type PatternEvalUpdate =
+  | {
+      cmd: "update_pattern";
+      data: {
+        crossfade_ms?: number | null;
+        path_interpolation_ms?: number | null;
+        pattern_json: string;
+      };
+    }
+  | {
+      cmd: "update_playstart";
+      data: {
+        playstart: number;
+        playstart_offset: number;
+      };
+    }
+  | {
+      cmd: "play";
+      data: {
+        pattern_time: number;
+      };
+    }
+  | {
+      cmd: "pause";
+      data: {};
+    }
+  | {
+      cmd: "resume";
+      data: {};
+    }
+  | {
+      cmd: "seek";
+      data: {
+        pattern_time: number;
+      };
+    }
+  | {
+      cmd: "stop";
+      data: {};
+    }
+  | {
+      cmd: "play_tacton";
+      data: {
+        name: string;
+      };
+    }
+  | {
+      cmd: "update_parameters";
+      data: {
+        evaluator_params: PatternEvaluatorParameters;
+      };
+    }
+  | {
+      cmd: "update_tracking";
+      data: {
+        enabled: boolean;
+        hand?: TrackedHand | null;
+      };
+    }
+  | {
+      cmd: "update_tracking_bindings";
+      data: {
+        bindings: TrackingBinding[];
+      };
+    }
+  | {
+      cmd: "update_user_parameter_automation";
+      data: {
+        automation: {
+          [k: string]: MAHUserParameterAutomationTrack;
+        };
+      };
+    }
+  | {
+      cmd: "queue_enqueue";
+      data: {
+        entry: QueueEntry;
+      };
+    }
+  | {
+      cmd: "queue_clear";
+      data: {};
+    }
+  | {
+      cmd: "voice_play";
+      data: {
+        pattern_json: string;
+        priority?: number;
+        user_parameters?: {
+          [k: string]: number;
+        };
+        voice_id?: number | null;
+      };
+    }
+  | {
+      cmd: "voice_stop";
+      data: {
+        voice_id: number;
+      };
+    }
+  | {
+      cmd: "voice_update_user_parameters";
+      data: {
+        user_parameters: {
+          [k: string]: number;
+        };
+        voice_id: number;
+      };
+    }
+  | {
+      cmd: "parameter_time";
+      data: {
+        time: number;
+      };
+    }
+  | {
+      cmd: "user_parameters";
+      data: {
+        user_parameters: {
+          [k: string]: number;
+        };
+      };
+    }
+  | {
+      cmd: "geo_transform_matrix";
+      data: {
+        transform: GeometricTransformMatrix;
+      };
+    }
+  | {
+      cmd: "user_parameter";
+      data: {
+        name: string;
+        value: number;
+      };
+    };
+/**
+ * @minItems 4
+ * @maxItems 4
+ */
+export type GeometricTransformMatrix = [
+  [number, number, number, number],
+  [number, number, number, number],
+  [number, number, number, number],
+  [number, number, number, number]
+];
+/**
+ * Which tracked hand(s) the focal point follows, see [`crate::PlaybackConfig::tracked_hand`]
+ */
+export type TrackedHand = ("left" | "right") | "first_seen" | "both";
+/**
+ * A quantity measured on the tracked hand, in the haptic coordinate system
+ */
+export type TrackingQuantity = "palm_height" | "pinch_distance" | "grab_openness" | "hand_speed";
+export type MAHTransition =
+  | {
+      name: "linear";
+      params: {};
+    }
+  | {
+      name: "step";
+      params: {};
+    };
+
+/**
+ * A request from a websocket client, answered with an [`AdapticsWSServerMessage::Response`] with the same `id`, e.g. `{ "v": 1, "id": 7, "request": { "cmd": "get_playback_state", "data": {} } }`.
+ *
+ * Requests are answered once applied, so responses may arrive in a different order than the requests were sent. Messages without the envelope (a bare [`PatternEvalUpdate`] or [`AdapticsWSClientMessage`]) are still accepted, but are not answered.
+ */
+export interface AdapticsWSRequest {
+  /**
+   * Chosen by the client to match responses to requests
+   */
+  id: number;
+  request: AdapticsWSRequestBody;
+  /**
+   * Protocol version, must be [`WS_PROTOCOL_VERSION`]
+   */
+  v: number;
+}
+/**
+ * Rate control of a subscribed topic. Messages skipped due to rate control are not sent later.
+ */
+export interface WsTopicOptions {
+  /**
+   * Only sends every `decimation`th message of the topic
+   */
+  decimation?: number | null;
+  /**
+   * Maximum number of messages per second
+   */
+  max_rate_hz?: number | null;
+}
+/**
+ * Defines the current evaluation in combination with [NextEvalParams]
+ */
+export interface PatternEvaluatorParameters {
+  /**
+   * The geometric transform to apply to the pattern
+   */
+  geometric_transform: GeometricTransformMatrix;
+  /**
+   * The time at which to evaluate the pattern
+   */
+  time: number;
+  /**
+   * The user parameters to use (External Parameters in the Designer)
+   */
+  user_parameters: {
+    [k: string]: number;
+  };
+}
+/**
+ * Sets the user parameter `parameter` from a quantity of the tracked hand on every tracking frame, see [`crate::PlaybackConfig::tracking_bindings`].
+ *
+ * e.g. `{ parameter = "intensity", quantity = "palm_height", input = [300, 100] }` goes from 0 to 1 while the palm is lowered from 300mm to 100mm.
+ */
+export interface TrackingBinding {
+  /**
+   * The range of the quantity that is mapped linearly to `output`, values outside of it are clamped. `input[0]` may be greater than `input[1]` to invert the mapping.
+   *
+   * @minItems 2
+   * @maxItems 2
+   */
+  input: [number, number];
+  /**
+   * The range of the user parameter, `[0, 1]` if not given
+   *
+   * @minItems 2
+   * @maxItems 2
+   */
+  output?: [number, number];
+  parameter: string;
+  quantity: TrackingQuantity;
+}
+/**
+ * A time-stamped curve of values for a single user parameter.
+ *
+ * The curve is evaluated against playback time ([`crate::PatternEvaluatorParameters::time`]), not pattern time, so playback speed and conditional jumps do not affect it. Before the first point the value of the first point is used, after the last point the value of the last point is held.
+ */
+export interface MAHUserParameterAutomationTrack {
+  points: MAHUserParameterAutomationPoint[];
+}
+export interface MAHUserParameterAutomationPoint {
+  time: number;
+  /**
+   * Transition from this point to the next point
+   */
+  transition: MAHTransition;
+  value: number;
+}
+/**
+ * A tacton to be played by the playback queue, see [`crate::PatternEvalUpdate::QueueEnqueue`]
+ */
+export interface QueueEntry {
+  /**
+   * Pause after this entry has finished, before the next entry is started
+   */
+  gap_ms?: number;
+  /**
+   * Identifies the entry in [`crate::AdapticsEngineEvent`]s
+   */
+  id: string;
+  /**
+   * Number of times the pattern is played back-to-back. 0 loops until the queue is cleared.
+   */
+  loops?: number;
+  /**
+   * The tacton/pattern in JSON format (see [`pattern_evaluator::MidAirHapticsAnimationFileFormat`])
+   */
+  pattern_json: string;
+  /**
+   * User parameters set while the entry is playing, the previous values are restored when it ends
+   */
+  user_parameters?: {
+    [k: string]: number;
+  };
+}
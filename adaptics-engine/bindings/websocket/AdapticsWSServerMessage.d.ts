@@ -20,8 +20,145 @@ export type AdapticsWSServerMessage =
       data: {
         tracking_frame: TrackingFrame;
       };
+    }
+  | {
+      cmd: "event";
+      data: {
+        event: AdapticsEngineEvent;
+      };
+    }
+  | {
+      cmd: "telemetry";
+      data: {
+        telemetry: TelemetrySnapshot;
+      };
+    }
+  | {
+      cmd: "response";
+      data: {
+        id: number;
+        result: AdapticsWSResult;
+      };
     };
 export type TrackingFrameHandChirality = "Right" | "Left";
+/**
+ * Events emitted by the playback thread (e.g. to notify the host application about queue progress)
+ */
+export type AdapticsEngineEvent =
+  | {
+      data: {
+        id: string;
+      };
+      event: "queue_entry_started";
+    }
+  | {
+      data: {
+        completed: boolean;
+        id: string;
+      };
+      event: "queue_entry_finished";
+    }
+  | {
+      data: {
+        voice_id: number;
+      };
+      event: "voice_finished";
+    }
+  | {
+      data: {
+        message: string;
+      };
+      event: "error";
+    };
+export type AdapticsWSResult =
+  | {
+      response: AdapticsWSResponse;
+      status: "ok";
+    }
+  | {
+      message: string;
+      status: "error";
+    };
+export type AdapticsWSResponse =
+  | {
+      data: {};
+      type: "ack";
+    }
+  | {
+      data: {
+        pattern_json: string;
+      };
+      type: "pattern";
+    }
+  | {
+      data: {
+        evaluator_params: PatternEvaluatorParameters;
+      };
+      type: "parameters";
+    }
+  | {
+      data: {
+        /**
+         * Pattern time (in milliseconds) of the last evaluated sample
+         */
+        pattern_time: number;
+        /**
+         * false if stopped or paused
+         */
+        playing: boolean;
+        /**
+         * Id of the queue entry that is playing, see [`PatternEvalUpdate::QueueEnqueue`]
+         */
+        queue_entry?: string | null;
+        /**
+         * Number of queue entries waiting to be played
+         */
+        queue_len: number;
+        /**
+         * Which hand(s) the focal point follows while tracking is enabled
+         */
+        tracked_hand: TrackedHand;
+        tracking_enabled: boolean;
+        /**
+         * Voices playing in addition to the default voice, see [`PatternEvalUpdate::VoicePlay`]
+         */
+        voices: number[];
+      };
+      type: "playback_state";
+    }
+  | {
+      data: {
+        engine_version: string;
+        protocol_version: number;
+      };
+      type: "version";
+    }
+  | {
+      data: {
+        tactons: TactonInfo[];
+      };
+      type: "tactons";
+    }
+  | {
+      data: {
+        voice_id: number;
+      };
+      type: "voice_started";
+    };
+/**
+ * @minItems 4
+ * @maxItems 4
+ */
+export type GeometricTransformMatrix = [
+  [number, number, number, number],
+  [number, number, number, number],
+  [number, number, number, number],
+  [number, number, number, number]
+];
+/**
+ * Which tracked hand(s) the focal point follows, see [`crate::PlaybackConfig::tracked_hand`]
+ */
+export type TrackedHand = ("left" | "right") | "first_seen" | "both";
 
 export interface BrushAtAnimLocalTime {
   next_eval_params: NextEvalParams;
@@ -59,7 +196,14 @@ export interface MAHCoordsConst {
   z: number;
 }
 export interface TrackingFrame {
+  /**
+   * The hand that has been tracked the longest
+   */
   hand?: TrackingFrameHand | null;
+  /**
+   * The other hand, if two hands are tracked
+   */
+  second_hand?: TrackingFrameHand | null;
 }
 export interface TrackingFrameHand {
   chirality: TrackingFrameHandChirality;
@@ -103,3 +247,93 @@ export interface TrackingFramePalm {
    */
   width: number;
 }
+/**
+ * A point-in-time copy of the engine's runtime telemetry. Counters are totals since the engine was started.
+ */
+export interface TelemetrySnapshot {
+  deadline_miss_max_us: number;
+  /**
+   * Number of batches of device samples that were not ready before the device needed them
+   */
+  deadline_misses: number;
+  /**
+   * Playback updates dropped because the network thread (or `get_playback_updates` caller) lagged
+   */
+  dropped_playback_updates: number;
+  /**
+   * Tracking frames dropped because the pattern-eval thread lagged
+   */
+  dropped_tracking_frames: number;
+  /**
+   * Messages dropped from the outbound queue of a websocket client because the client could not keep up
+   */
+  dropped_websocket_messages: number;
+  /**
+   * Number of batches of device samples evaluated by the pattern-eval thread
+   */
+  eval_batches: number;
+  /**
+   * Exclusive upper bounds (in microseconds) of `eval_time_histogram`. The last bucket has no upper bound.
+   */
+  eval_time_bucket_bounds_us: number[];
+  /**
+   * Number of eval batches per eval time bucket, see `eval_time_bucket_bounds_us`
+   */
+  eval_time_histogram: number[];
+  eval_time_max_us: number;
+  eval_time_mean_us: number;
+  /**
+   * Round trip time of the last packet sent to the vibrotactile grid device, if any
+   */
+  serial_rtt_last_us?: number | null;
+  serial_rtt_max_us?: number | null;
+  /**
+   * Tracking frames per second (moving average), 0 if tracking is stalled or disabled
+   */
+  tracking_frame_rate: number;
+  /**
+   * Number of frames received from the tracking system
+   */
+  tracking_frames: number;
+  /**
+   * Seconds since the engine was started
+   */
+  uptime_s: number;
+  websocket_clients: number;
+  /**
+   * Websocket clients removed because sending to them failed
+   */
+  websocket_clients_dropped: number;
+}
+/**
+ * Defines the current evaluation in combination with [NextEvalParams]
+ */
+export interface PatternEvaluatorParameters {
+  /**
+   * The geometric transform to apply to the pattern
+   */
+  geometric_transform: GeometricTransformMatrix;
+  /**
+   * The time at which to evaluate the pattern
+   */
+  time: number;
+  /**
+   * The user parameters to use (External Parameters in the Designer)
+   */
+  user_parameters: {
+    [k: string]: number;
+  };
+}
+/**
+ * A tacton in the tacton library, see [`crate::PatternEvalUpdate::PlayTacton`]
+ */
+export interface TactonInfo {
+  /**
+   * File name in the tacton directory, e.g. "loading.adaptics"
+   */
+  file_name: string;
+  /**
+   * The `name` stored in the tacton
+   */
+  name: string;
+}
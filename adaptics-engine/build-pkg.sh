@@ -14,9 +14,10 @@ cargo clippy
 cargo test --release # also generates bindings (see test/gen-bindings.rs)
 cargo build --release
 
-json_schema_file="bindings/websocket/AdapticsWSServerMessage.json"
-typescript_defs_file="${json_schema_file%.json}.d.ts"
-node -p "import('json-schema-to-typescript').then(j => j.compileFromFile('$json_schema_file', { additionalProperties: false }).then(ts => fs.writeFileSync('$typescript_defs_file', ts)))"
+for json_schema_file in "bindings/websocket/AdapticsWSServerMessage.json" "bindings/websocket/AdapticsWSRequest.json"; do
+	typescript_defs_file="${json_schema_file%.json}.d.ts"
+	node -p "import('json-schema-to-typescript').then(j => j.compileFromFile('$json_schema_file', { additionalProperties: false }).then(ts => fs.writeFileSync('$typescript_defs_file', ts)))"
+done
 
 cp ../target/release/adaptics-engine-cli.exe "$pkg_dir/adaptics-engine-cli.exe"
 
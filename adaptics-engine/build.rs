@@ -1,32 +1,41 @@
-use std::{fs, path::PathBuf};
+fn main() {
+    #[cfg(feature = "ulhaptics")]
+    ulhaptics::build_bridge();
+}
 
+/// Only built with the `ulhaptics` feature, which requires the Ultraleap Haptics SDK to be installed
+#[cfg(feature = "ulhaptics")]
+mod ulhaptics {
+    use std::{fs, path::PathBuf};
 
-const ULHAPTICS_LIBRARY_PATH: &str = "C:/Program Files/Ultraleap Haptics/lib";
-const ULHAPTICS_DLL_PATH: &str = "C:/Program Files/Ultraleap Haptics/bin";
-const ULHAPTICS_HEADER_PATH: &str = "C:/Program Files/Ultraleap Haptics/include";
 
-const COPY_DLL_TO_OUT_DIR: bool = true;
+    const ULHAPTICS_LIBRARY_PATH: &str = "C:/Program Files/Ultraleap Haptics/lib";
+    const ULHAPTICS_DLL_PATH: &str = "C:/Program Files/Ultraleap Haptics/bin";
+    const ULHAPTICS_HEADER_PATH: &str = "C:/Program Files/Ultraleap Haptics/include";
 
-fn main() {
-    let out_dir = PathBuf::from(&std::env::var("OUT_DIR").unwrap());
+    const COPY_DLL_TO_OUT_DIR: bool = true;
 
-    //*********              build UltraleapHaptics bridge              *********//
-    println!("cargo:rustc-link-search={}", ULHAPTICS_LIBRARY_PATH); // Tell cargo to look for shared libraries in the specified directory
-    // println!("cargo:rustc-link-search={}", DLL_PATH);
-    println!("cargo:rustc-link-lib=UltraleapHaptics"); // Tell cargo to tell rustc to link UltraleapHaptics.lib
+    pub fn build_bridge() {
+        let out_dir = PathBuf::from(&std::env::var("OUT_DIR").unwrap());
 
-    if COPY_DLL_TO_OUT_DIR {
-        fs::copy(PathBuf::from(ULHAPTICS_DLL_PATH).join("UltraleapHaptics.dll"), out_dir.join("UltraleapHaptics.dll")).unwrap();
-    }
+        //*********              build UltraleapHaptics bridge              *********//
+        println!("cargo:rustc-link-search={}", ULHAPTICS_LIBRARY_PATH); // Tell cargo to look for shared libraries in the specified directory
+        // println!("cargo:rustc-link-search={}", DLL_PATH);
+        println!("cargo:rustc-link-lib=UltraleapHaptics"); // Tell cargo to tell rustc to link UltraleapHaptics.lib
 
-    cxx_build::bridge("src/threads/streaming/ulhaptics/ffi.rs")
-		.include(ULHAPTICS_HEADER_PATH)
-		.include("./src/threads/streaming/ulhaptics")
-        .file("src/threads/streaming/ulhaptics/ulh3-streaming.cpp")
-		.flag_if_supported("-std=c++20")
-        .compile("ulh3-streaming");
-
-    println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ffi.rs");
-    println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ulh3-streaming.cpp");
-    println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ulh3-streaming.h");
-}
\ No newline at end of file
+        if COPY_DLL_TO_OUT_DIR {
+            fs::copy(PathBuf::from(ULHAPTICS_DLL_PATH).join("UltraleapHaptics.dll"), out_dir.join("UltraleapHaptics.dll")).unwrap();
+        }
+
+        cxx_build::bridge("src/threads/streaming/ulhaptics/ffi.rs")
+            .include(ULHAPTICS_HEADER_PATH)
+            .include("./src/threads/streaming/ulhaptics")
+            .file("src/threads/streaming/ulhaptics/ulh3-streaming.cpp")
+            .flag_if_supported("-std=c++20")
+            .compile("ulh3-streaming");
+
+        println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ffi.rs");
+        println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ulh3-streaming.cpp");
+        println!("cargo:rerun-if-changed=src/threads/streaming/ulhaptics/ulh3-streaming.h");
+    }
+}
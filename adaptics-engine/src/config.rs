@@ -0,0 +1,308 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{AdapticsError, CALLBACK_RATE, DEVICE_UPDATE_RATE, OscInputConfig, WsServerConfig, DEFAULT_ALLOWED_ORIGINS};
+use crate::output::{self, OutputBackend};
+use crate::streaming::hapticglove::{DeviceType, GloveConfig};
+use crate::threads::pattern::{playback::PlaybackConfig, safety::SafetyConfig, tracking_bindings::TrackingBinding};
+use crate::tracking::TrackingConfig;
+use crate::session_log::SessionLogConfig;
+
+/// Address of the websocket server if none is configured
+pub const DEFAULT_WEBSOCKET_BIND_ADDR: &str = "127.0.0.1:8037";
+
+/// Configuration of the engine, e.g. loaded from a file with [`EngineConfig::load`].
+///
+/// Every field may be omitted, the defaults match the engine's compile-time defaults
+/// (e.g. [`CALLBACK_RATE`] and [`DEVICE_UPDATE_RATE`]). Example TOML file:
+/// ```toml
+/// [output]
+/// backend = "mock"
+/// callback_rate = 1000.0
+///
+/// [network]
+/// websocket_bind_addr = "0.0.0.0:8037"
+///
+/// [safety]
+/// max_intensity = 0.8
+/// bounds = { min = { x = -80, y = -80, z = 100 }, max = { x = 80, y = 80, z = 300 } }
+///
+/// [tracking]
+/// source = "synthetic"
+/// synthetic_motion = { motion = "approach_retreat", near = { x = 0, y = 0, z = 120 }, far = { x = 0, y = 0, z = 300 }, period = 3 }
+///
+/// [playback]
+/// tracked_hand = "both"
+/// tracking_bindings = [{ parameter = "intensity", quantity = "palm_height", input = [300, 100] }]
+///
+/// [glove]
+/// layout = ["palm_top_center", "palm_top_left", { x = -35.0, y = 36.0, z = 0.0 }, ...]
+///
+/// [session_log]
+/// path = "session.jsonl"
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub output: OutputConfig,
+    pub network: NetworkConfig,
+    pub tracking: TrackingConfig,
+    pub playback: PlaybackConfig,
+    pub safety: SafetyConfig,
+    pub glove: GloveConfig,
+    pub session_log: SessionLogConfig,
+    pub debug: DebugConfig,
+}
+
+/// Format of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+impl ConfigFormat {
+    /// JSON for files ending in ".json", TOML otherwise
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) { Self::Json } else { Self::Toml }
+    }
+}
+
+impl EngineConfig {
+    /// Reads and validates the configuration file at `path`, see [`ConfigFormat::from_path`]
+    pub fn load(path: &Path) -> Result<Self, AdapticsError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| AdapticsError::new(&format!("failed to read config file '{}': {e}", path.display())))?;
+        Self::parse(&contents, ConfigFormat::from_path(path)).map_err(|e| AdapticsError::new(&format!("invalid config file '{}': {e}", path.display())))
+    }
+
+    /// Parses and validates a configuration
+    pub fn parse(contents: &str, format: ConfigFormat) -> Result<Self, AdapticsError> {
+        let config: Self = match format {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks values that can not be rejected while parsing (e.g. because they were overridden afterwards)
+    pub fn validate(&self) -> Result<(), AdapticsError> {
+        self.output.validate()?;
+        if self.playback.seconds_per_playback_update <= 0.0 { return Err(AdapticsError::new("playback.seconds_per_playback_update must be greater than 0")); }
+        self.playback.tracking_bindings.iter().try_for_each(TrackingBinding::validate)?;
+        self.tracking.validate()?;
+        self.safety.validate()?;
+        self.glove.validate()?;
+        Ok(())
+    }
+
+    /// Creates the output backend selected by [`OutputConfig::backend`]
+    pub fn output_backend(&self) -> Result<Box<dyn OutputBackend>, AdapticsError> {
+        let OutputConfig { device_update_rate, callback_rate, .. } = self.output;
+        match self.output.backend {
+            OutputBackendKind::Ultraleap => crate::ulhaptics_output(callback_rate),
+            OutputBackendKind::Mock => {
+                println!("using mock streaming");
+                Ok(Box::new(output::MockOutput::new(device_update_rate, callback_rate)))
+            },
+            OutputBackendKind::VibGrid => {
+                let device_type = match self.output.vib_grid_port.as_str() {
+                    "auto" => DeviceType::Auto,
+                    port => DeviceType::SerialPort(port.to_string()),
+                };
+                Ok(Box::new(output::GloveOutput::new(device_type).with_config(self.glove.clone())))
+            },
+            OutputBackendKind::Record => {
+                let path = self.output.record_path.clone().ok_or(AdapticsError::new("output.record_path is required for the record backend"))?;
+                let format = output::RecordingFormat::from_path(&path);
+                Ok(Box::new(output::RecordingOutput::new(path, format, device_update_rate, callback_rate)))
+            },
+            OutputBackendKind::Udp => {
+                let target = self.output.udp_target.ok_or(AdapticsError::new("output.udp_target is required for the udp backend"))?;
+                Ok(Box::new(output::UdpOutput::new(target, self.output.udp_format, device_update_rate, callback_rate).with_decimation(self.output.udp_decimation)))
+            },
+        }
+    }
+
+    /// The websocket server configuration, `None` if [`NetworkConfig::enabled`] is false
+    #[must_use]
+    pub fn websocket_config(&self) -> Option<WsServerConfig> {
+        self.network.enabled.then(|| WsServerConfig {
+            bind_addr: self.network.websocket_bind_addr.clone(),
+            allowed_origins: self.network.allowed_origins.clone(),
+            token: self.network.token.clone(),
+        })
+    }
+
+    /// The OSC input configuration, `None` if no [`NetworkConfig::osc_bind_addr`] is set
+    #[must_use]
+    pub fn osc_input(&self) -> Option<OscInputConfig> {
+        self.network.osc_bind_addr.clone().map(|bind_addr| OscInputConfig { bind_addr })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputBackendKind {
+    /// Ultraleap mid-air haptic device, requires the `ulhaptics` feature
+    #[default]
+    Ultraleap,
+    /// Evaluates patterns without a device, see [`output::MockOutput`]
+    Mock,
+    /// Alpha: vibrotactile grid device on [`OutputConfig::vib_grid_port`], see [`GloveConfig`]
+    VibGrid,
+    /// Records to [`OutputConfig::record_path`], see [`output::RecordingOutput`]
+    Record,
+    /// Streams to [`OutputConfig::udp_target`], see [`output::UdpOutput`]
+    Udp,
+}
+
+/// Selects the device that is streamed to, see [`crate::output`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub backend: OutputBackendKind,
+    /// Device samples per second of the mock, record and udp backends
+    pub device_update_rate: u64,
+    /// Batches of device samples requested per second by the Ultraleap, mock, record and udp backends
+    pub callback_rate: f64,
+    /// Serial port of the vibrotactile grid device (e.g. "COM3" or "/dev/ttyUSB0"), or "auto" to detect it
+    pub vib_grid_port: String,
+    /// Files ending in ".bin" are recorded in a binary format, all others as CSV
+    pub record_path: Option<PathBuf>,
+    /// e.g. "127.0.0.1:9000"
+    pub udp_target: Option<SocketAddr>,
+    pub udp_format: output::UdpFormat,
+    /// Only send every Nth device sample
+    pub udp_decimation: u64,
+}
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            backend: OutputBackendKind::default(),
+            device_update_rate: DEVICE_UPDATE_RATE,
+            callback_rate: CALLBACK_RATE,
+            vib_grid_port: "auto".to_string(),
+            record_path: None,
+            udp_target: None,
+            udp_format: output::UdpFormat::default(),
+            udp_decimation: 1,
+        }
+    }
+}
+impl OutputConfig {
+    fn validate(&self) -> Result<(), AdapticsError> {
+        if self.device_update_rate == 0 || self.callback_rate <= 0.0 { return Err(AdapticsError::new("output.device_update_rate and output.callback_rate must be greater than 0")); }
+        if self.udp_decimation == 0 { return Err(AdapticsError::new("output.udp_decimation must be at least 1")); }
+        match self.backend {
+            OutputBackendKind::Record if self.record_path.is_none() => Err(AdapticsError::new("output.record_path is required for the record backend")),
+            OutputBackendKind::Udp if self.udp_target.is_none() => Err(AdapticsError::new("output.udp_target is required for the udp backend")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Network interfaces used to control the engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Hosts the websocket server (and REST API), see [`WsServerConfig`]
+    pub enabled: bool,
+    pub websocket_bind_addr: String,
+    /// See [`WsServerConfig::allowed_origins`]
+    pub allowed_origins: Vec<String>,
+    /// See [`WsServerConfig::token`]
+    pub token: Option<String>,
+    /// Listens for OSC messages on this UDP address, see [`OscInputConfig`]
+    pub osc_bind_addr: Option<String>,
+}
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            websocket_bind_addr: DEFAULT_WEBSOCKET_BIND_ADDR.to_string(),
+            allowed_origins: DEFAULT_ALLOWED_ORIGINS.iter().map(ToString::to_string).collect(),
+            token: None,
+            osc_bind_addr: None,
+        }
+    }
+}
+
+/// Debug logging, disabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    /// Logs when a thread falls behind (dropped tracking frames, playback updates and events, long sleeps of the streaming thread)
+    pub log_lag_events: bool,
+    /// Logs the round trip time of every packet sent to the vibrotactile grid device
+    pub log_serial_rtt: bool,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let toml = r#"
+            [output]
+            backend = "mock"
+            callback_rate = 1000.0
+
+            [network]
+            osc_bind_addr = "0.0.0.0:9001"
+
+            [safety]
+            max_intensity = 0.8
+            bounds = { min = { x = -80, y = -80, z = 100 }, max = { x = 80, y = 80, z = 300 } }
+
+            [debug]
+            log_lag_events = true
+        "#;
+        let config = EngineConfig::parse(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.output.backend, OutputBackendKind::Mock);
+        assert!((config.output.callback_rate - 1000.0).abs() < f64::EPSILON);
+        assert_eq!(config.output.device_update_rate, DEVICE_UPDATE_RATE);
+        assert_eq!(config.network.websocket_bind_addr, DEFAULT_WEBSOCKET_BIND_ADDR);
+        assert_eq!(config.osc_input().unwrap().bind_addr, "0.0.0.0:9001");
+        assert!((config.safety.max_intensity - 0.8).abs() < f64::EPSILON);
+        assert!(config.safety.bounds.is_some());
+        assert!(config.debug.log_lag_events && !config.debug.log_serial_rtt);
+        assert_eq!(config.tracking, TrackingConfig::default());
+
+        let json = r#"{ "network": { "enabled": false }, "tracking": { "enabled": false } }"#;
+        let config = EngineConfig::parse(json, ConfigFormat::Json).unwrap();
+        assert!(config.websocket_config().is_none());
+        assert!(!config.tracking.enabled);
+
+        assert_eq!(EngineConfig::parse("", ConfigFormat::Toml).unwrap(), EngineConfig::default());
+        assert!(EngineConfig::parse("[output]\ncallback_rat = 1000.0", ConfigFormat::Toml).is_err(), "unknown fields should be rejected");
+        assert!(EngineConfig::parse("[output]\nbackend = \"record\"", ConfigFormat::Toml).is_err(), "record backend requires a path");
+        assert!(EngineConfig::parse("[safety]\nmax_intensity = 2.0", ConfigFormat::Toml).is_err());
+
+        let toml = "[tracking]\nsource = \"synthetic\"\nsynthetic_motion = { motion = \"static\", position = { x = 0, y = 0, z = 150 } }";
+        let config = EngineConfig::parse(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.tracking.synthetic_motion, crate::SyntheticMotion::Static { position: pattern_evaluator::MAHCoordsConst { x: 0.0, y: 0.0, z: 150.0 } });
+        assert!(EngineConfig::parse("[tracking]\nsource = \"replay\"", ConfigFormat::Toml).is_err(), "replay source requires a path");
+
+        let toml = "[[playback.tracking_bindings]]\nparameter = \"intensity\"\nquantity = \"palm_height\"\ninput = [300, 100]";
+        let config = EngineConfig::parse(toml, ConfigFormat::Toml).unwrap();
+        assert_eq!(config.playback.tracking_bindings[0].quantity, crate::TrackingQuantity::PalmHeight);
+        assert!(config.playback.tracking_bindings[0].output.iter().zip([0.0, 1.0]).all(|(a, b)| (a - b).abs() < f64::EPSILON));
+        assert!(EngineConfig::parse("[[playback.tracking_bindings]]\nparameter = \"p\"\nquantity = \"hand_speed\"\ninput = [0, 0]", ConfigFormat::Toml).is_err(), "empty input range");
+    }
+
+    #[test]
+    fn test_glove_layout() {
+        let named = ["palm_top_center", "palm_top_left", "palm_top_right", "palm_bottom_center", "palm_bottom_left", "palm_bottom_right", "wrist", "thumb",
+            "index_finger_base", "index_finger_tip", "middle_finger_base", "middle_finger_tip", "ring_finger_base", "ring_finger_tip", "little_finger_base"];
+        let layout: Vec<String> = named.iter().map(|n| format!("\"{n}\"")).chain(std::iter::once("{ x = -40.0, y = 58.0, z = 0.0 }".to_string())).collect();
+        let config = EngineConfig::parse(&format!("[glove]\nmax_dist = 20.0\nlayout = [{}]", layout.join(", ")), ConfigFormat::Toml).unwrap();
+        assert_eq!(config.glove.layout.as_ref().map(Vec::len), Some(16));
+
+        let too_short = format!("[glove]\nlayout = [{}]", layout[..15].join(", "));
+        assert!(EngineConfig::parse(&too_short, ConfigFormat::Toml).is_err());
+    }
+}
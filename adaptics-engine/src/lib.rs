@@ -45,11 +45,15 @@ int main() {
 }
 ```
 
+Instead of reading the files itself, the application can also point the engine at a directory of tactons and play them by name, see
+[`adaptics_engine_set_tacton_dir`](crate::adaptics_engine_set_tacton_dir()) and [`adaptics_engine_play_tacton`](crate::adaptics_engine_play_tacton()).
+Edited tactons are reloaded while the application is running.
+
 */
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, self};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::thread;
 use interoptopus::patterns::slice::FFISliceMut;
 use interoptopus::patterns::string::AsciiPointer;
@@ -59,28 +63,75 @@ use pattern_evaluator::{BrushAtAnimLocalTime, PatternEvaluator};
 
 mod threads;
 use threads::pattern::playback;
-pub use playback::PatternEvalUpdate;
+pub use playback::{PatternEvalUpdate, AdapticsEngineEvent};
+pub use threads::pattern::queue::QueueEntry;
+pub use threads::pattern::voice::{VoiceId, DEFAULT_VOICE_ID};
 use threads::streaming;
-use threads::net::websocket;
-pub use websocket::AdapticsWSServerMessage;
+use threads::net::{websocket, osc};
+pub use websocket::{AdapticsWSServerMessage, AdapticsWSClientMessage, WsTopic, WsTopicOptions, WsServerConfig, DEFAULT_ALLOWED_ORIGINS};
+pub use websocket::{AdapticsWSRequest, AdapticsWSRequestBody, AdapticsWSQuery, AdapticsWSResult, AdapticsWSResponse, WS_PROTOCOL_VERSION};
+pub use osc::OscInputConfig;
 use threads::tracking;
 pub use pattern_evaluator::PatternEvaluatorParameters;
 mod util;
 pub use util::AdapticsError;
+mod telemetry;
+use telemetry::Telemetry;
+pub use telemetry::{TelemetrySnapshot, TELEMETRY_INTERVAL};
+mod tacton_library;
+use tacton_library::{TactonLibrary, TactonLibraryWatcher};
+pub use tacton_library::{TactonInfo, TactonLibraryConfig, TACTON_LIBRARY_POLL_INTERVAL};
+mod session_log;
+pub use session_log::{SessionLogConfig, SessionLogLine, SessionLogEntry};
+mod config;
+pub use config::{EngineConfig, ConfigFormat, OutputConfig, OutputBackendKind, NetworkConfig, DebugConfig, DEFAULT_WEBSOCKET_BIND_ADDR};
+pub use threads::pattern::{playback::PlaybackConfig, safety::{SafetyConfig, SafetyBounds}, tracking_bindings::{TrackingBinding, TrackingQuantity}};
+pub use tracking::{TrackingConfig, TrackingSourceKind, TrackedHand, synthetic::SyntheticMotion};
 
 pub mod hapticglove {
     pub type DeviceType = crate::streaming::hapticglove::DeviceType;
+    pub use crate::streaming::hapticglove::{get_possible_serial_ports, GloveConfig, LRAPlacement, LRAPositions, DEFAULT_MAX_DIST};
+}
+
+/// Discovery of the haptic and tracking devices connected to this machine.
+pub mod devices {
     pub use crate::streaming::hapticglove::get_possible_serial_ports;
+    pub use crate::tracking::TrackingStatus;
+    #[cfg(feature = "leapmotion")]
+    pub use crate::tracking::leapmotion::tracking_status;
+    /// Probes the Ultraleap tracking service for `timeout`
+    #[cfg(not(feature = "leapmotion"))]
+    pub fn tracking_status(_timeout: std::time::Duration) -> Result<TrackingStatus, crate::AdapticsError> {
+        Err(crate::AdapticsError::new("adaptics-engine was built without the leapmotion feature"))
+    }
+    #[cfg(feature = "ulhaptics")]
+    pub use crate::streaming::ulhaptics::find_ulhaptics_device;
+    /// Identifier of the first Ultraleap haptic device that supports streaming
+    #[cfg(not(feature = "ulhaptics"))]
+    pub fn find_ulhaptics_device() -> Result<String, crate::AdapticsError> {
+        Err(crate::AdapticsError::new("adaptics-engine was built without the ulhaptics feature"))
+    }
 }
 
-/// The number of seconds between each playback update from the pattern evaluator.
-pub const SECONDS_PER_PLAYBACK_UPDATE: f64 = 1.0 / 60.0;
-const CALLBACK_RATE: f64 = 500.0;
-const DEVICE_UPDATE_RATE: u64 = 20000; //20khz
-const SEND_UNTRACKED_PLAYBACK_UPDATES: bool = false;
+/// Haptic output devices. Implement [`output::OutputBackend`] to drive custom hardware,
+/// and run it with [`run_threads_and_wait_with_output`].
+pub mod output {
+    pub use crate::streaming::output::{OutputBackend, OutputCapabilities, OutputKind, StreamingContext, run_timed_output_loop};
+    pub use crate::streaming::mock::MockOutput;
+    pub use crate::streaming::hapticglove::GloveOutput;
+    pub use crate::streaming::recording::{RecordingOutput, RecordingFormat, RecordingClock, RECORDING_BINARY_MAGIC, RECORDING_BINARY_RECORD_LEN};
+    pub use crate::streaming::udp::{UdpOutput, UdpFormat, UDP_OSC_CONTROL_POINT_ADDRESS, UDP_RAW_MAGIC, UDP_RAW_VERSION, UDP_RAW_HEADER_LEN, UDP_RAW_RECORD_LEN};
+    #[cfg(feature = "ulhaptics")]
+    pub use crate::streaming::ulhaptics::UlhapticsOutput;
+}
+use output::OutputBackend;
 
-const DEBUG_LOG_LAG_EVENTS: bool = false;
-const DEBUG_LOG_SERIAL_RTT: bool = false;
+/// The default number of seconds between each playback update from the pattern evaluator, see [`PlaybackConfig`].
+pub const SECONDS_PER_PLAYBACK_UPDATE: f64 = 1.0 / 60.0;
+/// The default batches of device samples requested per second by the mock and Ultraleap outputs, see [`OutputConfig`].
+pub const CALLBACK_RATE: f64 = 500.0;
+/// The default device samples per second of the mock output, see [`OutputConfig`].
+pub const DEVICE_UPDATE_RATE: u64 = 20000; //20khz
 
 
 /// Handle to the Adaptics Engine threads and channels.
@@ -88,38 +139,159 @@ pub struct AdapticsEngineHandle {
     end_streaming_tx: crossbeam_channel::Sender<()>,
     pattern_eval_handle: thread::JoinHandle<()>,
     patteval_update_tx: crossbeam_channel::Sender<playback::PatternEvalUpdate>,
+    patteval_request_tx: crossbeam_channel::Sender<playback::PatternEvalRequest>,
     ulh_streaming_handle: thread::JoinHandle<Result<(), AdapticsError>>,
     playback_updates_rx: Option<crossbeam_channel::Receiver<websocket::AdapticsWSServerMessage>>,
+    events_rx: crossbeam_channel::Receiver<playback::AdapticsEngineEvent>,
+    telemetry: Arc<Telemetry>,
+    tacton_library: Arc<TactonLibrary>,
+    tacton_library_watcher: Option<TactonLibraryWatcher>,
+}
+
+impl AdapticsEngineHandle {
+    /// Starts the engine threads streaming to `output`, without tracking or playback updates.
+    /// Intended for embedding the engine in Rust applications and tests.
+    pub fn start_with_output(output: Box<dyn OutputBackend>) -> Result<Self, AdapticsError> {
+        Self::start_with_config(output, &EngineConfig::default())
+    }
+
+    /// Same as [`AdapticsEngineHandle::start_with_output`], but uses the playback, safety and debug options of `config`.
+    /// The output, network, tracking and session log options are ignored.
+    pub fn start_with_config(output: Box<dyn OutputBackend>, config: &EngineConfig) -> Result<Self, AdapticsError> {
+        Self::start_with_updates(output, config, Vec::new())
+    }
+
+    /// Same as [`AdapticsEngineHandle::start_with_config`], but applies `initial_updates` before streaming starts,
+    /// e.g. to load and play a pattern from the first sample of a [`output::RecordingClock::Virtual`] recording.
+    /// Fails if one of the updates is rejected.
+    pub fn start_with_updates(output: Box<dyn OutputBackend>, config: &EngineConfig, initial_updates: Vec<PatternEvalUpdate>) -> Result<Self, AdapticsError> {
+        create_threads(output, true, None, Arc::default(), config, None, initial_updates)
+    }
+
+    /// Sends an update to the pattern-eval thread, see [`PatternEvalUpdate`].
+    pub fn update(&self, update: PatternEvalUpdate) -> Result<(), AdapticsError> {
+        Ok(self.patteval_update_tx.send(update)?)
+    }
+
+    /// Same as [`AdapticsEngineHandle::update`], but waits until the update has been applied and returns the reason if it was rejected.
+    pub fn update_and_wait(&self, update: PatternEvalUpdate) -> Result<(), AdapticsError> {
+        apply_update(&self.patteval_request_tx, update).map(|_| ())
+    }
+
+    /// Waits for the pattern-eval thread to answer `query`, see [`AdapticsWSQuery`].
+    pub fn query(&self, query: AdapticsWSQuery) -> Result<AdapticsWSResponse, AdapticsError> {
+        let (reply_tx, reply_rx) = playback::PatternEvalReplyTx::oneshot();
+        self.patteval_request_tx.send(playback::PatternEvalRequest::Query { query, reply_tx })?;
+        let (_, result) = reply_rx.recv()?;
+        result.map_err(|e| AdapticsError::new(&e))
+    }
+
+    /// Takes the oldest pending engine event, if any.
+    #[must_use]
+    pub fn try_recv_event(&self) -> Option<AdapticsEngineEvent> {
+        self.events_rx.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next engine event.
+    #[must_use]
+    pub fn recv_event_timeout(&self, timeout: std::time::Duration) -> Option<AdapticsEngineEvent> {
+        self.events_rx.recv_timeout(timeout).ok()
+    }
+
+    /// false once the streaming thread has exited (e.g. because the output device failed), see [`AdapticsEngineHandle::shutdown`] for the error.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        !self.ulh_streaming_handle.is_finished()
+    }
+
+    #[must_use]
+    pub fn telemetry(&self) -> TelemetrySnapshot {
+        self.telemetry.snapshot()
+    }
+
+    /// Loads the tactons in `dir` into the tacton library, replacing the previous directory.
+    /// The directory is polled for changes every [`TACTON_LIBRARY_POLL_INTERVAL`], see [`PatternEvalUpdate::PlayTacton`].
+    pub fn set_tacton_dir(&mut self, dir: std::path::PathBuf) -> Result<(), AdapticsError> {
+        self.tacton_library.set_dir(dir)?;
+        if self.tacton_library_watcher.is_none() {
+            self.tacton_library_watcher = Some(TactonLibraryWatcher::start(self.tacton_library.clone(), self.patteval_request_tx.clone())?);
+        }
+        Ok(())
+    }
+
+    /// Stops the engine threads and waits for them to exit.
+    pub fn shutdown(self) -> Result<(), AdapticsError> {
+        let AdapticsEngineHandle { end_streaming_tx, pattern_eval_handle, patteval_update_tx, ulh_streaming_handle, tacton_library_watcher, .. } = self;
+        if let Some(watcher) = tacton_library_watcher { watcher.stop(); }
+        drop(patteval_update_tx);
+        end_streaming_tx.send(()).ok(); // ignore send error (if thread already exited)
+        pattern_eval_handle.join().map_err(|_| AdapticsError::new("pattern-eval thread panicked"))?;
+        ulh_streaming_handle.join().map_err(|_| AdapticsError::new("streaming thread panicked"))?
+    }
+}
+
+#[cfg(feature = "ulhaptics")]
+#[allow(clippy::unnecessary_wraps)] // fails without the ulhaptics feature
+fn ulhaptics_output(callback_rate: f64) -> Result<Box<dyn OutputBackend>, AdapticsError> {
+    #[allow(clippy::cast_possible_truncation)]
+    Ok(Box::new(output::UlhapticsOutput::new(callback_rate as f32)))
+}
+#[cfg(not(feature = "ulhaptics"))]
+fn ulhaptics_output(_callback_rate: f64) -> Result<Box<dyn OutputBackend>, AdapticsError> {
+    Err(AdapticsError::new("adaptics-engine was built without the ulhaptics feature, use mock streaming or a vibrotactile grid device instead"))
+}
+
+/// Sends `update` to the pattern-eval thread and waits until it has been applied, returning the reason if it was rejected
+fn apply_update(patteval_request_tx: &crossbeam_channel::Sender<playback::PatternEvalRequest>, update: PatternEvalUpdate) -> Result<AdapticsWSResponse, AdapticsError> {
+    let (reply_tx, reply_rx) = playback::PatternEvalReplyTx::oneshot();
+    patteval_request_tx.send(playback::PatternEvalRequest::Update { update, reply_tx: Some(reply_tx) })?;
+    let (_, result) = reply_rx.recv()?;
+    result.map_err(|e| AdapticsError::new(&e))
 }
 
 fn create_threads(
-    use_mock_streaming: bool,
+    mut output: Box<dyn OutputBackend>,
     disable_playback_updates: bool,
-    vib_grid: Option<hapticglove::DeviceType>,
     tracking_data_rx: Option<crossbeam_channel::Receiver<tracking::TrackingFrame>>,
+    telemetry: Arc<Telemetry>,
+    config: &EngineConfig,
+    session_log: Option<session_log::SessionLogger>,
+    initial_updates: Vec<PatternEvalUpdate>,
 ) -> Result<AdapticsEngineHandle, AdapticsError> {
     let (patteval_call_tx, patteval_call_rx) = crossbeam_channel::bounded(1);
     let (patteval_update_tx, patteval_update_rx) = crossbeam_channel::bounded(1);
+    let (patteval_request_tx, patteval_request_rx) = crossbeam_channel::bounded(1);
     let (patteval_return_tx, patteval_return_rx) = crossbeam_channel::bounded::<Vec<BrushAtAnimLocalTime>>(0);
     let (playback_updates_tx, playback_updates_rx) = if disable_playback_updates { (None, None) } else { let (t,r) = crossbeam_channel::bounded(1); (Some(t), Some(r)) };
 
+    let (events_tx, events_rx) = crossbeam_channel::bounded(256);
     let (end_streaming_tx, end_streaming_rx) = crossbeam_channel::bounded(1);
 
     // thread_priority::set_current_thread_priority(thread_priority::ThreadPriority::Max).unwrap();
 
+    let pattern_eval_telemetry = telemetry.clone();
+    let tacton_library = Arc::<TactonLibrary>::default();
+    let pattern_eval_tacton_library = tacton_library.clone();
+    let EngineConfig { playback: playback_config, safety, debug, .. } = config.clone();
     let pattern_eval_handle = thread::Builder::new()
         .name("pattern-eval".to_string())
         .spawn(move || {
             println!("pattern-eval thread starting...");
 
             let res = playback::pattern_eval_loop(
-                SECONDS_PER_PLAYBACK_UPDATE,
-                SEND_UNTRACKED_PLAYBACK_UPDATES,
+                &playback_config,
+                &safety,
+                debug,
                 &patteval_call_rx,
                 &patteval_update_rx,
+                &patteval_request_rx,
                 &patteval_return_tx,
                 playback_updates_tx.as_ref(),
                 tracking_data_rx.as_ref(),
+                &events_tx,
+                &pattern_eval_telemetry,
+                &pattern_eval_tacton_library,
+                session_log.as_ref(),
             );
 
             // res.unwrap();
@@ -129,100 +301,158 @@ fn create_threads(
         })
         .unwrap();
 
-    let ulh_streaming_handle =  if use_mock_streaming {
-        println!("using mock streaming");
-        thread::Builder::new()
-            .name("mock-streaming".to_string())
-            .spawn(move || {
-                println!("mock streaming thread starting...");
-
-                streaming::mock::start_mock_emitter(
-                    DEVICE_UPDATE_RATE,
-                    CALLBACK_RATE,
-                    &patteval_call_tx,
-                    &patteval_return_rx,
-                    &end_streaming_rx,
-                );
+    // applied before the streaming thread requests the first batch (the pattern-eval thread exits if one is rejected, since the channels are dropped)
+    for update in initial_updates {
+        apply_update(&patteval_request_tx, update)?;
+    }
 
-                // println!("mock streaming thread exiting...");
-                Ok(())
-            })?
-    } else if let Some(vg_device) = vib_grid {
-        thread::Builder::new()
-            .name("vib-grid".to_string())
-            .spawn(move || -> Result<(), AdapticsError> {
-                println!("vib-grid thread starting...");
-                streaming::hapticglove::start_streaming_emitter(&vg_device, &patteval_call_tx, &patteval_return_rx, &end_streaming_rx)
-            })?
-    } else {
-        thread::Builder::new()
-            .name("ulh-streaming".to_string())
-            .spawn(move || -> Result<(), AdapticsError> {
-                println!("ulhaptics streaming thread starting...");
-
-                #[allow(clippy::cast_possible_truncation)]
-                streaming::ulhaptics::start_streaming_emitter(
-                    CALLBACK_RATE as f32,
-                    patteval_call_tx,
-                    patteval_return_rx,
-                    &end_streaming_rx,
-                )
-            })?
-    };
+    let streaming_ctx = output::StreamingContext::new(patteval_call_tx, patteval_return_rx, end_streaming_rx, telemetry.clone(), debug);
+    let output_name = output.capabilities().name;
+    let ulh_streaming_handle = thread::Builder::new()
+        .name(format!("{output_name}-streaming"))
+        .spawn(move || -> Result<(), AdapticsError> {
+            println!("{output_name} streaming thread starting...");
+            output.start()?;
+            let res = output.run(&streaming_ctx);
+            println!("{output_name} streaming thread exiting...");
+            res
+        })?;
 
     Ok(AdapticsEngineHandle {
         end_streaming_tx,
         pattern_eval_handle,
         patteval_update_tx,
+        patteval_request_tx,
         ulh_streaming_handle,
         playback_updates_rx,
+        events_rx,
+        telemetry,
+        tacton_library,
+        tacton_library_watcher: None,
     })
 }
 
 
+fn spawn_tracking_thread(mut source: Box<dyn tracking::source::TrackingSource>, tracking_ctx: tracking::source::TrackingContext) -> std::io::Result<thread::JoinHandle<Result<(), AdapticsError>>> {
+    let source_name = source.name();
+    thread::Builder::new()
+        .name(format!("{source_name}-tracking"))
+        .spawn(move || -> Result<(), AdapticsError> {
+            println!("{source_name} tracking thread starting...");
+            source.start()?;
+            let res = source.run(&tracking_ctx);
+            println!("{source_name} tracking thread exiting...");
+            res
+        })
+}
+
 /// Runs the main threads and waits for them to exit.
 /// This is the main function for the CLI.
 ///
+/// Streams to the output backend selected in `config` (see [`EngineConfig::output_backend`]).
+/// If enabled in `config.network`, the engine is controlled by websocket clients (see [`WsServerConfig`]) and OSC messages (see [`OscInputConfig`]).
+///
+/// If `telemetry_file` is provided, a [`TelemetrySnapshot`] is appended to it as a line of JSON every [`TELEMETRY_INTERVAL`].
+///
+/// If `tacton_library` is provided, the tactons in its directory can be played by name, see [`TactonLibraryConfig`].
+///
 /// # Panics
 /// Will panic if any of the threads panic (because panic may not not be `dyn std::error::Error + Send + Sync`).
 pub fn run_threads_and_wait(
-    use_mock_streaming: bool,
-    websocket_bind_addr: Option<String>,
-    enable_tracking: bool,
-    vib_grid: Option<hapticglove::DeviceType>,
+    config: &EngineConfig,
+    telemetry_file: Option<std::path::PathBuf>,
+    tacton_library: Option<TactonLibraryConfig>,
+) -> Result<(), AdapticsError> {
+    run_threads_and_wait_with_output(config.output_backend()?, config, telemetry_file, tacton_library)
+}
+
+/// Same as [`run_threads_and_wait`], but streams to a custom output backend (the output options of `config` are ignored).
+///
+/// # Panics
+/// Will panic if any of the threads panic (because panic may not not be `dyn std::error::Error + Send + Sync`).
+pub fn run_threads_and_wait_with_output(
+    output: Box<dyn OutputBackend>,
+    config: &EngineConfig,
+    telemetry_file: Option<std::path::PathBuf>,
+    tacton_library: Option<TactonLibraryConfig>,
 ) -> Result<(), AdapticsError> {
+    let websocket_config = config.websocket_config();
+    let osc_input = config.osc_input();
+    let enable_tracking = config.tracking.enabled;
 
     let (tracking_data_tx, tracking_data_rx) = if enable_tracking { let (s, r) = crossbeam_channel::bounded(1); (Some(s), Some(r)) } else { (None, None) };
 
+    let (mut session_logger, session_log_handle) = if let Some((logger, writer)) = session_log::session_log(&config.session_log)? {
+        let thread = thread::Builder::new()
+            .name("session-log".to_string())
+            .spawn(move || writer.run())?; // exits once all loggers are dropped
+        (Some(logger), Some(thread))
+    } else { (None, None) };
+
     let AdapticsEngineHandle {
         end_streaming_tx,
         pattern_eval_handle,
         patteval_update_tx,
+        patteval_request_tx,
         ulh_streaming_handle,
         playback_updates_rx,
-    } = create_threads(use_mock_streaming, websocket_bind_addr.is_none(), vib_grid, tracking_data_rx)?;
+        events_rx,
+        telemetry,
+        tacton_library: library,
+        ..
+    } = create_threads(output, websocket_config.is_none(), tracking_data_rx, Arc::default(), config, session_logger.clone(), Vec::new())?;
+
+    let tacton_library_watcher = if let Some(TactonLibraryConfig { dir, play }) = tacton_library {
+        library.set_dir(dir)?;
+        let watcher = TactonLibraryWatcher::start(library, patteval_request_tx.clone())?;
+        if let Some(name) = play {
+            patteval_update_tx.send(PatternEvalUpdate::PlayTacton { name })?;
+        }
+        Some(watcher)
+    } else { None };
+
+    let (end_osc_tx, end_osc_rx) = crossbeam_channel::bounded(1);
+    let osc_handle = if let Some(osc_input) = osc_input {
+        let patteval_update_tx = patteval_update_tx.clone();
+        let thread = thread::Builder::new()
+            .name("osc".to_string())
+            .spawn(move || -> Result<(), AdapticsError> {
+                println!("osc thread starting...");
+                let res = osc::start_osc_server(&osc_input, &patteval_update_tx, &end_osc_rx);
+                println!("osc thread exiting...");
+                res
+            })?;
+        Some(thread)
+    } else { None };
 
-    let (net_handle_opt, tracking_data_ws_tx) = if let Some(websocket_bind_addr) = websocket_bind_addr {
+    let (net_handle_opt, tracking_data_ws_tx) = if let Some(websocket_config) = websocket_config {
         let (tracking_data_ws_tx, tracking_data_ws_rx) = if enable_tracking { let (s, r) = crossbeam_channel::bounded(1); (Some(s), Some(r)) } else { (None, None) };
         let playback_updates_rx = playback_updates_rx.ok_or(AdapticsError::new("playback_updates_rx must be available when using the websocket server"))?;
+        let telemetry = telemetry.clone();
+        let patteval_request_tx = patteval_request_tx.clone();
         let thread = thread::Builder::new()
             .name("net".to_string())
             .spawn(move || {
                 println!("net thread starting...");
-                websocket::start_ws_server(&websocket_bind_addr, &patteval_update_tx, playback_updates_rx, tracking_data_ws_rx);
+                websocket::start_ws_server(&websocket_config, &patteval_request_tx, playback_updates_rx, tracking_data_ws_rx, events_rx, &telemetry);
                 println!("net thread thread exiting...");
             })?;
         (Some(thread), tracking_data_ws_tx)
     } else { Default::default() };
 
     let (end_tracking_tx, end_tracking_rx) = crossbeam_channel::bounded(1);
-    let lmc_tracking_handle = if let Some(tracking_data_tx) = tracking_data_tx {
+    let tracking_handle = if let Some(tracking_data_tx) = tracking_data_tx {
+        let tracking_ctx = tracking::source::TrackingContext::new(tracking_data_tx, tracking_data_ws_tx, end_tracking_rx, telemetry.clone(), config.debug, session_logger.take());
+        Some(spawn_tracking_thread(config.tracking.tracking_source()?, tracking_ctx)?)
+    } else { None };
+
+    drop(session_logger); // the session log thread exits once the other threads dropped their loggers
+
+    let (end_telemetry_file_tx, end_telemetry_file_rx) = crossbeam_channel::bounded(1);
+    let telemetry_file_handle = if let Some(telemetry_file) = telemetry_file {
         let thread = thread::Builder::new()
-            .name("lmc-tracking".to_string())
-            .spawn(move || -> Result<(), AdapticsError> {
-                println!("tracking thread starting...");
-                tracking::leapmotion::start_tracking_loop(tracking_data_tx, tracking_data_ws_tx, &end_tracking_rx)
-            })?;
+            .name("telemetry-file".to_string())
+            .spawn(move || telemetry::telemetry_file_loop(&telemetry, &telemetry_file, &end_telemetry_file_rx))?;
         Some(thread)
     } else { None };
 
@@ -231,12 +461,28 @@ pub fn run_threads_and_wait(
 
     pattern_eval_handle.join().unwrap();
 
+    if let Some(tacton_library_watcher) = tacton_library_watcher { tacton_library_watcher.stop(); }
+
     end_streaming_tx.send(()).ok(); // ignore send error (if thread already exited)
     ulh_streaming_handle.join().unwrap()?; // unwrap panics, return errors
 
-    if let Some(lmc_tracking_handle) = lmc_tracking_handle {
+    if let Some(tracking_handle) = tracking_handle {
         end_tracking_tx.send(()).ok(); // ignore send error (if thread already exited)
-        lmc_tracking_handle.join().unwrap()?; // unwrap panics, return errors
+        tracking_handle.join().unwrap()?; // unwrap panics, return errors
+    }
+
+    if let Some(session_log_handle) = session_log_handle {
+        session_log_handle.join().unwrap()?; // unwrap panics, return errors
+    }
+
+    if let Some(telemetry_file_handle) = telemetry_file_handle {
+        end_telemetry_file_tx.send(()).ok(); // ignore send error (if thread already exited)
+        telemetry_file_handle.join().unwrap()?; // unwrap panics, return errors
+    }
+
+    if let Some(osc_handle) = osc_handle {
+        end_osc_tx.send(()).ok(); // ignore send error (if thread already exited)
+        osc_handle.join().unwrap()?; // unwrap panics, return errors
     }
 
     println!("waiting for net thread...");
@@ -270,6 +516,7 @@ pub enum FFIError {
     CastError = 17,
     AdapticsError = 18,
     ErrMsgBufferNull = 19,
+    OutputBufferTooSmall = 20,
 }
 // Gives special meaning to some of your error variants.
 impl interoptopus::patterns::result::FFIError for FFIError {
@@ -302,6 +549,7 @@ impl FFIError {
             FFIError::CastError => "Error casting between types (e.g. from usize to u32).",
             FFIError::AdapticsError => "An error occurred. Further error information could not be marshalled but may be available with debug builds.",
             FFIError::ErrMsgBufferNull => "Error message buffer had no length.",
+            FFIError::OutputBufferTooSmall => "Output buffer is too small for the null-terminated result.",
         }
     }
 }
@@ -364,12 +612,12 @@ impl From<AdapticsError> for FFIError {
 pub struct AdapticsEngineHandleFFI {
     last_error_msg: Option<String>,
     aeh: AdapticsEngineHandle,
-    lmc_tracking_handle: Option<thread::JoinHandle<Result<(), AdapticsError>>>,
+    tracking_handle: Option<thread::JoinHandle<Result<(), AdapticsError>>>,
     end_tracking_tx: crossbeam_channel::Sender<()>,
 }
 impl AdapticsEngineHandleFFI {
-    fn new(aeh: AdapticsEngineHandle, lmc_tracking_handle: Option<thread::JoinHandle<Result<(), AdapticsError>>>, end_tracking_tx: crossbeam_channel::Sender<()>) -> Self {
-        Self { aeh, last_error_msg: None, lmc_tracking_handle, end_tracking_tx }
+    fn new(aeh: AdapticsEngineHandle, tracking_handle: Option<thread::JoinHandle<Result<(), AdapticsError>>>, end_tracking_tx: crossbeam_channel::Sender<()>) -> Self {
+        Self { aeh, last_error_msg: None, tracking_handle, end_tracking_tx }
     }
 }
 
@@ -440,6 +688,16 @@ macro_rules! get_handle_from_id {
         let $handle = rguard.as_ref().ok_or(FFIError::HandleIDNotFound)?.get(&$handle_id).ok_or(FFIError::HandleIDNotFound)?;
     };
 }
+/// Copies `json` into `buffer` as a null-terminated string.
+fn write_json_to_ffi_buffer(json: &str, buffer: &mut FFISliceMut<u8>) -> Result<(), FFIError> {
+    let buffer = buffer.as_slice_mut();
+    let json_bytes = json.as_bytes();
+    if buffer.len() <= json_bytes.len() { return Err(FFIError::OutputBufferTooSmall); }
+    buffer[..json_bytes.len()].copy_from_slice(json_bytes);
+    buffer[json_bytes.len()] = 0; // null terminate
+    Ok(())
+}
+
 macro_rules! deserialize_json_parameter {
     ($asciiptr:ident) => {
         if let Some(cstr) = $asciiptr.as_c_str() {
@@ -470,34 +728,32 @@ impl FFIHandle {
     ///
     #[ffi_service_ctor]
     pub fn init_experimental(use_mock_streaming: bool, enable_playback_updates: bool, vib_grid: AsciiPointer, enable_ultraleap_tracking: bool) -> Result<Self, FFIError> {
-        let vg = match vib_grid.as_str() {
-            Ok("") | Err(interoptopus::Error::Null) => None,
-            Ok("auto") => Some(hapticglove::DeviceType::Auto),
-            Ok(s) => Some(hapticglove::DeviceType::SerialPort(s.to_string())),
+        let mut config = EngineConfig::default();
+        match vib_grid.as_str() {
+            Ok("") | Err(interoptopus::Error::Null) => {},
+            Ok(port) => { config.output.backend = OutputBackendKind::VibGrid; config.output.vib_grid_port = port.to_string(); },
 
             Err(interoptopus::Error::UTF8(_)) => { return Err(FFIError::ParamUTF8Error) },
-            Err(e) => { eprintln!("WARN(AdapticsEngine): unexpected error {e}"); None }, //unreachable!(),
-        };
+            Err(e) => { eprintln!("WARN(AdapticsEngine): unexpected error {e}"); }, //unreachable!(),
+        }
+        if use_mock_streaming { config.output.backend = OutputBackendKind::Mock; }
 
+        let telemetry = Arc::<Telemetry>::default();
         let (tracking_data_tx, tracking_data_rx) = if enable_ultraleap_tracking { let (s, r) = crossbeam_channel::bounded(1); (Some(s), Some(r)) } else { (None, None) };
         let (end_tracking_tx, end_tracking_rx) = crossbeam_channel::bounded(1);
-        let lmc_tracking_handle = if let Some(tracking_data_tx) = tracking_data_tx {
-            let thread = thread::Builder::new()
-                .name("lmc-tracking".to_string())
-                .spawn(move || -> Result<(), AdapticsError> {
-                    println!("tracking thread starting...");
-                    tracking::leapmotion::start_tracking_loop(tracking_data_tx, None, &end_tracking_rx)
-                }).map_err(|e| {
-                    eprintln!("[ERROR] lmc-tracking thread panicked: {e}");
-                    FFIError::Panic
-                })?;
+        let tracking_handle = if let Some(tracking_data_tx) = tracking_data_tx {
+            let tracking_ctx = tracking::source::TrackingContext::new(tracking_data_tx, None, end_tracking_rx, telemetry.clone(), config.debug, None);
+            let thread = spawn_tracking_thread(config.tracking.tracking_source()?, tracking_ctx).map_err(|e| {
+                eprintln!("[ERROR] failed to spawn tracking thread: {e}");
+                FFIError::Panic
+            })?;
             Some(thread)
         } else { None };
 
-        let aeh = create_threads(use_mock_streaming, !enable_playback_updates, vg, tracking_data_rx)?;
-        let ffi_handle = AdapticsEngineHandleFFI::new(aeh, lmc_tracking_handle, end_tracking_tx);
+        let aeh = create_threads(config.output_backend()?, !enable_playback_updates, tracking_data_rx, telemetry, &config, None, Vec::new())?;
+        let ffi_handle = AdapticsEngineHandleFFI::new(aeh, tracking_handle, end_tracking_tx);
 
-        ffi_handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Tracking { enabled: enable_ultraleap_tracking })?;
+        ffi_handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Tracking { enabled: enable_ultraleap_tracking, hand: None })?;
 
         // get map or create new map
         let mut map = ENGINE_HANDLE_MAP.write().or(Err(FFIError::MutexPoisoned))?;
@@ -527,13 +783,14 @@ impl FFIHandle {
         let map = rwlg.as_mut().ok_or(FFIError::HandleIDNotFound)?;
         let handle = map.remove(&self.handle_id).ok_or(FFIError::HandleIDNotFound)?;
 
+        if let Some(watcher) = handle.aeh.tacton_library_watcher { watcher.stop(); }
         handle.end_tracking_tx.send(()).ok(); // ignore send error (if thread already exited)
-        if let Some(lmc_handle) = handle.lmc_tracking_handle {
-            lmc_handle.join().map_err(|e| {
-                eprintln!("[ERROR] lmc-tracking thread panicked: {e:?}");
+        if let Some(tracking_handle) = handle.tracking_handle {
+            tracking_handle.join().map_err(|e| {
+                eprintln!("[ERROR] tracking thread panicked: {e:?}");
                 FFIError::Panic
             })?.map_err(|e| {
-                eprintln!("[ERROR] lmc-tracking thread error: {e:?}");
+                eprintln!("[ERROR] tracking thread error: {e:?}");
                 FFIError::AdapticsError
             })?;
         }
@@ -563,7 +820,15 @@ impl FFIHandle {
     /// For further information, see [`PatternEvalUpdate::Pattern`].
     pub fn update_pattern(&self, pattern_json: AsciiPointer) -> Result<(), FFIError> {
         get_handle_from_id!(handle <- self.handle_id);
-        handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Pattern { pattern_json: pattern_json.as_str()?.to_owned() })?;
+        handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Pattern { pattern_json: pattern_json.as_str()?.to_owned(), crossfade_ms: None, path_interpolation_ms: None })?;
+        Ok(())
+    }
+    /// Updates the pattern to be played, blending from the currently playing pattern.
+    /// The intensity is crossfaded over `crossfade_ms`, and the position is interpolated over `path_interpolation_ms` (0 to disable either).
+    /// For further information, see [`PatternEvalUpdate::Pattern`].
+    pub fn update_pattern_with_crossfade(&self, pattern_json: AsciiPointer, crossfade_ms: f64, path_interpolation_ms: f64) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Pattern { pattern_json: pattern_json.as_str()?.to_owned(), crossfade_ms: Some(crossfade_ms), path_interpolation_ms: Some(path_interpolation_ms) })?;
         Ok(())
     }
     /// Alias for [`crate::adaptics_engine_update_pattern()`]
@@ -582,6 +847,41 @@ impl FFIHandle {
         Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Playstart { playstart, playstart_offset })?)
     }
 
+    /// Starts playback at `pattern_time` (in milliseconds).
+    /// For further information, see [`PatternEvalUpdate::Play`].
+    pub fn play(&self, pattern_time: f64) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Play { pattern_time })?)
+    }
+
+    /// Pauses playback, freezing the pattern time.
+    /// For further information, see [`PatternEvalUpdate::Pause`].
+    pub fn pause(&self) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Pause {})?)
+    }
+
+    /// Resumes playback from the pattern time it was paused at.
+    /// For further information, see [`PatternEvalUpdate::Resume`].
+    pub fn resume(&self) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Resume {})?)
+    }
+
+    /// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused.
+    /// For further information, see [`PatternEvalUpdate::Seek`].
+    pub fn seek(&self, pattern_time: f64) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Seek { pattern_time })?)
+    }
+
+    /// Stops playback and rewinds to the start of the pattern.
+    /// For further information, see [`PatternEvalUpdate::Stop`].
+    pub fn stop(&self) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::Stop {})?)
+    }
+
     /// Used to update all `evaluator_params`.
     ///
     /// Accepts a JSON string representing the evaluator parameters. See [`PatternEvaluatorParameters`].
@@ -629,6 +929,25 @@ impl FFIHandle {
         Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::UserParameter { name: name.as_str()?.to_owned(), value })?)
     }
 
+    /// Updates the user parameter automation tracks applied on top of the current pattern.
+    /// Accepts a JSON string in the format `{ [key: string]: { points: { time: double, value: double, transition: MAHTransition }[] } }`.
+    /// For further information, see [`PatternEvalUpdate::UserParameterAutomation`].
+    pub fn update_user_parameter_automation(&self, automation_json: AsciiPointer) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let automation = deserialize_json_parameter!(automation_json);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::UserParameterAutomation { automation })?)
+    }
+
+    /// Writes the user parameter definitions of `pattern_json` into `definitions_json` as a null-terminated JSON string
+    /// in the format `{ [key: string]: MAHUserParameterDefinition }` (see [`pattern_evaluator::MAHUserParameterDefinition`]).
+    ///
+    /// Intended for building UIs for the user parameters of a tacton (type, unit, description, etc.).
+    pub fn get_user_parameter_definitions(&self, pattern_json: AsciiPointer, mut definitions_json: FFISliceMut<u8>) -> Result<(), FFIError> {
+        let pattern: pattern_evaluator::MidAirHapticsAnimationFileFormat = deserialize_json_parameter!(pattern_json);
+        let json = serde_json::to_string(&pattern.user_parameter_definitions).or(Err(FFIError::OtherError))?;
+        write_json_to_ffi_buffer(&json, &mut definitions_json)
+    }
+
     /// Updates `geo_matrix`, a 4x4 matrix in row-major order, where `data[3]` is the fourth element of the first row (translate x).
     /// For further information, see [`PatternEvalUpdate::GeoTransformMatrix`].
     pub fn update_geo_transform_matrix(&self, geo_matrix: &GeoMatrix) -> Result<(), FFIError> {
@@ -669,7 +988,7 @@ impl FFIHandle {
                         *num_evals = u32::try_from(evalresults_to_copy)?;
                         Ok(())
                     },
-                    Ok(AdapticsWSServerMessage::TrackingData { .. }) | // ignore tracking data
+                    Ok(AdapticsWSServerMessage::TrackingData { .. } | AdapticsWSServerMessage::Event { .. } | AdapticsWSServerMessage::Telemetry { .. } | AdapticsWSServerMessage::Response { .. }) | // ignore tracking data, events (see poll_event), telemetry (see get_telemetry) and responses (only sent to websocket clients)
                     Err(crossbeam_channel::TryRecvError::Empty) => {
                         *num_evals = 0;
                         Ok(())
@@ -682,13 +1001,126 @@ impl FFIHandle {
     }
 
 
+    /// Adds a tacton to the end of the playback queue.
+    /// Accepts a JSON string representing the queue entry. See [`QueueEntry`].
+    /// For further information, see [`PatternEvalUpdate::QueueEnqueue`].
+    pub fn queue_enqueue(&self, entry_json: AsciiPointer) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let entry = deserialize_json_parameter!(entry_json);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::QueueEnqueue { entry })?)
+    }
+
+    /// Removes all tactons from the playback queue and stops the current queue entry.
+    /// For further information, see [`PatternEvalUpdate::QueueClear`].
+    pub fn queue_clear(&self) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::QueueClear {})?)
+    }
+
+    /// Takes the oldest pending engine event (see [`AdapticsEngineEvent`]) and writes it into `event_json` as a null-terminated JSON string.
+    /// `has_event` will be set to false if there are no pending events.
+    ///
+    /// Events are dropped if they are not polled, so this should be called regularly (e.g. every frame).
+    ///
+    /// # Safety
+    /// `has_event` must be a valid pointer to a bool
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // cant mark unsafe because it breaks interoptopus macro
+    pub fn poll_event(&self, mut event_json: FFISliceMut<u8>, has_event: *mut bool) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let has_event = deref_check_null!(has_event);
+        match handle.aeh.events_rx.try_recv() {
+            Ok(event) => {
+                let json = serde_json::to_string(&event).or(Err(FFIError::OtherError))?;
+                write_json_to_ffi_buffer(&json, &mut event_json)?;
+                *has_event = true;
+                Ok(())
+            },
+            Err(crossbeam_channel::TryRecvError::Empty) => {
+                *has_event = false;
+                Ok(())
+            },
+            Err(crossbeam_channel::TryRecvError::Disconnected) => Err(FFIError::AdapticsEngineThreadDisconnectedCheckDeinitForMoreInfo),
+        }
+    }
+
+
+    /// Writes a snapshot of the engine's runtime telemetry into `telemetry_json` as a null-terminated JSON string.
+    /// See [`TelemetrySnapshot`] for the format.
+    pub fn get_telemetry(&self, mut telemetry_json: FFISliceMut<u8>) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let json = serde_json::to_string(&handle.aeh.telemetry.snapshot()).or(Err(FFIError::OtherError))?;
+        write_json_to_ffi_buffer(&json, &mut telemetry_json)
+    }
+
+
+    /// Starts playing a tacton on a new voice, concurrently with the default voice and any other voices.
+    /// `voice_id` will be set to the id of the new voice, which can be used with [`adaptics_engine_stop_voice()`] and [`adaptics_engine_update_voice_user_parameter()`].
+    ///
+    /// The focal point is time-multiplexed between all playing voices, `priority` (>= 1) weights the share of the device samples this voice gets.
+    /// For further information, see [`PatternEvalUpdate::VoicePlay`].
+    ///
+    /// # Safety
+    /// `voice_id` must be a valid pointer to a u32
+    #[allow(clippy::not_unsafe_ptr_arg_deref)] // cant mark unsafe because it breaks interoptopus macro
+    pub fn play_voice(&self, tacton_json: AsciiPointer, priority: u32, voice_id: *mut u32) -> Result<(), FFIError> {
+        let voice_id = deref_check_null!(voice_id);
+        let update = PatternEvalUpdate::VoicePlay { voice_id: None, pattern_json: tacton_json.as_str()?.to_owned(), priority, user_parameters: HashMap::new() };
+        // release the handle map before waiting for the pattern-eval thread, so deinit is not blocked
+        let patteval_request_tx = {
+            get_handle_from_id!(handle <- self.handle_id);
+            handle.aeh.patteval_request_tx.clone()
+        };
+        // the pattern-eval thread picks the voice id, since ids can also be chosen by websocket clients
+        let AdapticsWSResponse::VoiceStarted { voice_id: new_voice_id } = apply_update(&patteval_request_tx, update)? else { return Err(FFIError::OtherError); };
+        *voice_id = new_voice_id;
+        Ok(())
+    }
+
+    /// Stops a voice started with [`adaptics_engine_play_voice()`]. Voice 0 stops the default voice.
+    /// For further information, see [`PatternEvalUpdate::VoiceStop`].
+    pub fn stop_voice(&self, voice_id: u32) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::VoiceStop { voice_id })?)
+    }
+
+    /// Updates a single user parameter of a voice. Voice 0 updates the default voice.
+    /// For further information, see [`PatternEvalUpdate::VoiceUserParameters`].
+    pub fn update_voice_user_parameter(&self, voice_id: u32, name: AsciiPointer, value: f64) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let user_parameters = HashMap::from([(name.as_str()?.to_owned(), value)]);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::VoiceUserParameters { voice_id, user_parameters })?)
+    }
+
+
+    /// Loads the tactons (`.adaptics` files) in `tacton_dir` into the tacton library, replacing the previous directory.
+    /// Edited tactons are reloaded automatically, including the one that is playing.
+    pub fn set_tacton_dir(&self, tacton_dir: AsciiPointer) -> Result<(), FFIError> {
+        let mut wguard = ENGINE_HANDLE_MAP.write().or(Err(FFIError::MutexPoisoned))?;
+        let handle = wguard.as_mut().ok_or(FFIError::HandleIDNotFound)?.get_mut(&self.handle_id).ok_or(FFIError::HandleIDNotFound)?;
+        handle.aeh.set_tacton_dir(tacton_dir.as_str()?.into())?;
+        Ok(())
+    }
+
+    /// Plays a tacton from the tacton library (see [`adaptics_engine_set_tacton_dir()`]) from the start, by file name or by the name stored in the tacton.
+    /// For further information, see [`PatternEvalUpdate::PlayTacton`].
+    pub fn play_tacton(&self, name: AsciiPointer) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        Ok(handle.aeh.patteval_update_tx.send(PatternEvalUpdate::PlayTacton { name: name.as_str()?.to_owned() })?)
+    }
+
+    /// Writes the tactons in the tacton library into `tactons_json` as a null-terminated JSON array of [`TactonInfo`].
+    pub fn get_tactons(&self, mut tactons_json: FFISliceMut<u8>) -> Result<(), FFIError> {
+        get_handle_from_id!(handle <- self.handle_id);
+        let json = serde_json::to_string(&handle.aeh.tacton_library.list()).or(Err(FFIError::OtherError))?;
+        write_json_to_ffi_buffer(&json, &mut tactons_json)
+    }
+
+
     /// Higher level function to load a new pattern and instantly start playback.
     pub fn adaptics_engine_play_tacton_immediate(&self, tacton_json: AsciiPointer) -> Result<(), FFIError> {
         self.update_pattern(tacton_json)?;
         self.reset_parameters()?;
-        let playstart = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).or(Err(FFIError::TimeError))?.as_secs_f64() * 1000.0;
-        let playstart_offset = 0.0;
-        self.update_playstart(playstart, playstart_offset)
+        self.play(0.0)
     }
 }
 }
@@ -713,11 +1145,16 @@ pub fn ffi_inventory() -> Inventory {
 }
 
 
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod test_common;
+
 #[cfg(test)]
 mod test {
     use std::{ffi::CString, time::{UNIX_EPOCH, SystemTime}};
 
     use crate::*;
+    use crate::test_common::TestPattern;
 
     fn assert_good_deinit(eh: &FFIHandle) {
         let err_msg_u8 = &mut [0u8; 1024];
@@ -761,15 +1198,7 @@ mod test {
 
 
         {
-            let pat = pattern_evaluator::MidAirHapticsAnimationFileFormat {
-                data_format: pattern_evaluator::MidAirHapticsAnimationFileFormatDataFormatName::DataFormat,
-                revision: pattern_evaluator::DataFormatRevision::CurrentRevision,
-                name: "DEFAULT_PATTERN".to_string(),
-                keyframes: vec![],
-                pattern_transform: pattern_evaluator::PatternTransformation::default(),
-                user_parameter_definitions: HashMap::new(),
-            };
-            let pat = serde_json::to_string(&pat).unwrap();
+            let pat = TestPattern::new("DEFAULT_PATTERN").json();
             let pat = CString::new(pat).unwrap();
             let pat = AsciiPointer::from_cstr(&pat);
             let rv = eh.update_pattern(pat);
@@ -823,4 +1252,257 @@ mod test {
 
         assert_good_deinit(&eh);
     }
+
+    #[test]
+    fn test_custom_output_backend() {
+        struct CountingOutput(std::sync::mpsc::Sender<usize>);
+        impl OutputBackend for CountingOutput {
+            fn sample_rate(&self) -> u64 { 1000 }
+            fn callback_rate(&self) -> f64 { 100.0 }
+            fn capabilities(&self) -> output::OutputCapabilities {
+                output::OutputCapabilities { name: "counting".to_string(), kind: output::OutputKind::Virtual, num_actuators: None }
+            }
+            fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], _ctx: &output::StreamingContext) -> Result<(), AdapticsError> {
+                self.0.send(evals.len()).ok();
+                Ok(())
+            }
+        }
+
+        let (batch_len_tx, batch_len_rx) = std::sync::mpsc::channel();
+        let aeh = create_threads(Box::new(CountingOutput(batch_len_tx)), true, None, Arc::default(), &EngineConfig::default(), None, Vec::new()).unwrap();
+        let batch_len = batch_len_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap();
+        assert_eq!(batch_len, 10); // sample_rate / callback_rate
+
+        drop(aeh.patteval_update_tx);
+        aeh.pattern_eval_handle.join().unwrap();
+        aeh.end_streaming_tx.send(()).unwrap();
+        aeh.ulh_streaming_handle.join().unwrap().unwrap();
+        assert!(aeh.telemetry.snapshot().eval_batches > 0);
+    }
+
+    #[test]
+    fn test_synthetic_tracking_offset() {
+        struct LastEvalOutput(std::sync::mpsc::Sender<pattern_evaluator::MAHCoordsConst>);
+        impl OutputBackend for LastEvalOutput {
+            fn sample_rate(&self) -> u64 { 1000 }
+            fn callback_rate(&self) -> f64 { 100.0 }
+            fn capabilities(&self) -> output::OutputCapabilities {
+                output::OutputCapabilities { name: "last-eval".to_string(), kind: output::OutputKind::Virtual, num_actuators: None }
+            }
+            fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], _ctx: &output::StreamingContext) -> Result<(), AdapticsError> {
+                if let Some(eval) = evals.last() { self.0.send(eval.ul_control_point.coords.clone()).ok(); }
+                Ok(())
+            }
+        }
+
+        let mut config = EngineConfig::default();
+        config.tracking.source = TrackingSourceKind::Synthetic;
+        config.tracking.synthetic_motion = SyntheticMotion::Static { position: pattern_evaluator::MAHCoordsConst { x: 10.0, y: 20.0, z: 200.0 } };
+        config.tracking.synthetic_second_hand = Some(SyntheticMotion::Static { position: pattern_evaluator::MAHCoordsConst { x: -50.0, y: 20.0, z: 150.0 } });
+
+        let (coords_tx, coords_rx) = std::sync::mpsc::channel();
+        let (tracking_data_tx, tracking_data_rx) = crossbeam_channel::bounded(1);
+        let aeh = create_threads(Box::new(LastEvalOutput(coords_tx)), true, Some(tracking_data_rx), Arc::default(), &config, None, Vec::new()).unwrap();
+        let (end_tracking_tx, end_tracking_rx) = crossbeam_channel::bounded(1);
+        let tracking_ctx = tracking::source::TrackingContext::new(tracking_data_tx, None, end_tracking_rx, aeh.telemetry.clone(), config.debug, None);
+        let tracking_handle = spawn_tracking_thread(config.tracking.tracking_source().unwrap(), tracking_ctx).unwrap();
+        aeh.update(PatternEvalUpdate::Tracking { enabled: true, hand: None }).unwrap();
+
+        let wait_for_z = |z: f64| {
+            let start = std::time::Instant::now();
+            loop {
+                let coords = coords_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap();
+                if (coords.z - z).abs() < 1e-9 || start.elapsed() > std::time::Duration::from_secs(1) { break coords; }
+            }
+        };
+        assert_eq!(wait_for_z(200.0), pattern_evaluator::MAHCoordsConst { x: 10.0, y: 20.0, z: 200.0 });
+
+        aeh.update(PatternEvalUpdate::Tracking { enabled: true, hand: Some(TrackedHand::Left) }).unwrap();
+        assert_eq!(wait_for_z(150.0), pattern_evaluator::MAHCoordsConst { x: -50.0, y: 20.0, z: 150.0 });
+
+        end_tracking_tx.send(()).unwrap();
+        tracking_handle.join().unwrap().unwrap();
+        assert!(aeh.telemetry().tracking_frames > 0);
+        aeh.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_queue_events() {
+        let eh = FFIHandle::init(true, false).unwrap();
+
+        for id in ["first", "second"] {
+            let pat = TestPattern::new(id).json();
+            let entry = QueueEntry { id: id.to_string(), pattern_json: pat, gap_ms: 10.0, loops: 1, user_parameters: HashMap::new() };
+            let entry = CString::new(serde_json::to_string(&entry).unwrap()).unwrap();
+            let rv = eh.queue_enqueue(AsciiPointer::from_cstr(&entry));
+            assert_eq!(rv, Ok(()));
+        }
+
+        let mut events = vec![];
+        let start = std::time::Instant::now();
+        while events.len() < 4 && start.elapsed() < std::time::Duration::from_secs(1) {
+            let event_json_u8 = &mut [0u8; 1024];
+            let mut has_event = false;
+            let rv = eh.poll_event(FFISliceMut::from_slice(event_json_u8), std::ptr::addr_of_mut!(has_event));
+            assert_eq!(rv, Ok(()));
+            if has_event {
+                let event_json = std::ffi::CStr::from_bytes_until_nul(event_json_u8).unwrap().to_str().unwrap();
+                events.push(serde_json::from_str::<AdapticsEngineEvent>(event_json).unwrap());
+            } else {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+
+        let events: Vec<_> = events.into_iter().map(|e| match e {
+            AdapticsEngineEvent::QueueEntryStarted { id } => format!("started {id}"),
+            AdapticsEngineEvent::QueueEntryFinished { id, completed } => format!("finished {id} {completed}"),
+            AdapticsEngineEvent::VoiceFinished { voice_id } => format!("voice finished {voice_id}"),
+            AdapticsEngineEvent::Error { message } => format!("error {message}"),
+        }).collect();
+        assert_eq!(events, vec!["started first", "finished first true", "started second", "finished second true"]);
+
+        assert_good_deinit(&eh);
+    }
+
+    /// Plays a long pattern on the default voice (or as a queue entry if `queued`), sends `stop`,
+    /// and checks that playback updates continue until one with stop set has been sent
+    fn assert_stop_update_sent(queued: bool, stop: PatternEvalUpdate) {
+        let eh = FFIHandle::init(true, true).unwrap();
+        let pat = TestPattern::new("long").length(60_000.0).json();
+        if !queued {
+            let pat = CString::new(pat.as_str()).unwrap();
+            assert_eq!(eh.update_pattern(AsciiPointer::from_cstr(&pat)), Ok(()));
+            let playstart = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64() * 1000.0;
+            assert_eq!(eh.update_playstart(playstart, 0.0), Ok(()));
+        }
+        {
+            let guard = ENGINE_HANDLE_MAP.read().unwrap();
+            let aeh = &guard.as_ref().unwrap()[&eh.handle_id].aeh;
+            if queued {
+                let entry = QueueEntry { id: "long".to_string(), pattern_json: pat.clone(), gap_ms: 0.0, loops: 1, user_parameters: HashMap::new() };
+                aeh.update(PatternEvalUpdate::QueueEnqueue { entry }).unwrap();
+                assert!(matches!(aeh.recv_event_timeout(std::time::Duration::from_secs(1)), Some(AdapticsEngineEvent::QueueEntryStarted { .. })));
+            }
+            aeh.update(stop).unwrap();
+        }
+
+        // playback updates continue after stopping, until one with stop set has been sent
+        let mut eval_results = vec![UnityEvalResult::default(); 4096];
+        let mut eval_results_slice = FFISliceMut::from_slice(&mut eval_results);
+        let mut num_evals = 0u32;
+        let start = std::time::Instant::now();
+        let mut stopped = false;
+        while !stopped && start.elapsed() < std::time::Duration::from_secs(1) {
+            assert_eq!(eh.get_playback_updates(&mut eval_results_slice, std::ptr::addr_of_mut!(num_evals)), Ok(()));
+            stopped = eval_results_slice.as_slice()[..num_evals as usize].iter().any(|e| e.stop);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(stopped, "expected a playback update with stop set");
+
+        assert_good_deinit(&eh);
+    }
+
+    #[test]
+    fn test_stop_sends_stop_update() {
+        assert_stop_update_sent(false, PatternEvalUpdate::Stop {});
+    }
+
+    #[test]
+    fn test_stop_default_voice_sends_stop_update() {
+        assert_stop_update_sent(false, PatternEvalUpdate::VoiceStop { voice_id: DEFAULT_VOICE_ID });
+    }
+
+    #[test]
+    fn test_playstart_zero_sends_stop_update() {
+        assert_stop_update_sent(false, PatternEvalUpdate::Playstart { playstart: 0.0, playstart_offset: 0.0 });
+    }
+
+    #[test]
+    fn test_queue_clear_sends_stop_update() {
+        assert_stop_update_sent(true, PatternEvalUpdate::QueueClear {});
+    }
+
+    #[test]
+    fn test_voice_ids() {
+        let eh = FFIHandle::init(true, false).unwrap();
+        let pattern_json = TestPattern::new("voice").length(10_000.0).json();
+        let voice_play = |voice_id| PatternEvalUpdate::VoicePlay { voice_id: Some(voice_id), pattern_json: pattern_json.clone(), priority: 1, user_parameters: HashMap::new() };
+
+        // voice ids chosen by other clients are skipped by adaptics_engine_play_voice
+        {
+            let guard = ENGINE_HANDLE_MAP.read().unwrap();
+            let aeh = &guard.as_ref().unwrap()[&eh.handle_id].aeh;
+            assert!(aeh.update_and_wait(voice_play(1)).is_ok());
+            assert!(aeh.update_and_wait(voice_play(1)).is_err(), "voice is already playing");
+            assert!(aeh.update_and_wait(voice_play(2)).is_ok());
+        }
+        let pattern_json_c = CString::new(pattern_json.clone()).unwrap();
+        let mut voice_ids = [0; 2];
+        for voice_id in &mut voice_ids {
+            assert_eq!(eh.play_voice(AsciiPointer::from_cstr(&pattern_json_c), 1, voice_id), Ok(()));
+        }
+        assert_eq!(voice_ids, [3, 4]);
+
+        let guard = ENGINE_HANDLE_MAP.read().unwrap();
+        let AdapticsWSResponse::PlaybackState { mut voices, .. } = guard.as_ref().unwrap()[&eh.handle_id].aeh.query(AdapticsWSQuery::GetPlaybackState {}).unwrap() else { panic!("expected playback state") };
+        drop(guard);
+        voices.sort_unstable();
+        assert_eq!(voices, vec![1, 2, 3, 4]);
+
+        assert_good_deinit(&eh);
+    }
+
+    #[test]
+    fn test_invalid_pattern_error_event() {
+        let eh = FFIHandle::init(true, false).unwrap();
+        let pat = CString::new("{\"not\": \"a pattern\"}").unwrap();
+        let rv = eh.update_pattern(AsciiPointer::from_cstr(&pat));
+        assert_eq!(rv, Ok(()));
+
+        let event_json_u8 = &mut [0u8; 1024];
+        let mut has_event = false;
+        let start = std::time::Instant::now();
+        while !has_event && start.elapsed() < std::time::Duration::from_secs(1) {
+            let rv = eh.poll_event(FFISliceMut::from_slice(event_json_u8), std::ptr::addr_of_mut!(has_event));
+            assert_eq!(rv, Ok(()));
+        }
+        let event_json = std::ffi::CStr::from_bytes_until_nul(event_json_u8).unwrap().to_str().unwrap();
+        assert!(matches!(serde_json::from_str(event_json).unwrap(), AdapticsEngineEvent::Error { .. }));
+
+        // pattern-eval thread should still be running
+        let rv = eh.update_user_parameter(AsciiPointer::from_cstr(&CString::new("a").unwrap()), 1.0);
+        assert_eq!(rv, Ok(()));
+        assert_good_deinit(&eh);
+    }
+
+    #[test]
+    fn test_queries() {
+        let aeh = AdapticsEngineHandle::start_with_output(Box::new(output::MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+        aeh.update(PatternEvalUpdate::UserParameter { name: "a".to_string(), value: 2.0 }).unwrap();
+        aeh.update(PatternEvalUpdate::Play { pattern_time: 0.0 }).unwrap();
+
+        let AdapticsWSResponse::Parameters { evaluator_params } = aeh.query(AdapticsWSQuery::GetParameters {}).unwrap() else { panic!("expected parameters") };
+        assert_eq!(evaluator_params.user_parameters.get("a"), Some(&2.0));
+        let AdapticsWSResponse::PlaybackState { playing, queue_len, .. } = aeh.query(AdapticsWSQuery::GetPlaybackState {}).unwrap() else { panic!("expected playback state") };
+        assert!(playing);
+        assert_eq!(queue_len, 0);
+        let AdapticsWSResponse::Pattern { pattern_json } = aeh.query(AdapticsWSQuery::GetPattern {}).unwrap() else { panic!("expected pattern") };
+        assert!(PatternEvaluator::new_from_json_string(&pattern_json).is_ok());
+        let AdapticsWSResponse::Version { protocol_version, .. } = aeh.query(AdapticsWSQuery::GetVersion {}).unwrap() else { panic!("expected version") };
+        assert_eq!(protocol_version, WS_PROTOCOL_VERSION);
+
+        aeh.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_rejected_tracking_update_keeps_state() {
+        let aeh = AdapticsEngineHandle::start_with_output(Box::new(output::MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+        assert!(aeh.update_and_wait(PatternEvalUpdate::Tracking { enabled: true, hand: Some(TrackedHand::Left) }).is_err(), "no tracking data channel is connected");
+
+        let AdapticsWSResponse::PlaybackState { tracking_enabled, tracked_hand, .. } = aeh.query(AdapticsWSQuery::GetPlaybackState {}).unwrap() else { panic!("expected playback state") };
+        assert!(!tracking_enabled);
+        assert_eq!(tracked_hand, TrackedHand::default());
+
+        aeh.shutdown().unwrap();
+    }
 }
\ No newline at end of file
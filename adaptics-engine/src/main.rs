@@ -1,21 +1,131 @@
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use adaptics_engine::{AdapticsError, EngineConfig, OutputBackendKind};
 // ## !NOTE: do NOT use mod (for threads). Everything must be through the lib crate
 
 /// Adaptics Engine CLI <https://github.com/AdaptiveHaptics/AdapticsEngine>
 ///
 /// Allows realtime playback of haptic patterns being developed in the designer <https://adaptivehaptics.github.io/AdapticsDesigner/>, using a WebSocket connection
+///
+/// Runs the `serve` command if no command is given.
+///
+/// Settings are read from the --config file (or adaptics-engine.toml in the working directory, if it exists),
+/// see `adaptics_engine::EngineConfig` for the available options. Command line options override the config file.
 #[derive(Parser, Debug)]
-#[command(author, version, long_about, verbatim_doc_comment)]
+#[command(author, version, long_about, verbatim_doc_comment, args_conflicts_with_subcommands = true)]
 struct AdapticsEngineCliArgs {
-    #[clap(short, long, default_value = "127.0.0.1:8037")]
-    websocket_bind_addr: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs the engine until it is stopped, controlled over WebSocket (e.g. by the designer), the REST API and OSC
+    Serve(ServeArgs),
+    /// Plays a tacton once (or in a loop) without any network interfaces, and exits when it has finished
+    Play(PlayArgs),
+    /// Checks that tacton files can be loaded, exits with an error if any of them can not
+    Validate(ValidateArgs),
+    /// Upgrades a tacton file to the latest revision of the file format
+    Convert(ConvertArgs),
+    /// Lists the serial ports (for --vib-grid), the Ultraleap haptic device and the status of the Ultraleap tracking service
+    Devices,
+}
+
+/// Configuration file used if no --config is given, if it exists
+const DEFAULT_CONFIG_FILE: &str = "adaptics-engine.toml";
+
+/// Selects the device that is streamed to
+#[derive(Args, Debug)]
+struct OutputArgs {
+    /// Reads the engine configuration from this TOML file (or JSON, if it ends in ".json"). Defaults to adaptics-engine.toml, if it exists.
+    #[clap(short, long)]
+    config: Option<PathBuf>,
 
     /// Uses a mock haptic device instead of a Ultraleap haptic device.
-    /// This still requires the Ultraleap SDK (and DLLs to be available), but does not attempt to connect to a device.
-    /// Mock device configuration is DEVICE_UPDATE_RATE=20000hz, CALLBACK_RATE=500hz
+    /// Does not attempt to connect to a device. Builds with the (default) `ulhaptics` feature still require the Ultraleap SDK DLLs to be available.
+    /// Mock device configuration defaults to DEVICE_UPDATE_RATE=20000hz, CALLBACK_RATE=500hz
     #[clap(short='m', long)]
     use_mock_streaming: bool,
 
+    /// Alpha feature: Output to a vibrotactile grid device (e.g. a vest or glove) instead of a mid-air ultrasound haptic device.
+    /// This is a work in progress and targets a prototype device.
+    ///
+    /// Provide the serial port of the device, e.g. "COM3" on Windows or "/dev/ttyUSB0" on Linux (see the devices command).
+    /// This parameter is ignored if --use-mock-streaming is enabled.
+    /// Provide "auto" to attempt to auto-detect the device.
+    #[clap(long)]
+    vib_grid: Option<String>,
+
+    /// Records every sample that would be sent to the device to this file instead of streaming to a device.
+    /// Files ending in ".bin" are recorded in a binary format, all others as CSV.
+    #[clap(long)]
+    record: Option<PathBuf>,
+
+    /// Streams every evaluated control point to this UDP address (e.g. "127.0.0.1:9000") instead of streaming to a device.
+    #[clap(long)]
+    udp_output: Option<std::net::SocketAddr>,
+
+    /// Packet format of --udp-output: "osc" (OSC bundles of /adaptics/control_point messages) or "raw" (see `adaptics_engine::output::UdpFormat`). [default: osc]
+    #[clap(long)]
+    udp_format: Option<adaptics_engine::output::UdpFormat>,
+
+    /// Only send every Nth device sample with --udp-output. [default: 1]
+    #[clap(long)]
+    udp_decimation: Option<u64>,
+}
+impl OutputArgs {
+    /// Loads the config file and applies the output options on top of it
+    fn load_config(self) -> Result<EngineConfig, AdapticsError> {
+        let mut config = match &self.config {
+            Some(path) => EngineConfig::load(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => EngineConfig::load(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => EngineConfig::default(),
+        };
+        let output = &mut config.output;
+        // applied in reverse order of precedence
+        if let Some(port) = self.vib_grid {
+            output.backend = OutputBackendKind::VibGrid;
+            output.vib_grid_port = port;
+        }
+        if self.use_mock_streaming { output.backend = OutputBackendKind::Mock; }
+        if let Some(udp_target) = self.udp_output {
+            output.backend = OutputBackendKind::Udp;
+            output.udp_target = Some(udp_target);
+        }
+        if let Some(record_path) = self.record {
+            output.backend = OutputBackendKind::Record;
+            output.record_path = Some(record_path);
+        }
+        if let Some(udp_format) = self.udp_format { output.udp_format = udp_format; }
+        if let Some(udp_decimation) = self.udp_decimation { output.udp_decimation = udp_decimation; }
+        Ok(config)
+    }
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// Address of the WebSocket server. The same address serves a REST API at /api/ (e.g. curl -X POST http://127.0.0.1:8037/api/stop),
+    /// with the routes status, version, tactons, tactons/<name>/play, pattern, parameters, play, pause, resume, stop and update. [default: 127.0.0.1:8037]
+    #[clap(short, long)]
+    websocket_bind_addr: Option<String>,
+
+    /// Allows web pages with this origin (e.g. "https://example.com" or "http://localhost:*" for any port) to connect to the WebSocket server.
+    /// Can be given multiple times, "*" allows any origin. Defaults to the designer and pages served from localhost.
+    /// Clients that do not send an Origin header (i.e. not browsers) are always allowed.
+    #[clap(long)]
+    allowed_origin: Vec<String>,
+
+    /// Requires WebSocket clients to pass this token, either in the URL (ws://127.0.0.1:8037/?token=<token>)
+    /// or in the first message: { "cmd": "auth", "data": { "token": "<token>" } }
+    /// REST API requests must pass it in the URL or an "Authorization: Bearer <token>" header.
+    #[clap(long)]
+    ws_token: Option<String>,
+
     /// Disables hosting the WebSocket server. Likely only useful for testing.
     #[clap(long)]
     no_network: bool,
@@ -25,39 +135,251 @@ struct AdapticsEngineCliArgs {
     #[clap(short='t', long)]
     no_tracking: bool,
 
-    /// Alpha feature: Output to a vibrotactile grid device (e.g. a vest or glove) instead of a mid-air ultrasound haptic device.
-    /// This is a work in progress and targets a prototype device.
-    ///
-    /// Provide the serial port of the device, e.g. "COM3" on Windows or "/dev/ttyUSB0" on Linux.
-    /// This parameter is ignored if --use-mock-streaming is enabled.
-    /// Provide "auto" to attempt to auto-detect the device.
-    /// Provide nothing or "" to print a list of available devices and exit.
-    #[clap(long, default_missing_value=Some(""), num_args=0..=1)]
-    vib_grid: Option<String>,
+    /// Generates tracking frames of a hand following the synthetic motion from the config file (a circle by default), instead of connecting to the tracking service.
+    #[clap(long, conflicts_with_all = ["no_tracking", "tracking_replay"])]
+    tracking_synthetic: bool,
+
+    /// Replays the tracking frames in this JSON lines file (one { "time": <seconds>, "tracking_frame": <frame> } per line, e.g. a --session-log), instead of connecting to the tracking service.
+    #[clap(long, conflicts_with = "no_tracking")]
+    tracking_replay: Option<PathBuf>,
+
+    /// Restarts the --tracking-replay file after the last frame
+    #[clap(long, requires = "tracking_replay")]
+    tracking_loop: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Appends a snapshot of the runtime telemetry (eval latency, deadline misses, dropped updates, etc.) to this file
+    /// as a line of JSON every second.
+    #[clap(long)]
+    telemetry_file: Option<PathBuf>,
+
+    /// Records the tracking frames, playback updates, applied updates (e.g. user parameter changes) and events of this session to a JSON lines file,
+    /// one { "time": <seconds>, "<entry>": <data> } per line. The file can be replayed with --tracking-replay.
+    #[clap(long)]
+    session_log: Option<PathBuf>,
+
+    /// Listens for OSC messages on this UDP address (e.g. "0.0.0.0:9001") to control playback and user parameters:
+    /// /adaptics/param/<name> <number>, /adaptics/play [<tacton>], /adaptics/pause, /adaptics/resume, /adaptics/stop, /adaptics/seek <ms>
+    #[clap(long)]
+    osc_bind_addr: Option<String>,
+
+    /// Directory containing the tacton library: the .adaptics files that can be played by name (e.g. with --play, /adaptics/play <tacton> or the play_tacton websocket command).
+    /// Edited files are reloaded automatically.
+    #[clap(long, default_value = ".")]
+    tacton_dir: PathBuf,
+
+    /// Plays this tacton from --tacton-dir on startup, by file name (with or without ".adaptics") or by the name stored in the tacton.
+    #[clap(long)]
+    play: Option<String>,
 }
 
-fn main() -> Result<(), adaptics_engine::AdapticsError> {
-    let cli_args = AdapticsEngineCliArgs::parse();
+#[derive(Args, Debug)]
+struct PlayArgs {
+    /// The tacton file (.adaptics) to play
+    file: PathBuf,
+
+    /// Sets a user parameter before playback starts, e.g. --param progress=0.5. Can be given multiple times.
+    #[clap(long = "param", value_name = "NAME=VALUE", value_parser = parse_user_parameter)]
+    params: Vec<(String, f64)>,
+
+    /// Plays the tacton in a loop until the process is stopped
+    #[clap(long = "loop")]
+    loop_playback: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// The tacton files (.adaptics) to check
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// The tacton file (.adaptics) to upgrade
+    file: PathBuf,
+
+    /// Writes the upgraded tacton to this file instead of stdout
+    #[clap(short, long, conflicts_with = "in_place")]
+    output: Option<PathBuf>,
+
+    /// Overwrites the input file with the upgraded tacton
+    #[clap(long)]
+    in_place: bool,
+}
+
+fn parse_user_parameter(s: &str) -> Result<(String, f64), String> {
+    let (name, value) = s.split_once('=').ok_or_else(|| format!("expected NAME=VALUE, got '{s}'"))?;
+    let value = value.trim().parse::<f64>().map_err(|e| format!("invalid value for '{name}': {e}"))?;
+    Ok((name.trim().to_string(), value))
+}
+
+fn read_tacton(path: &std::path::Path) -> Result<String, AdapticsError> {
+    std::fs::read_to_string(path).map_err(|e| AdapticsError::new(&format!("failed to read '{}': {e}", path.display())))
+}
+
+fn serve(args: ServeArgs) -> Result<(), AdapticsError> {
+    let mut config = args.output.load_config()?;
+    if let Some(bind_addr) = args.websocket_bind_addr { config.network.websocket_bind_addr = bind_addr; }
+    if !args.allowed_origin.is_empty() { config.network.allowed_origins = args.allowed_origin; }
+    if let Some(token) = args.ws_token { config.network.token = Some(token); }
+    if let Some(bind_addr) = args.osc_bind_addr { config.network.osc_bind_addr = Some(bind_addr); }
+    if args.no_network { config.network.enabled = false; }
+    if args.no_tracking { config.tracking.enabled = false; }
+    if args.tracking_synthetic { config.tracking.source = adaptics_engine::TrackingSourceKind::Synthetic; }
+    if let Some(replay_path) = args.tracking_replay {
+        config.tracking.source = adaptics_engine::TrackingSourceKind::Replay;
+        config.tracking.replay_path = Some(replay_path);
+    }
+    if args.tracking_loop { config.tracking.replay_loop = true; }
+    if let Some(session_log) = args.session_log { config.session_log.path = Some(session_log); }
+    config.validate()?;
+
+    let tacton_library = Some(adaptics_engine::TactonLibraryConfig { dir: args.tacton_dir, play: args.play });
 
+    adaptics_engine::run_threads_and_wait(&config, args.telemetry_file, tacton_library)
+}
+
+fn play(args: PlayArgs) -> Result<(), AdapticsError> {
+    let pattern_json = read_tacton(&args.file)?;
+    let id = args.file.display().to_string();
+    let entry = adaptics_engine::QueueEntry {
+        id: id.clone(),
+        pattern_json,
+        gap_ms: 0.0,
+        loops: u32::from(!args.loop_playback), // 0 loops forever
+        user_parameters: args.params.into_iter().collect(),
+    };
 
-    let device_type = match cli_args.vib_grid.as_deref() {
-        Some("auto") => Some(adaptics_engine::hapticglove::DeviceType::Auto),
-        Some("") => {
-            let serial_ports = adaptics_engine::hapticglove::get_possible_serial_ports()?;
-            println!("Available devices:");
-            for (i, p) in serial_ports.iter().enumerate() {
-                println!("{}: {:?}", i, p);
-            }
-            return Err(adaptics_engine::AdapticsError::new("No device specified."));
-        },
-        Some(s) => Some(adaptics_engine::hapticglove::DeviceType::SerialPort(s.to_string())),
-        None => None
+    let config = args.output.load_config()?;
+    config.validate()?;
+    let engine = adaptics_engine::AdapticsEngineHandle::start_with_config(config.output_backend()?, &config)?;
+    engine.update(adaptics_engine::PatternEvalUpdate::QueueEnqueue { entry })?;
+    let res = loop {
+        match engine.recv_event_timeout(Duration::from_millis(100)) {
+            Some(adaptics_engine::AdapticsEngineEvent::QueueEntryStarted { .. }) => println!("playing {id}"),
+            Some(adaptics_engine::AdapticsEngineEvent::QueueEntryFinished { completed: true, .. }) => break Ok(()),
+            Some(adaptics_engine::AdapticsEngineEvent::QueueEntryFinished { completed: false, .. }) => break Err(AdapticsError::new(&format!("failed to play {id}"))),
+            Some(_) => {}, // errors are also printed by the engine
+            None if !engine.is_running() => break Ok(()), // the output stopped, shutdown returns its error
+            None => {},
+        }
     };
+    engine.shutdown()?;
+    res
+}
+
+fn validate(args: &ValidateArgs) -> Result<(), AdapticsError> {
+    let mut invalid = 0;
+    for path in &args.files {
+        let pattern = read_tacton(path).and_then(|json| Ok(serde_json::from_str::<pattern_evaluator::MidAirHapticsAnimationFileFormat>(&json)?));
+        match pattern {
+            Ok(pattern) => {
+                let outdated = pattern.revision != pattern_evaluator::DataFormatRevision::CurrentRevision;
+                let pattern_eval = pattern_evaluator::PatternEvaluator::new(pattern);
+                let pattern = pattern_eval.mah_animation();
+                let mut user_parameters: Vec<_> = pattern.user_parameter_definitions.keys().map(String::as_str).collect();
+                user_parameters.sort_unstable();
+                println!("ok: {} \"{}\" ({} keyframes, {}ms, user parameters: [{}])", path.display(), pattern.name, pattern.keyframes.len(), pattern_eval.end_time(), user_parameters.join(", "));
+                if outdated { println!("  outdated file format revision, upgrade it with: adaptics-engine-cli convert --in-place {}", path.display()); }
+            },
+            Err(e) => {
+                invalid += 1;
+                eprintln!("invalid: {}: {e}", path.display());
+            },
+        }
+    }
+    if invalid > 0 { return Err(AdapticsError::new(&format!("{invalid} of {} tactons are invalid", args.files.len()))); }
+    Ok(())
+}
+
+fn convert(args: ConvertArgs) -> Result<(), AdapticsError> {
+    let converted = pattern_evaluator::try_parse_into_latest_version(&read_tacton(&args.file)?)?;
+    match args.output.or(args.in_place.then_some(args.file)) {
+        Some(path) => std::fs::write(&path, converted).map_err(|e| AdapticsError::new(&format!("failed to write '{}': {e}", path.display()))),
+        None => { println!("{converted}"); Ok(()) },
+    }
+}
+
+fn devices() -> Result<(), AdapticsError> {
+    let serial_ports = adaptics_engine::devices::get_possible_serial_ports()?;
+    println!("Serial ports (--vib-grid <port>):");
+    if serial_ports.is_empty() { println!("  none"); }
+    for p in &serial_ports {
+        println!("  {} ({:?})", p.port_name, p.port_type);
+    }
+
+    match adaptics_engine::devices::find_ulhaptics_device() {
+        Ok(identifier) => println!("Ultraleap haptic device: {identifier}"),
+        Err(e) => println!("Ultraleap haptic device: not available ({e})"),
+    }
+
+    match adaptics_engine::devices::tracking_status(Duration::from_secs(2)) {
+        Ok(status) if !status.service_connected => println!("Ultraleap tracking: service not running"),
+        Ok(status) => println!("Ultraleap tracking: service running, {} device(s), {}", status.devices, if status.streaming { "tracking" } else { "not tracking" }),
+        Err(e) => println!("Ultraleap tracking: not available ({e})"),
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), AdapticsError> {
+    let cli_args = AdapticsEngineCliArgs::parse();
+
+    match cli_args.command {
+        None => serve(cli_args.serve),
+        Some(Command::Serve(args)) => serve(args),
+        Some(Command::Play(args)) => play(args),
+        Some(Command::Validate(args)) => validate(&args),
+        Some(Command::Convert(args)) => convert(args),
+        Some(Command::Devices) => devices(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use super::{AdapticsEngineCliArgs, Command, OutputBackendKind, parse_user_parameter};
+
+    #[test]
+    fn no_command_serves() {
+        let args = AdapticsEngineCliArgs::try_parse_from(["adaptics-engine-cli", "-m", "--no-network"]).unwrap();
+        assert!(args.command.is_none());
+        assert!(args.serve.output.use_mock_streaming);
+        assert!(args.serve.no_network);
+    }
+
+    #[test]
+    fn play_params() {
+        let args = AdapticsEngineCliArgs::try_parse_from(["adaptics-engine-cli", "play", "a.adaptics", "--param", "progress=0.5", "--param", "speed = 2", "--loop", "-m"]).unwrap();
+        let Some(Command::Play(play)) = args.command else { panic!("expected play, got {:?}", args.command) };
+        assert_eq!(play.params, vec![("progress".to_string(), 0.5), ("speed".to_string(), 2.0)]);
+        assert!(play.loop_playback);
+        assert!(play.output.use_mock_streaming);
+
+        assert!(parse_user_parameter("progress").is_err());
+        assert!(parse_user_parameter("progress=fast").is_err());
+    }
+
+    #[test]
+    fn output_options_override_config() {
+        let args = AdapticsEngineCliArgs::try_parse_from(["adaptics-engine-cli", "serve", "-m", "--udp-output", "127.0.0.1:9000", "--udp-decimation", "4"]).unwrap();
+        let Some(Command::Serve(serve)) = args.command else { panic!("expected serve, got {:?}", args.command) };
+        let config = serve.output.load_config().unwrap();
+        assert_eq!(config.output.backend, OutputBackendKind::Udp, "--udp-output takes precedence over -m");
+        assert_eq!(config.output.udp_decimation, 4);
+        assert_eq!(config.output.udp_format, adaptics_engine::output::UdpFormat::Osc);
+
+        let args = AdapticsEngineCliArgs::try_parse_from(["adaptics-engine-cli", "--config", "missing.toml"]).unwrap();
+        assert!(args.serve.output.load_config().is_err());
+    }
 
-    adaptics_engine::run_threads_and_wait(
-        cli_args.use_mock_streaming,
-        if cli_args.no_network { None } else { Some(cli_args.websocket_bind_addr) },
-        !cli_args.no_tracking,
-        device_type,
-    )
+    #[test]
+    fn convert_output_conflicts_with_in_place() {
+        assert!(AdapticsEngineCliArgs::try_parse_from(["adaptics-engine-cli", "convert", "a.adaptics", "-o", "b.adaptics", "--in-place"]).is_err());
+    }
 }
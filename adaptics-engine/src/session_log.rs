@@ -0,0 +1,143 @@
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use pattern_evaluator::BrushAtAnimLocalTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{AdapticsError, AdapticsEngineEvent, PatternEvalUpdate};
+use crate::tracking::TrackingFrame;
+
+/// Entries that were not written yet. Entries are dropped (and counted in [`SessionLogEntry::SessionEnd`]) if the writer falls this far behind.
+const SESSION_LOG_CAPACITY: usize = 4096;
+
+/// Records a session to a JSON lines file, see [`crate::EngineConfig::session_log`]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionLogConfig {
+    /// The file is overwritten when the engine starts. Nothing is recorded if this is not set.
+    pub path: Option<PathBuf>,
+    /// Leaves out the playback updates (every evaluated control point), which make up most of the log
+    pub skip_playback_updates: bool,
+}
+
+/// One line of a session log: `{ "time": <seconds since the session started>, "<entry>": <data> }`.
+/// Session logs can be replayed as tracking input, see [`crate::TrackingSourceKind::Replay`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionLogLine {
+    pub time: f64,
+    #[serde(flatten)]
+    pub entry: SessionLogEntry,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionLogEntry {
+    /// The first line of every session log
+    SessionStart { unix_time_ms: u64, engine_version: String },
+    /// A frame from the tracking source
+    TrackingFrame(TrackingFrame),
+    /// The evals sent to the device since the previous playback update (the same as sent to websocket clients)
+    PlaybackUpdate { evals: Vec<BrushAtAnimLocalTime> },
+    /// An update that was applied by the pattern-eval thread (e.g. a user parameter change)
+    Update(PatternEvalUpdate),
+    Event(AdapticsEngineEvent),
+    /// The last line of every session log
+    SessionEnd { dropped_entries: u64 },
+}
+
+/// Sends entries to the session log thread without blocking
+#[derive(Clone)]
+pub(crate) struct SessionLogger {
+    tx: crossbeam_channel::Sender<(Instant, SessionLogEntry)>,
+    skip_playback_updates: bool,
+    dropped_entries: Arc<AtomicU64>,
+}
+impl SessionLogger {
+    pub(crate) fn log(&self, entry: SessionLogEntry) {
+        if let Err(crossbeam_channel::TrySendError::Full(_)) = self.tx.try_send((Instant::now(), entry)) {
+            self.dropped_entries.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn log_playback_update(&self, evals: &[BrushAtAnimLocalTime]) {
+        if !self.skip_playback_updates {
+            self.log(SessionLogEntry::PlaybackUpdate { evals: evals.to_vec() });
+        }
+    }
+}
+
+/// Writes the entries of a session log until all [`SessionLogger`]s are dropped
+pub(crate) struct SessionLogWriter {
+    writer: BufWriter<std::fs::File>,
+    rx: crossbeam_channel::Receiver<(Instant, SessionLogEntry)>,
+    start: Instant,
+    dropped_entries: Arc<AtomicU64>,
+}
+
+/// Creates the file at `config.path`, and a logger and the writer for the thread that writes its entries to the file. `None` if no path is set.
+/// The session starts now.
+pub(crate) fn session_log(config: &SessionLogConfig) -> Result<Option<(SessionLogger, SessionLogWriter)>, AdapticsError> {
+    let Some(path) = &config.path else { return Ok(None) };
+    let file = std::fs::File::create(path).map_err(|e| AdapticsError::new(&format!("failed to create session log '{}': {e}", path.display())))?;
+    let (tx, rx) = crossbeam_channel::bounded(SESSION_LOG_CAPACITY);
+    let dropped_entries = Arc::<AtomicU64>::default();
+    let start = Instant::now();
+    tx.try_send((start, SessionLogEntry::SessionStart {
+        unix_time_ms: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX)),
+        engine_version: env!("CARGO_PKG_VERSION").to_string(),
+    })).ok(); // can not be full yet
+    Ok(Some((
+        SessionLogger { tx, skip_playback_updates: config.skip_playback_updates, dropped_entries: dropped_entries.clone() },
+        SessionLogWriter { writer: BufWriter::new(file), rx, start, dropped_entries },
+    )))
+}
+
+impl SessionLogWriter {
+    pub(crate) fn run(mut self) -> Result<(), AdapticsError> {
+        for (instant, entry) in &self.rx {
+            write_line(&mut self.writer, self.start, instant, entry)?;
+        }
+
+        let dropped_entries = self.dropped_entries.load(Ordering::Relaxed);
+        if dropped_entries > 0 { eprintln!("[WARN] session log fell behind, dropped {dropped_entries} entries"); }
+        write_line(&mut self.writer, self.start, Instant::now(), SessionLogEntry::SessionEnd { dropped_entries })?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn write_line(writer: &mut impl Write, start: Instant, instant: Instant, entry: SessionLogEntry) -> Result<(), AdapticsError> {
+    let line = SessionLogLine { time: instant.saturating_duration_since(start).as_secs_f64(), entry };
+    writeln!(writer, "{}", serde_json::to_string(&line)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_log() {
+        let path = std::env::temp_dir().join(format!("adaptics-session-log-test-{}.jsonl", std::process::id()));
+        let (logger, writer) = session_log(&SessionLogConfig { path: Some(path.clone()), skip_playback_updates: true }).unwrap().unwrap();
+        logger.log(SessionLogEntry::TrackingFrame(TrackingFrame::new(None, None)));
+        logger.log(SessionLogEntry::Update(PatternEvalUpdate::UserParameter { name: "progress".to_string(), value: 0.5 }));
+        logger.log_playback_update(&[]);
+        drop(logger);
+        writer.run().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let entries: Vec<String> = contents.lines().map(|line| {
+            let line: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(line["time"].is_f64());
+            line.as_object().unwrap().keys().find(|key| *key != "time").unwrap().clone()
+        }).collect();
+        assert_eq!(entries, vec!["session_start", "tracking_frame", "update", "session_end"]);
+
+        let frames = crate::tracking::replay::read_tracking_log(contents.as_bytes()).unwrap();
+        assert_eq!(frames.len(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use pattern_evaluator::MidAirHapticsAnimationFileFormat;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::AdapticsError;
+use crate::threads::pattern::playback::PatternEvalRequest;
+
+/// How often the tacton directory is checked for added, edited and removed files.
+pub const TACTON_LIBRARY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const TACTON_EXTENSION: &str = "adaptics";
+
+/// Tacton library of the CLI, see [`crate::run_threads_and_wait`]
+#[derive(Debug, Clone)]
+pub struct TactonLibraryConfig {
+    /// Directory containing the tactons (`.adaptics` files), watched for changes
+    pub dir: PathBuf,
+    /// Tacton to play on startup, see [`crate::PatternEvalUpdate::PlayTacton`]
+    pub play: Option<String>,
+}
+
+/// A tacton in the tacton library, see [`crate::PatternEvalUpdate::PlayTacton`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TactonInfo {
+    /// The `name` stored in the tacton
+    pub name: String,
+    /// File name in the tacton directory, e.g. "loading.adaptics"
+    pub file_name: String,
+}
+
+struct LibraryTacton {
+    /// `None` if the file could not be parsed, the tacton cannot be played until it is fixed
+    tacton: Option<(String, String)>, // (name, pattern_json)
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+#[derive(Default)]
+struct LibraryState {
+    dir: Option<PathBuf>,
+    /// by file name
+    tactons: BTreeMap<String, LibraryTacton>,
+}
+
+/// Index of the tactons (`.adaptics` files) in a directory, by file name and by the `name` stored in the tacton.
+/// Kept up to date by a watcher thread polling the directory (see [`TactonLibraryWatcher`]).
+#[derive(Default)]
+pub(crate) struct TactonLibrary {
+    state: RwLock<LibraryState>,
+}
+impl TactonLibrary {
+    /// Replaces the index with the tactons in `dir`
+    pub fn set_dir(&self, dir: PathBuf) -> Result<(), AdapticsError> {
+        if !dir.is_dir() { return Err(AdapticsError::new(&format!("tacton directory '{}' does not exist", dir.display()))); }
+        *self.state.write().unwrap() = LibraryState { dir: Some(dir), tactons: BTreeMap::new() };
+        self.rescan()?;
+        Ok(())
+    }
+
+    /// Loads added and edited tactons and removes deleted ones. Returns the file names of the tactons that changed.
+    pub fn rescan(&self) -> Result<Vec<String>, AdapticsError> {
+        let Some(dir) = self.state.read().unwrap().dir.clone() else { return Ok(vec![]); };
+        let mut found = BTreeMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(TACTON_EXTENSION)) { continue; }
+            let (Some(file_name), Ok(metadata)) = (path.file_name().and_then(|f| f.to_str()), path.metadata()) else { continue; };
+            if metadata.is_file() { found.insert(file_name.to_string(), (metadata.modified().ok(), metadata.len())); }
+        }
+
+        let mut state = self.state.write().unwrap();
+        if state.dir.as_ref() != Some(&dir) { return Ok(vec![]); } // replaced while scanning
+        let mut changed: Vec<String> = state.tactons.keys().filter(|file_name| !found.contains_key(*file_name)).cloned().collect();
+        state.tactons.retain(|file_name, _| found.contains_key(file_name));
+        for (file_name, (modified, len)) in found {
+            if state.tactons.get(&file_name).is_some_and(|t| t.modified == modified && t.len == len) { continue; }
+            let tacton = load_tacton(&dir.join(&file_name)).map_err(|e| eprintln!("[warn] skipping tacton '{file_name}': {e}")).ok();
+            state.tactons.insert(file_name.clone(), LibraryTacton { tacton, modified, len });
+            changed.push(file_name);
+        }
+        Ok(changed)
+    }
+
+    /// Finds a tacton by file name (with or without the `.adaptics` extension) or by the `name` stored in it.
+    /// Returns the file name and the tacton JSON.
+    pub fn get(&self, name: &str) -> Option<(String, String)> {
+        let state = self.state.read().unwrap();
+        let with_extension = format!("{name}.{TACTON_EXTENSION}");
+        let (file_name, tacton) = [name, with_extension.as_str()].into_iter()
+            .find_map(|file_name| state.tactons.get_key_value(file_name))
+            .or_else(|| state.tactons.iter().find(|(_, t)| t.tacton.as_ref().is_some_and(|(n, _)| n == name)))?;
+        Some((file_name.clone(), tacton.tacton.as_ref()?.1.clone()))
+    }
+
+    /// The tacton JSON of the file `file_name`, if it exists and is valid
+    pub fn get_file(&self, file_name: &str) -> Option<String> {
+        self.state.read().unwrap().tactons.get(file_name)?.tacton.as_ref().map(|(_, pattern_json)| pattern_json.clone())
+    }
+
+    /// All valid tactons, sorted by file name
+    pub fn list(&self) -> Vec<TactonInfo> {
+        self.state.read().unwrap().tactons.iter()
+            .filter_map(|(file_name, t)| t.tacton.as_ref().map(|(name, _)| TactonInfo { name: name.clone(), file_name: file_name.clone() }))
+            .collect()
+    }
+}
+
+/// Reads and validates a tacton, returning its name and JSON
+fn load_tacton(path: &Path) -> Result<(String, String), AdapticsError> {
+    let pattern_json = std::fs::read_to_string(path)?;
+    let pattern: MidAirHapticsAnimationFileFormat = serde_json::from_str(&pattern_json)?;
+    Ok((pattern.name, pattern_json))
+}
+
+/// The thread polling the directory of a [`TactonLibrary`], notifying the pattern-eval thread about changed tactons
+pub(crate) struct TactonLibraryWatcher {
+    handle: thread::JoinHandle<()>,
+    end_tx: crossbeam_channel::Sender<()>,
+}
+impl TactonLibraryWatcher {
+    pub fn start(library: Arc<TactonLibrary>, patteval_request_tx: crossbeam_channel::Sender<PatternEvalRequest>) -> Result<Self, AdapticsError> {
+        let (end_tx, end_rx) = crossbeam_channel::bounded(1);
+        let handle = thread::Builder::new()
+            .name("tacton-library".to_string())
+            .spawn(move || {
+                while let Err(crossbeam_channel::RecvTimeoutError::Timeout) = end_rx.recv_timeout(TACTON_LIBRARY_POLL_INTERVAL) {
+                    match library.rescan() {
+                        Ok(file_names) if file_names.is_empty() => {},
+                        Ok(file_names) => {
+                            println!("tactons changed: {file_names:?}");
+                            if patteval_request_tx.send(PatternEvalRequest::TactonsChanged { file_names }).is_err() { break; } // pattern-eval thread exited
+                        },
+                        Err(e) => eprintln!("[warn] failed to scan the tacton directory: {e}"),
+                    }
+                }
+            })?;
+        Ok(Self { handle, end_tx })
+    }
+
+    /// Stops the thread and waits for it to exit
+    pub fn stop(self) {
+        self.end_tx.send(()).ok(); // ignore send error (if thread already exited)
+        self.handle.join().ok(); // the thread does not panic
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tacton_json(name: &str) -> String {
+        format!(r#"{{ "$DATA_FORMAT": "MidAirHapticsAnimationFileFormat", "$REVISION": "0.1.0-alpha.3", "name": "{name}", "keyframes": [], "pattern_transform": {{ "geometric_transforms": {{ "translate": {{ "x": {{ "type": "f64", "value": 0 }}, "y": {{ "type": "f64", "value": 0 }}, "z": {{ "type": "f64", "value": 200 }} }}, "rotation": {{ "type": "f64", "value": 0 }}, "scale": {{ "x": {{ "type": "f64", "value": 1 }}, "y": {{ "type": "f64", "value": 1 }}, "z": {{ "type": "f64", "value": 1 }} }} }}, "intensity_factor": {{ "type": "f64", "value": 1 }}, "playback_speed": {{ "type": "f64", "value": 1 }} }}, "user_parameter_definitions": {{}} }}"#)
+    }
+
+    #[test]
+    fn test_tacton_library() {
+        let dir = std::env::temp_dir().join(format!("adaptics-engine-test-{}-library", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pulse.adaptics"), tacton_json("Pulse")).unwrap();
+        std::fs::write(dir.join("broken.adaptics"), "{").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let library = TactonLibrary::default();
+        assert!(library.set_dir(dir.join("missing")).is_err());
+        library.set_dir(dir.clone()).unwrap();
+        assert_eq!(library.list(), vec![TactonInfo { name: "Pulse".to_string(), file_name: "pulse.adaptics".to_string() }]);
+        for name in ["pulse", "pulse.adaptics", "Pulse"] {
+            assert_eq!(library.get(name).map(|(file_name, _)| file_name).as_deref(), Some("pulse.adaptics"), "{name}");
+        }
+        assert_eq!(library.get("broken"), None);
+        assert_eq!(library.rescan().unwrap(), Vec::<String>::new());
+
+        std::fs::write(dir.join("pulse.adaptics"), tacton_json("Long Pulse")).unwrap();
+        std::fs::write(dir.join("wave.adaptics"), tacton_json("Wave")).unwrap();
+        std::fs::remove_file(dir.join("broken.adaptics")).unwrap();
+        assert_eq!(library.rescan().unwrap(), vec!["broken.adaptics", "pulse.adaptics", "wave.adaptics"]);
+        assert!(library.get_file("pulse.adaptics").unwrap().contains("Long Pulse"));
+        assert_eq!(library.list().len(), 2);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
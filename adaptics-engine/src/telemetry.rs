@@ -0,0 +1,233 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::AdapticsError;
+
+/// Exclusive upper bounds (in microseconds) of the eval time histogram buckets. The last bucket has no upper bound.
+pub const EVAL_TIME_BUCKET_BOUNDS_US: [u64; 7] = [50, 100, 250, 500, 1000, 2500, 5000];
+const NUM_EVAL_TIME_BUCKETS: usize = EVAL_TIME_BUCKET_BOUNDS_US.len() + 1;
+
+/// How often telemetry is pushed to websocket clients and written to the telemetry file.
+pub const TELEMETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// The tracking frame rate is reported as 0 if no frame was received for this long.
+const TRACKING_STALE_AFTER: Duration = Duration::from_secs(1);
+
+fn duration_as_micros_u64(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Runtime telemetry, shared between the engine threads.
+/// Everything is atomic so recording never blocks the realtime threads.
+pub(crate) struct Telemetry {
+    start: Instant,
+    eval_time_buckets: [AtomicU64; NUM_EVAL_TIME_BUCKETS],
+    eval_time_total_us: AtomicU64,
+    eval_time_max_us: AtomicU64,
+    deadline_misses: AtomicU64,
+    deadline_miss_max_us: AtomicU64,
+    /// The last running total passed to [`Telemetry::record_total_deadline_misses`]
+    device_deadline_miss_total: AtomicU64,
+    dropped_playback_updates: AtomicU64,
+    dropped_tracking_frames: AtomicU64,
+    websocket_clients: AtomicU64,
+    websocket_clients_dropped: AtomicU64,
+    dropped_websocket_messages: AtomicU64,
+    /// `u64::MAX` if no round trip has been measured
+    serial_rtt_last_us: AtomicU64,
+    serial_rtt_max_us: AtomicU64,
+    tracking_frames: AtomicU64,
+    /// microseconds since `start`
+    last_tracking_frame_us: AtomicU64,
+    /// exponential moving average
+    tracking_frame_interval_us: AtomicU64,
+}
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            eval_time_buckets: Default::default(),
+            eval_time_total_us: AtomicU64::new(0),
+            eval_time_max_us: AtomicU64::new(0),
+            deadline_misses: AtomicU64::new(0),
+            deadline_miss_max_us: AtomicU64::new(0),
+            device_deadline_miss_total: AtomicU64::new(0),
+            dropped_playback_updates: AtomicU64::new(0),
+            dropped_tracking_frames: AtomicU64::new(0),
+            websocket_clients: AtomicU64::new(0),
+            websocket_clients_dropped: AtomicU64::new(0),
+            dropped_websocket_messages: AtomicU64::new(0),
+            serial_rtt_last_us: AtomicU64::new(u64::MAX),
+            serial_rtt_max_us: AtomicU64::new(0),
+            tracking_frames: AtomicU64::new(0),
+            last_tracking_frame_us: AtomicU64::new(0),
+            tracking_frame_interval_us: AtomicU64::new(0),
+        }
+    }
+}
+impl Telemetry {
+    /// Records the time taken by the pattern-eval thread to evaluate one batch of device samples
+    pub fn record_eval_time(&self, eval_time: Duration) {
+        let us = duration_as_micros_u64(eval_time);
+        let bucket = EVAL_TIME_BUCKET_BOUNDS_US.iter().position(|bound| us < *bound).unwrap_or(NUM_EVAL_TIME_BUCKETS - 1);
+        self.eval_time_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.eval_time_total_us.fetch_add(us, Ordering::Relaxed);
+        self.eval_time_max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub fn record_deadline_miss(&self, missed_by: Duration) {
+        self.deadline_misses.fetch_add(1, Ordering::Relaxed);
+        self.deadline_miss_max_us.fetch_max(duration_as_micros_u64(missed_by), Ordering::Relaxed);
+    }
+    /// For devices that only report a running total of missed deadlines (e.g. Ultraleap's missed callback iterations).
+    /// Only the misses since the last reported total are added, so it can be combined with [`Telemetry::record_deadline_miss`].
+    pub fn record_total_deadline_misses(&self, total: u64) {
+        let last_total = self.device_deadline_miss_total.swap(total, Ordering::Relaxed);
+        let new_misses = total.checked_sub(last_total).unwrap_or(total); // the device restarted counting
+        self.deadline_misses.fetch_add(new_misses, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_playback_update(&self) {
+        self.dropped_playback_updates.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_dropped_tracking_frame(&self) {
+        self.dropped_tracking_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_websocket_clients(&self, connected: usize) {
+        self.websocket_clients.store(connected as u64, Ordering::Relaxed);
+    }
+    pub fn record_websocket_client_dropped(&self) {
+        self.websocket_clients_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn record_dropped_websocket_messages(&self, dropped: usize) {
+        self.dropped_websocket_messages.fetch_add(dropped as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_serial_rtt(&self, rtt: Duration) {
+        let us = duration_as_micros_u64(rtt);
+        self.serial_rtt_last_us.store(us, Ordering::Relaxed);
+        self.serial_rtt_max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    pub fn record_tracking_frame(&self) {
+        let now_us = duration_as_micros_u64(self.start.elapsed());
+        let last_us = self.last_tracking_frame_us.swap(now_us, Ordering::Relaxed);
+        if self.tracking_frames.fetch_add(1, Ordering::Relaxed) > 0 {
+            let interval_us = now_us.saturating_sub(last_us);
+            let avg_us = self.tracking_frame_interval_us.load(Ordering::Relaxed);
+            let avg_us = if avg_us == 0 { interval_us } else { (avg_us * 7 + interval_us) / 8 };
+            self.tracking_frame_interval_us.store(avg_us, Ordering::Relaxed);
+        }
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn snapshot(&self) -> TelemetrySnapshot {
+        let uptime = self.start.elapsed();
+        let eval_time_histogram: Vec<u64> = self.eval_time_buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let eval_batches: u64 = eval_time_histogram.iter().sum();
+        let serial_rtt_last_us = self.serial_rtt_last_us.load(Ordering::Relaxed);
+        let tracking_frames = self.tracking_frames.load(Ordering::Relaxed);
+        let since_last_tracking_frame = uptime.saturating_sub(Duration::from_micros(self.last_tracking_frame_us.load(Ordering::Relaxed)));
+        let tracking_frame_interval_us = self.tracking_frame_interval_us.load(Ordering::Relaxed);
+        TelemetrySnapshot {
+            uptime_s: uptime.as_secs_f64(),
+            eval_batches,
+            eval_time_histogram,
+            eval_time_bucket_bounds_us: EVAL_TIME_BUCKET_BOUNDS_US.to_vec(),
+            eval_time_mean_us: if eval_batches == 0 { 0.0 } else { self.eval_time_total_us.load(Ordering::Relaxed) as f64 / eval_batches as f64 },
+            eval_time_max_us: self.eval_time_max_us.load(Ordering::Relaxed),
+            deadline_misses: self.deadline_misses.load(Ordering::Relaxed),
+            deadline_miss_max_us: self.deadline_miss_max_us.load(Ordering::Relaxed),
+            dropped_playback_updates: self.dropped_playback_updates.load(Ordering::Relaxed),
+            dropped_tracking_frames: self.dropped_tracking_frames.load(Ordering::Relaxed),
+            websocket_clients: self.websocket_clients.load(Ordering::Relaxed),
+            websocket_clients_dropped: self.websocket_clients_dropped.load(Ordering::Relaxed),
+            dropped_websocket_messages: self.dropped_websocket_messages.load(Ordering::Relaxed),
+            serial_rtt_last_us: if serial_rtt_last_us == u64::MAX { None } else { Some(serial_rtt_last_us) },
+            serial_rtt_max_us: if serial_rtt_last_us == u64::MAX { None } else { Some(self.serial_rtt_max_us.load(Ordering::Relaxed)) },
+            tracking_frames,
+            tracking_frame_rate: if tracking_frames < 2 || tracking_frame_interval_us == 0 || since_last_tracking_frame > TRACKING_STALE_AFTER { 0.0 } else { 1e6 / tracking_frame_interval_us as f64 },
+        }
+    }
+}
+
+/// A point-in-time copy of the engine's runtime telemetry.
+/// Counters are totals since the engine was started.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TelemetrySnapshot {
+    /// Seconds since the engine was started
+    pub uptime_s: f64,
+    /// Number of batches of device samples evaluated by the pattern-eval thread
+    pub eval_batches: u64,
+    /// Number of eval batches per eval time bucket, see `eval_time_bucket_bounds_us`
+    pub eval_time_histogram: Vec<u64>,
+    /// Exclusive upper bounds (in microseconds) of `eval_time_histogram`. The last bucket has no upper bound.
+    pub eval_time_bucket_bounds_us: Vec<u64>,
+    pub eval_time_mean_us: f64,
+    pub eval_time_max_us: u64,
+    /// Number of batches of device samples that were not ready before the device needed them
+    pub deadline_misses: u64,
+    pub deadline_miss_max_us: u64,
+    /// Playback updates dropped because the network thread (or `get_playback_updates` caller) lagged
+    pub dropped_playback_updates: u64,
+    /// Tracking frames dropped because the pattern-eval thread lagged
+    pub dropped_tracking_frames: u64,
+    pub websocket_clients: u64,
+    /// Websocket clients removed because sending to them failed
+    pub websocket_clients_dropped: u64,
+    /// Messages dropped from the outbound queue of a websocket client because the client could not keep up
+    pub dropped_websocket_messages: u64,
+    /// Round trip time of the last packet sent to the vibrotactile grid device, if any
+    pub serial_rtt_last_us: Option<u64>,
+    pub serial_rtt_max_us: Option<u64>,
+    /// Number of frames received from the tracking system
+    pub tracking_frames: u64,
+    /// Tracking frames per second (moving average), 0 if tracking is stalled or disabled
+    pub tracking_frame_rate: f64,
+}
+
+/// Appends a telemetry snapshot as a line of JSON to `path` every [`TELEMETRY_INTERVAL`], until `end_rx` receives or disconnects.
+pub(crate) fn telemetry_file_loop(telemetry: &Telemetry, path: &std::path::Path, end_rx: &crossbeam_channel::Receiver<()>) -> Result<(), AdapticsError> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    while let Err(crossbeam_channel::RecvTimeoutError::Timeout) = end_rx.recv_timeout(TELEMETRY_INTERVAL) {
+        writeln!(file, "{}", serde_json::to_string(&telemetry.snapshot())?)?;
+    }
+    writeln!(file, "{}", serde_json::to_string(&telemetry.snapshot())?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_time_histogram() {
+        let telemetry = Telemetry::default();
+        telemetry.record_eval_time(Duration::from_micros(10));
+        telemetry.record_eval_time(Duration::from_micros(100));
+        telemetry.record_eval_time(Duration::from_millis(20));
+        let snapshot = telemetry.snapshot();
+        assert_eq!(snapshot.eval_batches, 3);
+        assert_eq!(snapshot.eval_time_histogram, vec![1, 0, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(snapshot.eval_time_max_us, 20_000);
+        assert_eq!(snapshot.serial_rtt_last_us, None);
+        assert!(snapshot.tracking_frame_rate.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_deadline_misses() {
+        let telemetry = Telemetry::default();
+        telemetry.record_deadline_miss(Duration::from_micros(100));
+        telemetry.record_total_deadline_misses(3);
+        telemetry.record_total_deadline_misses(5);
+        telemetry.record_deadline_miss(Duration::from_micros(50));
+        assert_eq!(telemetry.snapshot().deadline_misses, 7);
+        telemetry.record_total_deadline_misses(1); // device restarted counting
+        assert_eq!(telemetry.snapshot().deadline_misses, 8);
+        assert_eq!(telemetry.snapshot().deadline_miss_max_us, 100);
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{self, BufRead, Read, Write};
+
+/// Maximum size of the request line and headers of a request
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+/// Maximum number of headers in a request
+const MAX_HEADERS: usize = 64;
+
+/// A rejected request: the HTTP status and reason to respond with
+pub(crate) type HttpRejection = (u16, &'static str);
+
+/// The request line and headers of an HTTP/1.x request (see rfc9112#section-2.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HttpRequest {
+    pub method: String,
+    /// The request target in origin-form, e.g. "/?token=abc"
+    pub target: String,
+    pub version: String,
+    /// Header names and values in the order they were received, values are trimmed
+    pub headers: Vec<(String, String)>,
+}
+impl HttpRequest {
+    /// The value of the first header named `name` (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// true if the comma separated header `name` contains `token` (case-insensitive), e.g. `Connection: keep-alive, Upgrade`
+    pub fn header_contains_token(&self, name: &str, token: &str) -> bool {
+        self.headers.iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .flat_map(|(_, v)| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    /// The length of the request body, 0 if there is none. Chunked bodies are not supported.
+    pub fn content_length(&self, max_len: usize) -> Result<usize, HttpRejection> {
+        if self.header("Transfer-Encoding").is_some() { return Err((501, "Not Implemented")); }
+        let Some(len) = self.header("Content-Length") else { return Ok(0); };
+        let len = len.parse::<usize>().map_err(|_| (400, "Bad Request"))?;
+        if len > max_len { return Err((413, "Content Too Large")); }
+        Ok(len)
+    }
+
+    /// The target without the query string
+    pub fn path(&self) -> &str {
+        self.target.split_once('?').map_or(&self.target, |(path, _)| path)
+    }
+
+    /// The percent-decoded value of the first query parameter named `name`
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let (_, query) = self.target.split_once('?')?;
+        query.split('&')
+            .filter_map(|pair| { let (k, v) = pair.split_once('=').unwrap_or((pair, "")); Some((percent_decode(k, true)?, v)) })
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| percent_decode(v, true))
+    }
+}
+
+/// Decodes `%XX` escapes (and `+` as a space if `plus_as_space`, in query strings) in a path segment or query string component.
+/// Returns `None` for invalid escapes or UTF-8.
+pub(crate) fn percent_decode(s: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        bytes.push(match b {
+            b'+' if plus_as_space => b' ',
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            },
+            b => b,
+        });
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads the request line and headers, leaving the body (or the first websocket frames) in `reader`.
+/// Fails with [`io::ErrorKind::InvalidData`] if the request is malformed or too large.
+pub(crate) fn read_http_request(reader: &mut impl BufRead) -> io::Result<HttpRequest> {
+    let mut reader = reader.take(MAX_HEAD_LEN);
+    let mut read_line = || -> io::Result<String> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 { return Err(io::ErrorKind::UnexpectedEof.into()); }
+        if !line.ends_with('\n') { return Err(invalid_data("request head too large")); }
+        line.truncate(line.trim_end_matches(['\r', '\n']).len());
+        Ok(line)
+    };
+
+    let request_line = read_line()?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid_data("invalid request line"));
+    };
+    if method.is_empty() || !target.starts_with('/') || !version.starts_with("HTTP/1.") {
+        return Err(invalid_data("invalid request line"));
+    }
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line()?;
+        if line.is_empty() { break; }
+        if line.starts_with([' ', '\t']) { return Err(invalid_data("obsolete header line folding")); }
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid_data("invalid header line"))?;
+        if name.is_empty() || name.contains(|c: char| c.is_ascii_whitespace()) { return Err(invalid_data("invalid header name")); }
+        if headers.len() == MAX_HEADERS { return Err(invalid_data("too many headers")); }
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+
+    Ok(HttpRequest { method: method.to_string(), target: target.to_string(), version: version.to_string(), headers })
+}
+
+/// Writes a complete response, the connection is closed afterwards
+pub(crate) fn write_http_response(stream: &mut impl Write, status: u16, reason: &str, content_type: &str, extra_headers: &[(&str, &str)], body: &[u8]) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {status} {reason}\r\nConnection: close\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n", body.len())?;
+    for (name, value) in extra_headers {
+        write!(stream, "{name}: {value}\r\n")?;
+    }
+    stream.write_all(b"\r\n")?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Writes a response without a body (other than `reason`), the connection is closed afterwards
+pub(crate) fn write_http_error(stream: &mut impl Write, status: u16, reason: &str, extra_headers: &[(&str, &str)]) -> io::Result<()> {
+    write_http_response(stream, status, reason, "text/plain", extra_headers, reason.as_bytes())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_http_request() {
+        let mut stream: &[u8] = b"GET /?token=a%20b+c&x HTTP/1.1\r\nHost: localhost\r\nconnection:  keep-alive, Upgrade \r\n\r\nframe";
+        let request = read_http_request(&mut stream).unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path(), "/");
+        assert_eq!(request.query_param("token").as_deref(), Some("a b c"));
+        assert_eq!(request.query_param("x").as_deref(), Some(""));
+        assert_eq!(request.query_param("y"), None);
+        assert_eq!(request.header("HOST"), Some("localhost"));
+        assert!(request.header_contains_token("Connection", "upgrade"));
+        assert!(!request.header_contains_token("Connection", "close"));
+        assert_eq!(stream, b"frame", "the rest of the stream should not be consumed");
+        assert_eq!(request.content_length(10), Ok(0));
+
+        let mut upload: &[u8] = b"PUT /api/pattern HTTP/1.1\r\nContent-Length: 11\r\n\r\n";
+        let upload = read_http_request(&mut upload).unwrap();
+        assert_eq!(upload.content_length(11), Ok(11));
+        assert_eq!(upload.content_length(10), Err((413, "Content Too Large")));
+        let mut chunked: &[u8] = b"PUT /api/pattern HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(read_http_request(&mut chunked).unwrap().content_length(10), Err((501, "Not Implemented")));
+
+        let mut bad_header: &[u8] = b"GET / HTTP/1.1\r\nHost localhost\r\n\r\n";
+        assert_eq!(read_http_request(&mut bad_header).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut folded: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\n b\r\n\r\n";
+        assert_eq!(read_http_request(&mut folded).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut not_http: &[u8] = b"hello\r\n\r\n";
+        assert_eq!(read_http_request(&mut not_http).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let too_large = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "x".repeat(usize::try_from(MAX_HEAD_LEN).unwrap()));
+        assert_eq!(read_http_request(&mut too_large.as_bytes()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let mut truncated: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\n";
+        assert_eq!(read_http_request(&mut truncated).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
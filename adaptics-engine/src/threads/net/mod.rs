@@ -1 +1,4 @@
-pub(crate) mod websocket;
\ No newline at end of file
+pub(crate) mod websocket;
+pub(crate) mod osc;
+pub(crate) mod http;
+pub(crate) mod rest;
\ No newline at end of file
@@ -0,0 +1,270 @@
+//! Minimal [OSC 1.0](https://opensoundcontrol.stanford.edu/spec-1_0.html) encoding and decoding, supporting the argument types used by the engine,
+//! and the OSC input server translating OSC messages into [`PatternEvalUpdate`]s.
+
+use std::{net::UdpSocket, time::Duration};
+
+use crate::{PatternEvalUpdate, util::AdapticsError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum OscArg {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Double(f64),
+    Bool(bool),
+}
+impl OscArg {
+    /// Numeric value of the argument, OSC controllers send parameter values as any of int, float, double or bool
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            OscArg::Int(i) => Some(f64::from(*i)),
+            OscArg::Float(f) => Some(f64::from(*f)),
+            OscArg::Double(d) => Some(*d),
+            OscArg::Bool(b) => Some(f64::from(u8::from(*b))),
+            OscArg::String(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+/// OSC timetag meaning "immediately"
+const OSC_TIMETAG_IMMEDIATE: u64 = 1;
+
+fn write_osc_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    // null terminated, padded to a multiple of 4 bytes
+    buf.extend(std::iter::repeat_n(0, 4 - s.len() % 4));
+}
+
+impl OscMessage {
+    pub fn new(address: impl Into<String>, args: Vec<OscArg>) -> Self {
+        Self { address: address.into(), args }
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_osc_string(buf, &self.address);
+        let type_tags: String = std::iter::once(',').chain(self.args.iter().map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::String(_) => 's',
+            OscArg::Double(_) => 'd',
+            OscArg::Bool(true) => 'T',
+            OscArg::Bool(false) => 'F',
+        })).collect();
+        write_osc_string(buf, &type_tags);
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(i) => buf.extend_from_slice(&i.to_be_bytes()),
+                OscArg::Float(f) => buf.extend_from_slice(&f.to_be_bytes()),
+                OscArg::String(s) => write_osc_string(buf, s),
+                OscArg::Double(d) => buf.extend_from_slice(&d.to_be_bytes()),
+                OscArg::Bool(_) => {}, // no argument data
+            }
+        }
+    }
+}
+
+/// Encodes `messages` as a single OSC bundle to be processed immediately
+pub(crate) fn encode_osc_bundle(messages: &[OscMessage]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_osc_string(&mut buf, "#bundle");
+    buf.extend_from_slice(&OSC_TIMETAG_IMMEDIATE.to_be_bytes());
+    let mut message_buf = Vec::new();
+    for message in messages {
+        message_buf.clear();
+        message.encode(&mut message_buf);
+        let message_len = i32::try_from(message_buf.len()).expect("OSC message too long");
+        buf.extend_from_slice(&message_len.to_be_bytes());
+        buf.extend_from_slice(&message_buf);
+    }
+    buf
+}
+
+/// Takes the first `n` bytes of `buf`, advancing it
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8], AdapticsError> {
+    if buf.len() < n { return Err(AdapticsError::new("truncated OSC packet")); }
+    let (head, rest) = buf.split_at(n);
+    *buf = rest;
+    Ok(head)
+}
+
+fn read_osc_string<'a>(buf: &mut &'a [u8]) -> Result<&'a str, AdapticsError> {
+    let len = buf.iter().position(|&b| b == 0).ok_or(AdapticsError::new("unterminated OSC string"))?;
+    let s = take(buf, (len / 4 + 1) * 4)?;
+    Ok(std::str::from_utf8(&s[..len])?)
+}
+
+impl OscMessage {
+    fn decode(mut buf: &[u8]) -> Result<Self, AdapticsError> {
+        let address = read_osc_string(&mut buf)?.to_string();
+        if buf.is_empty() { return Ok(Self::new(address, vec![])); } // type tag string may be omitted by older implementations
+        let type_tags = read_osc_string(&mut buf)?.strip_prefix(',').ok_or(AdapticsError::new("OSC type tag string must start with ','"))?;
+        let args = type_tags.chars().map(|type_tag| Ok(match type_tag {
+            'i' => OscArg::Int(i32::from_be_bytes(take(&mut buf, 4)?.try_into()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(take(&mut buf, 4)?.try_into()?)),
+            's' => OscArg::String(read_osc_string(&mut buf)?.to_string()),
+            'd' => OscArg::Double(f64::from_be_bytes(take(&mut buf, 8)?.try_into()?)),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            _ => return Err(AdapticsError::new(&format!("unsupported OSC type tag '{type_tag}'"))),
+        })).collect::<Result<_, AdapticsError>>()?;
+        Ok(Self { address, args })
+    }
+}
+
+/// Decodes an OSC packet (a message or a possibly nested bundle) into its messages.
+/// Bundle timetags are ignored, all messages are processed immediately.
+pub(crate) fn decode_osc_packet(packet: &[u8]) -> Result<Vec<OscMessage>, AdapticsError> {
+    fn decode_into(mut packet: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), AdapticsError> {
+        if packet.starts_with(b"#bundle\0") {
+            take(&mut packet, 16)?; // "#bundle" and timetag
+            while !packet.is_empty() {
+                let element_len = usize::try_from(i32::from_be_bytes(take(&mut packet, 4)?.try_into()?))?;
+                decode_into(take(&mut packet, element_len)?, messages)?;
+            }
+        } else {
+            messages.push(OscMessage::decode(packet)?);
+        }
+        Ok(())
+    }
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages)?;
+    Ok(messages)
+}
+
+
+/// How often the OSC server checks if it should exit
+const OSC_RECV_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Configuration of the OSC input server.
+///
+/// The server listens for the following OSC messages:
+/// - `/adaptics/param/<name> <number>`: sets the user parameter `<name>`
+/// - `/adaptics/play [<tacton>]`: plays `<tacton>` from the tacton library (if given, see [`PatternEvalUpdate::PlayTacton`]) or the current pattern from the start
+/// - `/adaptics/pause`, `/adaptics/resume`, `/adaptics/stop`
+/// - `/adaptics/seek <ms>`: moves playback to the pattern time `<ms>`
+///
+/// Numbers may be sent as int, float, double or bool arguments.
+/// Play, pause, resume and stop ignore messages with a single argument of 0, which many OSC controllers send when a button is released.
+#[derive(Debug, Clone)]
+pub struct OscInputConfig {
+    /// UDP address to listen on, e.g. "0.0.0.0:9001"
+    pub bind_addr: String,
+}
+
+/// Translates an OSC input message into updates for the pattern-eval thread, see [`OscInputConfig`]
+fn osc_message_to_updates(message: &OscMessage) -> Result<Vec<PatternEvalUpdate>, AdapticsError> {
+    let command = message.address.strip_prefix("/adaptics/").ok_or(AdapticsError::new("unknown OSC address"))?;
+    let number_arg = || message.args.first().and_then(OscArg::as_f64).ok_or(AdapticsError::new("expected a number argument"));
+    let is_button_release = matches!(message.args.as_slice(), [arg] if arg.as_f64().is_some_and(|v| v.abs() < f64::EPSILON));
+
+    if let Some(name) = command.strip_prefix("param/") {
+        return Ok(vec![PatternEvalUpdate::UserParameter { name: name.to_string(), value: number_arg()? }]);
+    }
+    Ok(match command {
+        "play" | "pause" | "resume" | "stop" if is_button_release => vec![],
+        "play" => match message.args.first() {
+            Some(OscArg::String(name)) => vec![PatternEvalUpdate::PlayTacton { name: name.clone() }],
+            _ => vec![PatternEvalUpdate::Play { pattern_time: 0.0 }],
+        },
+        "pause" => vec![PatternEvalUpdate::Pause {}],
+        "resume" => vec![PatternEvalUpdate::Resume {}],
+        "stop" => vec![PatternEvalUpdate::Stop {}],
+        "seek" => vec![PatternEvalUpdate::Seek { pattern_time: number_arg()? }],
+        _ => return Err(AdapticsError::new("unknown OSC address")),
+    })
+}
+
+/// Listens for OSC messages on `config.bind_addr` and sends the resulting updates to the pattern-eval thread,
+/// until `end_osc_rx` receives or the pattern-eval thread disconnects.
+pub(crate) fn start_osc_server(
+    config: &OscInputConfig,
+    patteval_update_tx: &crossbeam_channel::Sender<PatternEvalUpdate>,
+    end_osc_rx: &crossbeam_channel::Receiver<()>,
+) -> Result<(), AdapticsError> {
+    let socket = UdpSocket::bind(&config.bind_addr)?;
+    socket.set_read_timeout(Some(OSC_RECV_TIMEOUT))?;
+    println!("listening for OSC messages on {}", socket.local_addr()?);
+
+    let mut buf = vec![0; 65536]; // max UDP payload size
+    while let Err(crossbeam_channel::TryRecvError::Empty) = end_osc_rx.try_recv() {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let messages = match decode_osc_packet(&buf[..len]) {
+            Ok(messages) => messages,
+            Err(e) => { eprintln!("invalid OSC packet: {e}"); continue; },
+        };
+        for message in &messages {
+            match osc_message_to_updates(message) {
+                Ok(updates) => for update in updates {
+                    if patteval_update_tx.send(update).is_err() { return Ok(()); } // pattern-eval thread exited
+                },
+                Err(e) => eprintln!("invalid OSC message '{}': {e}", message.address),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_osc_message() {
+        let mut buf = Vec::new();
+        OscMessage::new("/a", vec![OscArg::Int(1), OscArg::Float(0.5), OscArg::String("abcd".to_string())]).encode(&mut buf);
+        assert_eq!(buf, [
+            b'/', b'a', 0, 0,
+            b',', b'i', b'f', b's', 0, 0, 0, 0,
+            0, 0, 0, 1,
+            0x3f, 0, 0, 0,
+            b'a', b'b', b'c', b'd', 0, 0, 0, 0,
+        ]);
+
+        let bundle = encode_osc_bundle(&[OscMessage::new("/a", vec![])]);
+        assert_eq!(&bundle[..8], b"#bundle\0");
+        assert_eq!(&bundle[16..20], &8i32.to_be_bytes());
+        assert_eq!(bundle.len(), 16 + 4 + 8);
+    }
+
+    #[test]
+    fn test_decode_osc_packet() {
+        let message = OscMessage::new("/adaptics/param/progress", vec![OscArg::Float(0.5), OscArg::Double(2.0), OscArg::Bool(true), OscArg::String("x".to_string())]);
+        let mut buf = Vec::new();
+        message.encode(&mut buf);
+        assert_eq!(decode_osc_packet(&buf).unwrap(), vec![message.clone()]);
+
+        let stop = OscMessage::new("/adaptics/stop", vec![]);
+        let bundle = encode_osc_bundle(&[message.clone(), stop.clone()]);
+        assert_eq!(decode_osc_packet(&bundle).unwrap(), vec![message, stop]);
+
+        assert!(decode_osc_packet(&bundle[..bundle.len() - 1]).is_err());
+        assert!(decode_osc_packet(b"/a\0\0,x\0\0").is_err());
+    }
+
+    #[test]
+    fn test_osc_message_to_updates() {
+        let updates = |address: &str, args: Vec<OscArg>| osc_message_to_updates(&OscMessage::new(address, args));
+
+        assert!(matches!(updates("/adaptics/param/progress", vec![OscArg::Int(1)]).unwrap().as_slice(),
+            [PatternEvalUpdate::UserParameter { name, value }] if name == "progress" && (*value - 1.0).abs() < f64::EPSILON));
+        assert!(matches!(updates("/adaptics/play", vec![OscArg::String("pulse".to_string())]).unwrap().as_slice(),
+            [PatternEvalUpdate::PlayTacton { name }] if name == "pulse"));
+        assert!(matches!(updates("/adaptics/play", vec![]).unwrap().as_slice(), [PatternEvalUpdate::Play { .. }]));
+        assert!(matches!(updates("/adaptics/stop", vec![OscArg::Float(1.0)]).unwrap().as_slice(), [PatternEvalUpdate::Stop {}]));
+        assert!(updates("/adaptics/stop", vec![OscArg::Float(0.0)]).unwrap().is_empty(), "button release should be ignored");
+        assert!(matches!(updates("/adaptics/seek", vec![OscArg::Double(250.0)]).unwrap().as_slice(), [PatternEvalUpdate::Seek { .. }]));
+
+        assert!(updates("/adaptics/param/progress", vec![]).is_err());
+        assert!(updates("/other/stop", vec![]).is_err());
+    }
+}
\ No newline at end of file
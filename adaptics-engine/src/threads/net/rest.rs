@@ -0,0 +1,213 @@
+//! Small HTTP API on the websocket listener, for scripts and tools that do not want to implement a websocket client.
+//! Requests are translated into the same [`PatternEvalUpdate`]s and [`AdapticsWSQuery`]s as websocket requests,
+//! and answered with the JSON of an [`AdapticsWSResult`], e.g. `curl -X POST http://127.0.0.1:8037/api/stop`.
+//!
+//! | Route | Methods | |
+//! |---|---|---|
+//! | `/api/status` | `GET` | [`AdapticsWSQuery::GetPlaybackState`] |
+//! | `/api/version` | `GET` | [`AdapticsWSQuery::GetVersion`] |
+//! | `/api/tactons` | `GET` | [`AdapticsWSQuery::GetTactons`] |
+//! | `/api/tactons/<name>/play` | `POST` | [`PatternEvalUpdate::PlayTacton`] |
+//! | `/api/pattern` | `GET`, `PUT` | Gets or replaces the pattern, the body of `PUT` is the pattern file |
+//! | `/api/parameters` | `GET`, `POST` | Gets the evaluator parameters, or sets the user parameters in a body like `{ "speed": 2 }` |
+//! | `/api/play` | `POST` | Starts playback, at the `pattern_time` given in an optional body like `{ "pattern_time": 500 }` |
+//! | `/api/pause`, `/api/resume`, `/api/stop` | `POST` | |
+//! | `/api/update` | `POST` | Applies the [`PatternEvalUpdate`] in the body, as sent over the websocket |
+
+use std::{io::{self, BufReader, Read, Write}, net::TcpStream};
+use pattern_evaluator::{MAHTime, UserParameters};
+use serde::Deserialize;
+
+use crate::{PatternEvalUpdate, threads::pattern::playback::{PatternEvalRequest, PatternEvalReplyTx}};
+use super::{http::{self, HttpRequest}, websocket::{AdapticsWSQuery, AdapticsWSResponse, AdapticsWSResult}};
+
+/// Paths starting with this are handled by the REST API instead of the websocket server
+pub(crate) const API_PREFIX: &str = "/api/";
+/// Maximum size of a request body (e.g. an uploaded pattern)
+const MAX_BODY_LEN: usize = 16 * 1024 * 1024;
+/// How long a request waits for the pattern-eval thread to answer
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// A REST request, translated into queries and updates for the pattern-eval thread
+#[derive(Debug)]
+enum RestRequest {
+    Query(AdapticsWSQuery),
+    /// Applied in order, stopping at the first update that is rejected
+    Updates(Vec<PatternEvalUpdate>),
+}
+
+#[derive(Debug, PartialEq)]
+enum RestError {
+    NotFound,
+    /// The methods allowed for the route, sent in the `Allow` header
+    MethodNotAllowed(&'static str),
+    InvalidBody(String),
+}
+
+#[derive(Default, Deserialize)]
+struct PlayBody {
+    #[serde(default)]
+    pattern_time: MAHTime,
+}
+
+fn parse_json<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, RestError> {
+    serde_json::from_slice(body).map_err(|e| RestError::InvalidBody(format!("invalid body: {e}")))
+}
+
+/// Translates the route (the path without [`API_PREFIX`]) and body into a [`RestRequest`]
+fn parse_rest_request(method: &str, route: &str, body: &[u8]) -> Result<RestRequest, RestError> {
+    let update = |update| Ok(RestRequest::Updates(vec![update]));
+    if let Some(name) = route.strip_prefix("tactons/").and_then(|route| route.strip_suffix("/play")) {
+        if method != "POST" { return Err(RestError::MethodNotAllowed("POST")); }
+        let name = http::percent_decode(name, false).ok_or(RestError::NotFound)?;
+        return update(PatternEvalUpdate::PlayTacton { name });
+    }
+    match (route, method) {
+        ("status", "GET") => Ok(RestRequest::Query(AdapticsWSQuery::GetPlaybackState {})),
+        ("version", "GET") => Ok(RestRequest::Query(AdapticsWSQuery::GetVersion {})),
+        ("tactons", "GET") => Ok(RestRequest::Query(AdapticsWSQuery::GetTactons {})),
+        ("pattern", "GET") => Ok(RestRequest::Query(AdapticsWSQuery::GetPattern {})),
+        ("pattern", "PUT") => {
+            let pattern_json = String::from_utf8(body.to_vec()).map_err(|_| RestError::InvalidBody("pattern is not valid UTF-8".to_string()))?;
+            update(PatternEvalUpdate::Pattern { pattern_json, crossfade_ms: None, path_interpolation_ms: None })
+        },
+        ("parameters", "GET") => Ok(RestRequest::Query(AdapticsWSQuery::GetParameters {})),
+        ("parameters", "POST") => {
+            let user_parameters: UserParameters = parse_json(body)?;
+            Ok(RestRequest::Updates(user_parameters.into_iter().map(|(name, value)| PatternEvalUpdate::UserParameter { name, value }).collect()))
+        },
+        ("play", "POST") => {
+            let PlayBody { pattern_time } = if body.is_empty() { PlayBody::default() } else { parse_json(body)? };
+            update(PatternEvalUpdate::Play { pattern_time })
+        },
+        ("pause", "POST") => update(PatternEvalUpdate::Pause {}),
+        ("resume", "POST") => update(PatternEvalUpdate::Resume {}),
+        ("stop", "POST") => update(PatternEvalUpdate::Stop {}),
+        ("update", "POST") => update(parse_json(body)?),
+        ("status" | "version" | "tactons", _) => Err(RestError::MethodNotAllowed("GET")),
+        ("pattern", _) => Err(RestError::MethodNotAllowed("GET, PUT")),
+        ("parameters", _) => Err(RestError::MethodNotAllowed("GET, POST")),
+        ("play" | "pause" | "resume" | "stop" | "update", _) => Err(RestError::MethodNotAllowed("POST")),
+        _ => Err(RestError::NotFound),
+    }
+}
+
+/// Sends a query or update (with a `reply_tx`) to the pattern-eval thread and waits for its response. Returns `None` if the pattern-eval thread has exited.
+fn send_pattern_eval_request(
+    patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>,
+    request: impl FnOnce(PatternEvalReplyTx) -> PatternEvalRequest,
+) -> Option<Result<AdapticsWSResponse, String>> {
+    let (reply_tx, reply_rx) = PatternEvalReplyTx::oneshot();
+    patteval_request_tx.send(request(reply_tx)).ok()?;
+    Some(reply_rx.recv_timeout(REQUEST_TIMEOUT).map_or_else(|e| Err(format!("no response from the pattern-eval thread: {e}")), |(_, result)| result))
+}
+
+/// Sends the request to the pattern-eval thread and waits for the response. Returns `None` if the pattern-eval thread has exited.
+fn execute_rest_request(request: RestRequest, patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>) -> Option<Result<AdapticsWSResponse, String>> {
+    match request {
+        RestRequest::Query(query) => send_pattern_eval_request(patteval_request_tx, |reply_tx| PatternEvalRequest::Query { query, reply_tx }),
+        RestRequest::Updates(updates) => {
+            for update in updates {
+                let result = send_pattern_eval_request(patteval_request_tx, |reply_tx| PatternEvalRequest::Update { update, reply_tx: Some(reply_tx) })?;
+                if result.is_err() { return Some(result); }
+            }
+            Some(Ok(AdapticsWSResponse::Ack {}))
+        },
+    }
+}
+
+/// Reads the body of `request` and responds with the result of its route
+pub(crate) fn handle_rest_request(
+    request: &HttpRequest,
+    bufread: &mut BufReader<TcpStream>,
+    patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>,
+) -> io::Result<()> {
+    let body = match request.content_length(MAX_BODY_LEN) {
+        Ok(len) => {
+            if len > 0 && request.header("Expect").is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue")) {
+                bufread.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            }
+            let mut body = vec![0; len];
+            bufread.read_exact(&mut body)?;
+            body
+        },
+        Err((status, reason)) => return http::write_http_error(bufread.get_mut(), status, reason, &[]),
+    };
+
+    let route = request.path().strip_prefix(API_PREFIX).unwrap_or_default();
+    let (status, reason, allow, result) = match parse_rest_request(&request.method, route, &body).map(|r| execute_rest_request(r, patteval_request_tx)) {
+        Ok(Some(Ok(response))) => (200, "OK", None, AdapticsWSResult::Ok { response }),
+        Ok(Some(Err(message))) => (400, "Bad Request", None, AdapticsWSResult::Error { message }),
+        Ok(None) => (503, "Service Unavailable", None, AdapticsWSResult::Error { message: "engine stopped".to_string() }),
+        Err(RestError::InvalidBody(message)) => (400, "Bad Request", None, AdapticsWSResult::Error { message }),
+        Err(RestError::NotFound) => (404, "Not Found", None, AdapticsWSResult::Error { message: format!("unknown route {}", request.path()) }),
+        Err(RestError::MethodNotAllowed(allowed)) => (405, "Method Not Allowed", Some(allowed), AdapticsWSResult::Error { message: format!("{} only supports {allowed}", request.path()) }),
+    };
+    let extra_headers: &[_] = if let Some(allowed) = allow { &[("Allow", allowed)] } else { &[] };
+    http::write_http_response(bufread.get_mut(), status, reason, "application/json", extra_headers, serde_json::to_string(&result).unwrap().as_bytes())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_rest_request() {
+        assert!(matches!(parse_rest_request("GET", "status", b""), Ok(RestRequest::Query(AdapticsWSQuery::GetPlaybackState {}))));
+        assert!(matches!(parse_rest_request("PUT", "pattern", b"{}"), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::Pattern { pattern_json, .. }] if pattern_json == "{}")));
+        assert!(matches!(parse_rest_request("POST", "parameters", br#"{ "a": 1 }"#), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::UserParameter { name, value }] if name == "a" && (*value - 1.0).abs() < f64::EPSILON)));
+        assert!(matches!(parse_rest_request("POST", "play", b""), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::Play { pattern_time }] if pattern_time.abs() < f64::EPSILON)));
+        assert!(matches!(parse_rest_request("POST", "play", br#"{ "pattern_time": 500 }"#), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::Play { pattern_time }] if (*pattern_time - 500.0).abs() < f64::EPSILON)));
+        assert!(matches!(parse_rest_request("POST", "update", br#"{ "cmd": "seek", "data": { "pattern_time": 10 } }"#), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::Seek { .. }])));
+        assert!(matches!(parse_rest_request("POST", "tactons/Short%20Pulse+1/play", b""), Ok(RestRequest::Updates(u)) if matches!(u.as_slice(), [PatternEvalUpdate::PlayTacton { name }] if name == "Short Pulse+1")));
+        assert!(matches!(parse_rest_request("GET", "tactons/pulse/play", b""), Err(RestError::MethodNotAllowed("POST"))));
+
+        assert!(matches!(parse_rest_request("POST", "parameters", b"[1]"), Err(RestError::InvalidBody(_))));
+        assert!(matches!(parse_rest_request("GET", "stop", b""), Err(RestError::MethodNotAllowed("POST"))));
+        assert!(matches!(parse_rest_request("DELETE", "pattern", b""), Err(RestError::MethodNotAllowed("GET, PUT"))));
+        assert!(matches!(parse_rest_request("GET", "nope", b""), Err(RestError::NotFound)));
+    }
+
+    #[test]
+    fn test_handle_rest_request() {
+        let (patteval_request_tx, patteval_request_rx) = crossbeam_channel::unbounded();
+        let pattern_eval = std::thread::spawn(move || {
+            for request in patteval_request_rx {
+                match request {
+                    PatternEvalRequest::Update { update: PatternEvalUpdate::Pattern { .. }, reply_tx } => { reply_tx.unwrap().send(Err("invalid pattern".to_string())).unwrap(); },
+                    PatternEvalRequest::Update { reply_tx, .. } => { reply_tx.unwrap().send(Ok(AdapticsWSResponse::Ack {})).unwrap(); },
+                    PatternEvalRequest::Query { reply_tx, .. } => { reply_tx.send(Ok(AdapticsWSResponse::Version { engine_version: "test".to_string(), protocol_version: 1 })).unwrap(); },
+                    PatternEvalRequest::TactonsChanged { .. } => unreachable!(),
+                }
+            }
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let respond = |request: &str| {
+            let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            let mut bufread = BufReader::new(listener.accept().unwrap().0);
+            let request = http::read_http_request(&mut bufread).unwrap();
+            handle_rest_request(&request, &mut bufread, &patteval_request_tx).unwrap();
+            drop(bufread);
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = respond("GET /api/version HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with(r#"{"status":"ok","response":{"type":"version","data":{"engine_version":"test","protocol_version":1}}}"#), "{response}");
+        let response = respond("POST /api/stop HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        let response = respond("PUT /api/pattern HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\n{}");
+        assert!(response.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 400 Bad Request\r\n"), "{response}");
+        assert!(response.ends_with(r#"{"status":"error","message":"invalid pattern"}"#), "{response}");
+        let response = respond("GET /api/stop HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n") && response.contains("\r\nAllow: POST\r\n"), "{response}");
+
+        drop(patteval_request_tx);
+        pattern_eval.join().unwrap();
+    }
+}
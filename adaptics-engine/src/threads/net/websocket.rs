@@ -1,12 +1,13 @@
-use std::{io::prelude::*, sync::{Arc, Mutex}, time::Duration, net::TcpListener};
+use std::{collections::HashMap, io::prelude::*, sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}, time::Instant, net::TcpListener};
 use std::{io::BufReader, net::TcpStream};
-use pattern_evaluator::BrushAtAnimLocalTime;
+use pattern_evaluator::{BrushAtAnimLocalTime, MAHTime, PatternEvaluatorParameters};
 use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use sha1::{Sha1, Digest};
 use base64::{self, Engine as _};
 
-use crate::{PatternEvalUpdate, threads::tracking};
+use crate::{PatternEvalUpdate, AdapticsEngineEvent, VoiceId, threads::{tracking, pattern::playback::{PatternEvalRequest, PatternEvalReply, PatternEvalReplyTx}}, telemetry::{Telemetry, TelemetrySnapshot, TELEMETRY_INTERVAL}, tacton_library::TactonInfo};
+use super::{http::{self, HttpRequest, HttpRejection}, rest};
 
 /// Messages sent to websocket clients
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -17,17 +18,211 @@ pub enum AdapticsWSServerMessage {
     PlaybackUpdate{ evals: Vec<BrushAtAnimLocalTime> },
     /// Updates from the tracking system to be sent to websocket clients
     TrackingData{ tracking_frame: tracking::TrackingFrame },
+    /// Events from the playback thread (e.g. queue progress)
+    Event{ event: AdapticsEngineEvent },
+    /// Runtime telemetry, sent every `TELEMETRY_INTERVAL`
+    Telemetry{ telemetry: TelemetrySnapshot },
+    /// Answer to the [`AdapticsWSRequest`] with the same `id`, only sent to the client that made the request
+    Response{ id: u64, result: AdapticsWSResult },
 }
 
+/// Messages from websocket clients that configure their own connection, instead of being sent to the pattern-eval thread as a [`PatternEvalUpdate`]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "cmd", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum AdapticsWSClientMessage {
+    /// Replaces the topics sent to this client, e.g. `{ "cmd": "subscribe", "data": { "topics": { "playback": { "max_rate_hz": 30 }, "events": {} } } }`.
+    /// Until a client subscribes, it receives all topics at full rate.
+    Subscribe{ topics: HashMap<WsTopic, WsTopicOptions> },
+    /// Must be the first message if the server requires a token ([`WsServerConfig::token`]) and it was not passed in the handshake (`ws://host/?token=...`).
+    /// Ignored after the client is authenticated.
+    Auth{ token: String },
+}
+
+/// Version of the request envelope ([`AdapticsWSRequest`]) and its responses. Increased on breaking changes.
+pub const WS_PROTOCOL_VERSION: u32 = 1;
+
+/// A request from a websocket client, answered with an [`AdapticsWSServerMessage::Response`] with the same `id`,
+/// e.g. `{ "v": 1, "id": 7, "request": { "cmd": "get_playback_state", "data": {} } }`.
+///
+/// Requests are answered once applied, so responses may arrive in a different order than the requests were sent.
+/// Messages without the envelope (a bare [`PatternEvalUpdate`] or [`AdapticsWSClientMessage`]) are still accepted, but are not answered.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AdapticsWSRequest {
+    /// Protocol version, must be [`WS_PROTOCOL_VERSION`]
+    pub v: u32,
+    /// Chosen by the client to match responses to requests
+    pub id: u64,
+    pub request: AdapticsWSRequestBody,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum AdapticsWSRequestBody {
+    Query(AdapticsWSQuery),
+    Client(AdapticsWSClientMessage),
+    Update(PatternEvalUpdate),
+}
+/// Dispatches on `cmd` instead of trying every variant (like `#[serde(untagged)]` does), to keep the error messages of the matching variant
+impl<'de> Deserialize<'de> for AdapticsWSRequestBody {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let body = serde_json::Value::deserialize(deserializer)?;
+        match body.get("cmd").and_then(serde_json::Value::as_str) {
+            Some(cmd) if cmd.starts_with("get_") => serde_json::from_value(body).map(AdapticsWSRequestBody::Query),
+            Some("subscribe" | "auth") => serde_json::from_value(body).map(AdapticsWSRequestBody::Client),
+            _ => serde_json::from_value(body).map(AdapticsWSRequestBody::Update),
+        }.map_err(serde::de::Error::custom)
+    }
+}
+
+/// Requests for the current state of the engine
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "cmd", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum AdapticsWSQuery {
+    /// Answered with [`AdapticsWSResponse::Pattern`]
+    GetPattern{},
+    /// Answered with [`AdapticsWSResponse::Parameters`]
+    GetParameters{},
+    /// Answered with [`AdapticsWSResponse::PlaybackState`]
+    GetPlaybackState{},
+    /// Answered with [`AdapticsWSResponse::Version`]
+    GetVersion{},
+    /// Answered with [`AdapticsWSResponse::Tactons`]
+    GetTactons{},
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "status")]
+#[serde(rename_all = "snake_case")]
+pub enum AdapticsWSResult {
+    Ok{ response: AdapticsWSResponse },
+    /// The request was invalid or rejected (e.g. a pattern failed to parse)
+    Error{ message: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum AdapticsWSResponse {
+    /// The update or subscription was applied
+    Ack{},
+    /// `pattern_json` is the pattern currently loaded on the default voice, in JSON format (see [`pattern_evaluator::MidAirHapticsAnimationFileFormat`])
+    Pattern{ pattern_json: String },
+    /// See [`PatternEvaluatorParameters`]
+    Parameters{ evaluator_params: PatternEvaluatorParameters },
+    PlaybackState{
+        /// false if stopped or paused
+        playing: bool,
+        /// Pattern time (in milliseconds) of the last evaluated sample
+        pattern_time: MAHTime,
+        /// Id of the queue entry that is playing, see [`PatternEvalUpdate::QueueEnqueue`]
+        queue_entry: Option<String>,
+        /// Number of queue entries waiting to be played
+        queue_len: usize,
+        /// Voices playing in addition to the default voice, see [`PatternEvalUpdate::VoicePlay`]
+        voices: Vec<VoiceId>,
+        tracking_enabled: bool,
+        /// Which hand(s) the focal point follows while tracking is enabled
+        tracked_hand: tracking::TrackedHand,
+    },
+    Version{ engine_version: String, protocol_version: u32 },
+    /// The tactons in the tacton library, see [`PatternEvalUpdate::PlayTacton`]
+    Tactons{ tactons: Vec<TactonInfo> },
+    /// The voice a [`PatternEvalUpdate::VoicePlay`] started playing on
+    VoiceStarted{ voice_id: VoiceId },
+}
+
+/// Topics of the [`AdapticsWSServerMessage`]s that websocket clients can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WsTopic {
+    Playback,
+    Tracking,
+    Telemetry,
+    Events,
+}
+impl AdapticsWSServerMessage {
+    /// `None` for messages that are not broadcast to subscribers
+    #[must_use]
+    pub fn topic(&self) -> Option<WsTopic> {
+        match self {
+            AdapticsWSServerMessage::PlaybackUpdate { .. } => Some(WsTopic::Playback),
+            AdapticsWSServerMessage::TrackingData { .. } => Some(WsTopic::Tracking),
+            AdapticsWSServerMessage::Event { .. } => Some(WsTopic::Events),
+            AdapticsWSServerMessage::Telemetry { .. } => Some(WsTopic::Telemetry),
+            AdapticsWSServerMessage::Response { .. } => None,
+        }
+    }
+}
+
+/// Rate control of a subscribed topic. Messages skipped due to rate control are not sent later.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct WsTopicOptions {
+    /// Maximum number of messages per second
+    #[serde(default)]
+    pub max_rate_hz: Option<f64>,
+    /// Only sends every `decimation`th message of the topic
+    #[serde(default)]
+    pub decimation: Option<u32>,
+}
+
+struct WsTopicRateLimiter {
+    options: WsTopicOptions,
+    messages_seen: u64,
+    last_sent: Option<Instant>,
+}
+impl WsTopicRateLimiter {
+    fn new(options: WsTopicOptions) -> Self {
+        Self { options, messages_seen: 0, last_sent: None }
+    }
+    fn should_send(&mut self, now: Instant) -> bool {
+        let decimation = u64::from(self.options.decimation.unwrap_or(1).max(1));
+        let skip = !self.messages_seen.is_multiple_of(decimation);
+        self.messages_seen += 1;
+        if skip { return false; }
+        if let (Some(max_rate_hz), Some(last_sent)) = (self.options.max_rate_hz, self.last_sent) {
+            if now.duration_since(last_sent).as_secs_f64() * max_rate_hz < 1.0 { return false; }
+        }
+        self.last_sent = Some(now);
+        true
+    }
+}
+
+/// Number of frames queued per client before the oldest are dropped
+const OUTBOUND_QUEUE_LEN: usize = 64;
+
+/// A connected websocket client. Frames are written by its own send thread, so a slow client only delays itself.
 pub(crate) struct MAHWebsocket {
-    bufread: BufReader<TcpStream>,
     uid: u64,
-    _wsrecvjh: std::thread::JoinHandle<()>
+    /// `None` until the client subscribes, sending all topics
+    subscriptions: Option<HashMap<WsTopic, WsTopicRateLimiter>>,
+    outbound_tx: crossbeam_channel::Sender<Arc<[u8]>>,
+    /// Used to drop the oldest frame when the queue is full
+    outbound_rx: crossbeam_channel::Receiver<Arc<[u8]>>,
 }
 impl MAHWebsocket {
-    pub fn send(&mut self, wsm: &AdapticsWSServerMessage) -> std::io::Result<usize> {
-        let payload = serde_json::to_string(wsm).unwrap();
-        self.bufread.get_mut().write(&create_ws_frame(WsFrameOpcodes::Text, payload.as_bytes()))
+    fn subscribe(&mut self, topics: HashMap<WsTopic, WsTopicOptions>) {
+        self.subscriptions = Some(topics.into_iter().map(|(topic, options)| (topic, WsTopicRateLimiter::new(options))).collect());
+    }
+    fn wants(&mut self, topic: WsTopic, now: Instant) -> bool {
+        match &mut self.subscriptions {
+            None => true,
+            Some(subscriptions) => subscriptions.get_mut(&topic).is_some_and(|rate_limiter| rate_limiter.should_send(now)),
+        }
+    }
+    /// Queues `frame`, dropping the oldest queued frames if the queue is full. Returns the number of dropped frames.
+    fn queue(&self, mut frame: Arc<[u8]>) -> usize {
+        let mut dropped = 0;
+        loop {
+            match self.outbound_tx.try_send(frame) {
+                Err(crossbeam_channel::TrySendError::Full(f)) => {
+                    if self.outbound_rx.try_recv().is_ok() { dropped += 1; }
+                    frame = f;
+                },
+                Err(crossbeam_channel::TrySendError::Disconnected(_)) | Ok(()) => return dropped,
+            }
+        }
     }
 }
 
@@ -54,6 +249,11 @@ impl std::convert::TryFrom<u8> for WsFrameOpcodes {
         }
     }
 }
+impl WsFrameOpcodes {
+    fn is_control(self) -> bool {
+        (self as u8) & 0x8 != 0
+    }
+}
 
 struct WsFrameRecvd {
     fin: bool,
@@ -94,192 +294,613 @@ fn create_ws_frame(opcode: WsFrameOpcodes, payload: &[u8]) -> Vec<u8> {
     // println!("{:?}", frame);
     frame
 }
-/// returns (`fin` (see rfc6455#section-5.2), `opcode`, `payload_length`, `payload_start_index`)
-fn parse_ws_frame_header(frame: &[u8]) -> Option<(bool, WsFrameOpcodes, usize, usize, [u8; 4])> {
-    let mut i_aii = 0;
-    let framelen = frame.len();
-    let mut aii = || {let oi=i_aii; i_aii+=1; if oi<framelen {Some(oi)} else {None} };
-
-    let frameb0 = frame[aii()?];
-    let fin = (frameb0 & 0b1000_0000) > 0;
-    let opcode = (frameb0 & 0b0000_1111).try_into().unwrap();
-    let rawlen = frame[aii()?] & 0b0111_1111;
-    let payload_length = match rawlen {
-        0..=125 => { (rawlen).into() } // TODO: close ws with error instead of panic
-        126 => {
-            (u16::from(frame[aii()?])<<8 | u16::from(frame[aii()?])).into()
-        }
-        127 => {
-            (
-                u64::from(frame[aii()?])<<56 |
-                u64::from(frame[aii()?])<<48 |
-                u64::from(frame[aii()?])<<40 |
-                u64::from(frame[aii()?])<<32 |
-                u64::from(frame[aii()?])<<24 |
-                u64::from(frame[aii()?])<<16 |
-                u64::from(frame[aii()?])<<8  |
-                u64::from(frame[aii()?])
-            ).try_into().unwrap() // TODO: close ws with error instead of panic
+
+/// Maximum size of a message received from a client, after reassembling fragmented frames
+const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+/// Maximum size of a message received from a client that has not authenticated yet (see [`ws_auth`])
+const MAX_AUTH_MESSAGE_LEN: usize = 4 * 1024;
+/// Maximum payload size of a control frame (see rfc6455#section-5.5)
+const MAX_CONTROL_PAYLOAD_LEN: u64 = 125;
+
+/// Status codes sent in close frames (see rfc6455#section-7.4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WsCloseCode {
+    Normal = 1000,
+    GoingAway = 1001,
+    ProtocolError = 1002,
+    InvalidPayload = 1007,
+    PolicyViolation = 1008,
+    MessageTooBig = 1009,
+}
+
+impl WsCloseCode {
+    /// Whether `code` may be sent in a close frame: 1005, 1006 and 1015 are reserved for reporting, other unassigned codes below 3000 and codes above 4999 are invalid
+    fn is_valid(code: u16) -> bool {
+        matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+#[derive(Debug)]
+enum WsRecvError {
+    /// The connection failed or was closed without a close frame
+    Io(std::io::Error),
+    /// The client violated the protocol, the connection should be closed with a close frame
+    Close(WsCloseCode, &'static str),
+}
+impl From<std::io::Error> for WsRecvError {
+    fn from(e: std::io::Error) -> Self { WsRecvError::Io(e) }
+}
+
+/// A complete message received from a client
+#[derive(Debug, PartialEq)]
+enum WsMessage {
+    Text(String),
+    Binary(Vec<u8>),
+    /// Status code of the close frame, if any
+    Close(Option<u16>),
+    Ping(Vec<u8>),
+}
+
+/// Reads a single (masked) frame sent by a client. Fails with [`WsCloseCode::MessageTooBig`] if the payload is longer than `max_payload_len`.
+fn read_ws_frame(stream: &mut impl Read, max_payload_len: usize) -> Result<WsFrameRecvd, WsRecvError> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header)?;
+    let fin = (header[0] & 0b1000_0000) > 0;
+    if header[0] & 0b0111_0000 != 0 { return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "reserved bits set")); }
+    let opcode = WsFrameOpcodes::try_from(header[0] & 0b0000_1111).map_err(|()| WsRecvError::Close(WsCloseCode::ProtocolError, "unknown opcode"))?;
+    if (header[1] & 0b1000_0000) == 0 { return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "client frames must be masked")); }
+    let payload_len = match header[1] & 0b0111_1111 {
+        126 => { let mut len = [0; 2]; stream.read_exact(&mut len)?; u64::from(u16::from_be_bytes(len)) }
+        127 => { let mut len = [0; 8]; stream.read_exact(&mut len)?; u64::from_be_bytes(len) }
+        len => u64::from(len),
+    };
+    if opcode.is_control() && (!fin || payload_len > MAX_CONTROL_PAYLOAD_LEN) {
+        return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "control frames must not be fragmented or longer than 125 bytes"));
+    }
+    let payload_len = usize::try_from(payload_len).ok()
+        .filter(|&len| len <= max_payload_len)
+        .ok_or(WsRecvError::Close(WsCloseCode::MessageTooBig, "message too big"))?;
+
+    let mut masking_key = [0; 4];
+    stream.read_exact(&mut masking_key)?;
+    // read incrementally, so the buffer only grows as the payload actually arrives (instead of trusting the length in the header)
+    let mut payload = Vec::new();
+    stream.take(payload_len as u64).read_to_end(&mut payload)?;
+    if payload.len() < payload_len { return Err(WsRecvError::Io(std::io::ErrorKind::UnexpectedEof.into())); }
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= masking_key[i % 4];
+    }
+    Ok(WsFrameRecvd { fin, opcode, payload })
+}
+
+/// Reassembles fragmented messages (see rfc6455#section-5.4). Control frames may be interleaved with the fragments of a message.
+struct WsMessageAssembler {
+    fragmented: Option<(WsFrameOpcodes, Vec<u8>)>,
+    /// Messages longer than this (after reassembling) are rejected with [`WsCloseCode::MessageTooBig`]
+    max_message_len: usize,
+}
+impl WsMessageAssembler {
+    fn new(max_message_len: usize) -> Self {
+        Self { fragmented: None, max_message_len }
+    }
+
+    /// Reads frames from `stream` until a complete message is received
+    fn read_message(&mut self, stream: &mut impl Read) -> Result<WsMessage, WsRecvError> {
+        loop {
+            let buffered_len = self.fragmented.as_ref().map_or(0, |(_, payload)| payload.len());
+            let frame = read_ws_frame(stream, self.max_message_len.saturating_sub(buffered_len))?;
+            let (opcode, payload) = match (frame.opcode, self.fragmented.take()) {
+                (WsFrameOpcodes::Close, fragmented) => {
+                    self.fragmented = fragmented;
+                    return match frame.payload.as_slice() {
+                        [] => Ok(WsMessage::Close(None)),
+                        [b0, b1, reason @ ..] => {
+                            let code = u16::from_be_bytes([*b0, *b1]);
+                            if !WsCloseCode::is_valid(code) { return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "invalid close code")); }
+                            if std::str::from_utf8(reason).is_err() { return Err(WsRecvError::Close(WsCloseCode::InvalidPayload, "close reason is not valid UTF-8")); }
+                            Ok(WsMessage::Close(Some(code)))
+                        },
+                        [_] => Err(WsRecvError::Close(WsCloseCode::ProtocolError, "invalid close frame")),
+                    };
+                },
+                (WsFrameOpcodes::Ping, fragmented) => {
+                    self.fragmented = fragmented;
+                    return Ok(WsMessage::Ping(frame.payload));
+                },
+                (WsFrameOpcodes::Pong, fragmented) => {
+                    self.fragmented = fragmented;
+                    continue;
+                },
+                (WsFrameOpcodes::Continuation, None) => return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "continuation frame without a message to continue")),
+                (WsFrameOpcodes::Continuation, Some((opcode, mut payload))) => {
+                    if payload.len() + frame.payload.len() > self.max_message_len { return Err(WsRecvError::Close(WsCloseCode::MessageTooBig, "message too big")); }
+                    payload.extend_from_slice(&frame.payload);
+                    (opcode, payload)
+                },
+                (WsFrameOpcodes::Text | WsFrameOpcodes::Binary, Some(_)) => return Err(WsRecvError::Close(WsCloseCode::ProtocolError, "new message before the fragmented message was finished")),
+                (opcode @ (WsFrameOpcodes::Text | WsFrameOpcodes::Binary), None) => (opcode, frame.payload),
+            };
+            if !frame.fin {
+                self.fragmented = Some((opcode, payload));
+                continue;
+            }
+            return match opcode {
+                WsFrameOpcodes::Text => String::from_utf8(payload).map(WsMessage::Text)
+                    .map_err(|_| WsRecvError::Close(WsCloseCode::InvalidPayload, "text message is not valid UTF-8")),
+                _ => Ok(WsMessage::Binary(payload)),
+            };
         }
-        _ => unreachable!()
+    }
+}
+
+fn close_frame(code: u16, reason: &str) -> Arc<[u8]> {
+    let mut payload = code.to_be_bytes().to_vec();
+    payload.extend_from_slice(reason.as_bytes());
+    create_ws_frame(WsFrameOpcodes::Close, &payload).into()
+}
+
+fn remove_ws_client(wsclients: &Mutex<Vec<MAHWebsocket>>, uid: u64, patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>, telemetry: &Telemetry) {
+    let Ok(mut wsclients) = wsclients.lock() else { return; };
+    wsclients.retain(|pwso| pwso.uid != uid);
+    telemetry.record_websocket_clients(wsclients.len());
+    if wsclients.is_empty() {
+        println!("no more ws clients, stopping playback");
+        let update = PatternEvalUpdate::Playstart { playstart: 0.0, playstart_offset: 0.0 };
+        patteval_request_tx.send(PatternEvalRequest::Update { update, reply_tx: None }).ok(); // ignore send error (if pattern-eval thread already exited)
+    }
+}
+
+enum WsClientRequest {
+    Client(AdapticsWSClientMessage),
+    Update(PatternEvalUpdate),
+    /// A message in the [`AdapticsWSRequest`] envelope, `body` is the error message if the request is invalid
+    Request{ id: u64, body: Result<AdapticsWSRequestBody, String> },
+}
+fn parse_ws_message(payload: &[u8]) -> serde_json::Result<WsClientRequest> {
+    let message: serde_json::Value = serde_json::from_slice(payload)?;
+    if message.get("request").is_some() {
+        let id = message.get("id").and_then(serde_json::Value::as_u64).ok_or_else(|| <serde_json::Error as serde::de::Error>::missing_field("id"))?;
+        let body = match serde_json::from_value::<AdapticsWSRequest>(message) {
+            Ok(AdapticsWSRequest { v: WS_PROTOCOL_VERSION, request, .. }) => Ok(request),
+            Ok(AdapticsWSRequest { v, .. }) => Err(format!("unsupported protocol version {v}, expected {WS_PROTOCOL_VERSION}")),
+            Err(e) => Err(format!("invalid request: {e}")),
+        };
+        Ok(WsClientRequest::Request { id, body })
+    } else if matches!(message.get("cmd").and_then(serde_json::Value::as_str), Some("subscribe" | "auth")) {
+        serde_json::from_value(message).map(WsClientRequest::Client)
+    } else {
+        serde_json::from_value(message).map(WsClientRequest::Update)
+    }
+}
+
+fn text_frame(msg: &AdapticsWSServerMessage) -> Arc<[u8]> {
+    create_ws_frame(WsFrameOpcodes::Text, serde_json::to_string(msg).unwrap().as_bytes()).into()
+}
+
+fn apply_client_message(message: AdapticsWSClientMessage, uid: u64, wsclients: &Mutex<Vec<MAHWebsocket>>) {
+    match message {
+        AdapticsWSClientMessage::Subscribe { topics } => {
+            if let Some(client) = wsclients.lock().unwrap().iter_mut().find(|pwso| pwso.uid == uid) {
+                client.subscribe(topics);
+            }
+        },
+        AdapticsWSClientMessage::Auth { .. } => {}, // only checked before the client is added, see `ws_auth`
+    }
+}
+
+fn response_frame(id: u64, result: Result<AdapticsWSResponse, String>) -> Arc<[u8]> {
+    let result = match result {
+        Ok(response) => AdapticsWSResult::Ok { response },
+        Err(message) => AdapticsWSResult::Error { message },
     };
-    let masking_key: [u8; 4] = [frame[aii()?], frame[aii()?], frame[aii()?], frame[aii()?]];
-    Some((fin, opcode, payload_length, i_aii, masking_key))
-}
-fn parse_ws_frame_body(frame: &[u8], payload_length: usize, payload_start_index: usize, masking_key: [u8; 4]) -> Option<(usize, Vec<u8>)> {
-    let bytes_read = payload_length+payload_start_index;
-    let payloadmasked = &frame[payload_start_index..bytes_read];
-    let mut payload = Vec::with_capacity(payload_length);
-    for i in 0..payload_length {
-        payload.push(payloadmasked.get(i)? ^ masking_key[i % 4]);
-    }
-
-    Some((bytes_read, payload))
-}
-fn parse_ws_frame_full(frame: &[u8]) -> Option<(usize, WsFrameRecvd)> {
-    let (fin, opcode, payload_length, payload_start_index, masking_key) = parse_ws_frame_header(frame)?;
-    let (bytes_read, payload) = parse_ws_frame_body(frame, payload_length, payload_start_index, masking_key)?;
-    Some((bytes_read, WsFrameRecvd {
-        fin,
-        opcode,
-        payload
-    }))
-}
-
-fn handle_websocket(mut bufread: BufReader<TcpStream>, mut buf: String, wsclients: &Arc<Mutex<Vec<MAHWebsocket>>>, patteval_update_tx: crossbeam_channel::Sender<PatternEvalUpdate>) {
-    let sec_ws_key_header = "Sec-WebSocket-Key: ";
-    let mut response = String::from("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: ");
-    while buf != "\r\n" { //line before data will have only \r\n (0D 0A)
-        if buf.starts_with(sec_ws_key_header) {
-            let ms = buf[sec_ws_key_header.len()..buf.len()-2].to_owned() + "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
-            response += {
-                let mut s1hasher = Sha1::new();
-                s1hasher.update(ms);
-                let res = s1hasher.finalize();
-                &base64::engine::general_purpose::STANDARD.encode(res)
+    text_frame(&AdapticsWSServerMessage::Response { id, result })
+}
+
+/// Handles a text or binary message containing a JSON [`AdapticsWSRequest`], [`AdapticsWSClientMessage`] or [`PatternEvalUpdate`]. Returns false if the pattern-eval thread has exited.
+///
+/// Requests are answered through `replies_tx` without waiting for the pattern-eval thread, so the client can keep sending while a request is applied.
+fn handle_ws_message(
+    payload: &[u8],
+    uid: u64,
+    wsclients: &Mutex<Vec<MAHWebsocket>>,
+    outbound_tx: &crossbeam_channel::Sender<Arc<[u8]>>,
+    replies_tx: &crossbeam_channel::Sender<PatternEvalReply>,
+    patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>,
+) -> bool {
+    match parse_ws_message(payload) {
+        Ok(WsClientRequest::Client(message)) => {
+            apply_client_message(message, uid, wsclients);
+            true
+        },
+        Ok(WsClientRequest::Update(update)) => patteval_request_tx.send(PatternEvalRequest::Update { update, reply_tx: None }).is_ok(),
+        Ok(WsClientRequest::Request { id, body }) => {
+            let reply_tx = PatternEvalReplyTx::new(id, replies_tx.clone());
+            let sent = match body {
+                Ok(AdapticsWSRequestBody::Client(message)) => {
+                    apply_client_message(message, uid, wsclients);
+                    reply_tx.send(Ok(AdapticsWSResponse::Ack {})).ok(); // ignore send error, the client is not keeping up
+                    true
+                },
+                Ok(AdapticsWSRequestBody::Query(query)) => patteval_request_tx.send(PatternEvalRequest::Query { query, reply_tx }).is_ok(),
+                Ok(AdapticsWSRequestBody::Update(update)) => patteval_request_tx.send(PatternEvalRequest::Update { update, reply_tx: Some(reply_tx) }).is_ok(),
+                Err(message) => {
+                    reply_tx.send(Err(message)).ok(); // ignore send error, the client is not keeping up
+                    true
+                },
             };
+            if !sent {
+                outbound_tx.send(response_frame(id, Err("engine stopped".to_string()))).ok(); // ignore send error, the send thread exits when the connection fails
+            }
+            sent
+        },
+        Err(e) => {
+            eprintln!("invalid message from ws\t'{uid:#X}': {e}");
+            let error_msg = AdapticsWSServerMessage::Event { event: AdapticsEngineEvent::Error { message: format!("invalid message: {e}") } };
+            outbound_tx.send(text_frame(&error_msg)).ok(); // ignore send error, the send thread exits when the connection fails
+            true
         }
-        buf.clear();
-        bufread.read_line(&mut buf).unwrap();
     }
-    response+="\r\n\r\n";
-    bufread.get_mut().write_all(response.as_bytes()).unwrap();
-    bufread.get_mut().flush().unwrap();
+}
+
+/// Handles messages from the client until the connection closes. Returns the close frame to send, if any.
+fn ws_recv_loop(
+    mut reader: impl Read,
+    uid: u64,
+    wsclients: &Mutex<Vec<MAHWebsocket>>,
+    outbound_tx: &crossbeam_channel::Sender<Arc<[u8]>>,
+    replies_tx: &crossbeam_channel::Sender<PatternEvalReply>,
+    patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>,
+) -> Option<Arc<[u8]>> {
+    let mut assembler = WsMessageAssembler::new(MAX_MESSAGE_LEN);
+    loop {
+        let keep_open = match assembler.read_message(&mut reader) {
+            Ok(WsMessage::Text(text)) => handle_ws_message(text.as_bytes(), uid, wsclients, outbound_tx, replies_tx, patteval_request_tx),
+            Ok(WsMessage::Binary(payload)) => handle_ws_message(&payload, uid, wsclients, outbound_tx, replies_tx, patteval_request_tx),
+            Ok(WsMessage::Ping(payload)) => outbound_tx.send(create_ws_frame(WsFrameOpcodes::Pong, &payload).into()).is_ok(),
+            Ok(WsMessage::Close(code)) => {
+                println!("closing ws\t'{uid:#X}'");
+                return Some(close_frame(code.unwrap_or(WsCloseCode::Normal as u16), ""));
+            },
+            Err(WsRecvError::Io(e)) => {
+                println!("ws disconnected\t'{uid:#X}': {e}");
+                return None;
+            },
+            Err(WsRecvError::Close(code, reason)) => {
+                eprintln!("closing ws\t'{uid:#X}' ({code:?}): {reason}");
+                return Some(close_frame(code as u16, reason));
+            },
+        };
+        if !keep_open {
+            return Some(close_frame(WsCloseCode::GoingAway as u16, "engine stopped"));
+        }
+    }
+}
+
+/// Writes queued frames and the answers to the client's requests until the client is removed (or a close frame was sent), then shuts down the connection
+fn ws_send_loop(mut tcpstream: TcpStream, outbound_rx: &crossbeam_channel::Receiver<Arc<[u8]>>, replies_rx: &crossbeam_channel::Receiver<PatternEvalReply>, uid: u64, telemetry: &Telemetry) {
+    let mut replies_rx = replies_rx.clone();
+    loop {
+        let frame = crossbeam_channel::select! {
+            recv(outbound_rx) -> frame => match frame {
+                Ok(frame) => frame,
+                Err(_) => break,
+            },
+            recv(replies_rx) -> reply => {
+                let Ok((id, result)) = reply else { replies_rx = crossbeam_channel::never(); continue; }; // no more requests
+                response_frame(id, result)
+            },
+        };
+        if let Err(e) = tcpstream.write_all(&frame) {
+            println!("removing ws client\t'{uid:#X}' for {e}");
+            telemetry.record_websocket_client_dropped();
+            break;
+        }
+        if frame[0] & 0b0000_1111 == WsFrameOpcodes::Close as u8 { break; } // nothing may be sent after a close frame
+    }
+    tcpstream.shutdown(std::net::Shutdown::Both).ok(); // also ends the recv thread, which removes the client
+}
+
+/// Origins of web pages allowed to connect by default: the designer and pages served from this machine
+pub const DEFAULT_ALLOWED_ORIGINS: &[&str] = &["https://adaptivehaptics.github.io", "http://localhost:*", "http://127.0.0.1:*"];
+
+/// Configuration of the websocket server, and of the REST API served on the same listener (at "/api/...")
+#[derive(Debug, Clone)]
+pub struct WsServerConfig {
+    /// TCP address to listen on, e.g. "127.0.0.1:8037"
+    pub bind_addr: String,
+    /// Values of the `Origin` header that are allowed to connect (compared case-insensitively).
+    /// An entry ending in ":*" allows any port (or none), "*" allows any origin.
+    /// Connections without an `Origin` header (i.e. not from a browser) are always allowed.
+    pub allowed_origins: Vec<String>,
+    /// If set, clients must pass this token in the handshake (`ws://host/?token=...` or an `Authorization: Bearer ...` header) or in an [`AdapticsWSClientMessage::Auth`] first message.
+    /// Clients that are idle for 5 seconds before authenticating are disconnected.
+    /// REST requests must pass it in the query string or header.
+    pub token: Option<String>,
+}
+impl WsServerConfig {
+    /// Allows [`DEFAULT_ALLOWED_ORIGINS`] without a token
+    #[must_use]
+    pub fn new(bind_addr: String) -> Self {
+        Self { bind_addr, allowed_origins: DEFAULT_ALLOWED_ORIGINS.iter().map(ToString::to_string).collect(), token: None }
+    }
+
+    fn origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| {
+            if allowed == "*" { return true; }
+            match allowed.strip_suffix(":*") {
+                Some(without_port) => origin.get(..without_port.len()).is_some_and(|o| o.eq_ignore_ascii_case(without_port))
+                    && (origin.len() == without_port.len() || origin[without_port.len()..].strip_prefix(':').is_some_and(|port| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))),
+                None => origin.eq_ignore_ascii_case(allowed),
+            }
+        })
+    }
+}
+
+/// Compares in constant time (for equal lengths), so the token cannot be guessed byte by byte from response times
+fn token_matches(token: &str, expected: &str) -> bool {
+    token.len() == expected.len() && token.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// How long a client may wait between sending parts of the handshake or REST request, or before authenticating (see [`ws_auth`])
+const HTTP_READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Maximum number of open connections (websocket clients, including ones that have not authenticated yet, and REST requests)
+const MAX_CONNECTIONS: usize = 64;
+
+/// Number of open connections, see [`MAX_CONNECTIONS`]
+#[derive(Clone, Default)]
+struct ConnectionCounter(Arc<AtomicUsize>);
+impl ConnectionCounter {
+    /// Counts a new connection until the returned guard is dropped. Returns `None` if `max` connections are already open.
+    fn try_acquire(&self, max: usize) -> Option<ConnectionGuard> {
+        self.0.fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| (open < max).then_some(open + 1)).ok()?;
+        Some(ConnectionGuard(self.0.clone()))
+    }
+}
+struct ConnectionGuard(Arc<AtomicUsize>);
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Checks the `Origin` header and the token (passed as `?token=...` or `Authorization: Bearer ...`) of a websocket handshake or REST request.
+/// Returns false if the server requires a token and none was passed.
+fn check_access(request: &HttpRequest, config: &WsServerConfig) -> Result<bool, HttpRejection> {
+    if let Some(origin) = request.header("Origin") {
+        if !config.origin_allowed(origin) { return Err((403, "Forbidden")); }
+    }
+    let Some(expected) = &config.token else { return Ok(true); };
+    let token = request.query_param("token")
+        .or_else(|| request.header("Authorization").and_then(|auth| auth.strip_prefix("Bearer ")).map(|token| token.trim().to_string()));
+    match token {
+        Some(token) if token_matches(&token, expected) => Ok(true),
+        Some(_) => Err((403, "Forbidden")),
+        None => Ok(false),
+    }
+}
+
+/// Validates the opening handshake (see rfc6455#section-4.2.1). Returns the `Sec-WebSocket-Accept` value, and whether a valid token was passed in the handshake.
+fn check_handshake(request: &HttpRequest, config: &WsServerConfig) -> Result<(String, bool), HttpRejection> {
+    if request.path() != "/" { return Err((404, "Not Found")); }
+    if request.method != "GET" || request.version == "HTTP/1.0" { return Err((400, "Bad Request")); }
+    if request.header("Host").is_none()
+        || !request.header_contains_token("Upgrade", "websocket")
+        || !request.header_contains_token("Connection", "Upgrade") {
+        return Err((400, "Bad Request"));
+    }
+    if request.header("Sec-WebSocket-Version") != Some("13") { return Err((426, "Upgrade Required")); }
+    let key = request.header("Sec-WebSocket-Key")
+        .filter(|key| base64::engine::general_purpose::STANDARD.decode(key).is_ok_and(|nonce| nonce.len() == 16))
+        .ok_or((400, "Bad Request"))?;
+    let authenticated = check_access(request, config)?; // if not, the token must be sent in the first message
+
+    let mut s1hasher = Sha1::new();
+    s1hasher.update(key.to_owned() + "258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+    Ok((base64::engine::general_purpose::STANDARD.encode(s1hasher.finalize()), authenticated))
+}
+
+/// Waits for the [`AdapticsWSClientMessage::Auth`] message of a client that did not pass the token in the handshake.
+/// Returns the close frame to send (if any) if the client did not authenticate.
+fn ws_auth(reader: &mut impl Read, token: &str, uid: u64, outbound_tx: &crossbeam_channel::Sender<Arc<[u8]>>) -> Result<(), Option<Arc<[u8]>>> {
+    let mut assembler = WsMessageAssembler::new(MAX_AUTH_MESSAGE_LEN);
+    loop {
+        let payload = match assembler.read_message(reader) {
+            Ok(WsMessage::Text(text)) => text.into_bytes(),
+            Ok(WsMessage::Binary(payload)) => payload,
+            Ok(WsMessage::Ping(payload)) => { outbound_tx.send(create_ws_frame(WsFrameOpcodes::Pong, &payload).into()).ok(); continue; },
+            Ok(WsMessage::Close(code)) => return Err(Some(close_frame(code.unwrap_or(WsCloseCode::Normal as u16), ""))),
+            Err(WsRecvError::Io(_)) => return Err(None),
+            Err(WsRecvError::Close(code, reason)) => return Err(Some(close_frame(code as u16, reason))),
+        };
+        let (id, received_token) = match parse_ws_message(&payload) {
+            Ok(WsClientRequest::Client(AdapticsWSClientMessage::Auth { token })) => (None, token),
+            Ok(WsClientRequest::Request { id, body: Ok(AdapticsWSRequestBody::Client(AdapticsWSClientMessage::Auth { token })) }) => (Some(id), token),
+            _ => (None, String::new()),
+        };
+        let authenticated = token_matches(&received_token, token);
+        if let Some(id) = id {
+            let result = if authenticated { Ok(AdapticsWSResponse::Ack {}) } else { Err("invalid token".to_string()) };
+            outbound_tx.send(response_frame(id, result)).ok(); // ignore send error, the send thread exits when the connection fails
+        }
+        if authenticated { return Ok(()); }
+        eprintln!("closing ws\t'{uid:#X}': not authenticated");
+        return Err(Some(close_frame(WsCloseCode::PolicyViolation as u16, "authentication required")));
+    }
+}
+
+/// Reads the request of a new connection, and either upgrades it to a websocket or handles it as a REST request (see [`rest`])
+fn handle_connection(
+    mut bufread: BufReader<TcpStream>,
+    connection_guard: ConnectionGuard,
+    config: &WsServerConfig,
+    wsclients: &Arc<Mutex<Vec<MAHWebsocket>>>,
+    patteval_request_tx: crossbeam_channel::Sender<PatternEvalRequest>,
+    telemetry: &Arc<Telemetry>,
+) -> std::io::Result<()> {
+    bufread.get_ref().set_read_timeout(Some(HTTP_READ_TIMEOUT))?;
+    let request = match http::read_http_request(&mut bufread) {
+        Ok(request) => request,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            http::write_http_error(bufread.get_mut(), 400, "Bad Request", &[])?;
+            return Err(e);
+        },
+        Err(e) => return Err(e),
+    };
+    if request.path().starts_with(rest::API_PREFIX) && !request.header_contains_token("Upgrade", "websocket") {
+        return match check_access(&request, config) {
+            Ok(true) => rest::handle_rest_request(&request, &mut bufread, &patteval_request_tx),
+            Ok(false) => http::write_http_error(bufread.get_mut(), 401, "Unauthorized", &[("WWW-Authenticate", "Bearer")]),
+            Err((status, reason)) => http::write_http_error(bufread.get_mut(), status, reason, &[]),
+        };
+    }
+    handle_websocket(bufread, connection_guard, &request, config, wsclients, patteval_request_tx, telemetry)
+}
+
+fn handle_websocket(
+    mut bufread: BufReader<TcpStream>,
+    connection_guard: ConnectionGuard,
+    request: &HttpRequest,
+    config: &WsServerConfig,
+    wsclients: &Arc<Mutex<Vec<MAHWebsocket>>>,
+    patteval_request_tx: crossbeam_channel::Sender<PatternEvalRequest>,
+    telemetry: &Arc<Telemetry>,
+) -> std::io::Result<()> {
+    let (accept_key, authenticated) = match check_handshake(request, config) {
+        Ok(accepted) => accepted,
+        Err((status, reason)) => {
+            let extra_headers: &[_] = if status == 426 { &[("Sec-WebSocket-Version", "13")] } else { &[] };
+            http::write_http_error(bufread.get_mut(), status, reason, extra_headers)?;
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, format!("rejected {} {} (origin {:?}): {status} {reason}", request.method, request.path(), request.header("Origin"))));
+        },
+    };
+    let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept_key}\r\n\r\n");
+    bufread.get_mut().write_all(response.as_bytes())?;
+    bufread.get_mut().flush()?;
 
     let uid = rand::random();
     println!("starting ws\t'{uid:#X}'");
 
-    let wsrecvjh = {
+    // frames sent right after the handshake may already be buffered
+    let mut reader = std::io::Cursor::new(bufread.buffer().to_vec()).chain(bufread.get_ref().try_clone()?);
+    let tcpstream = bufread.into_inner();
+
+    let (outbound_tx, outbound_rx) = crossbeam_channel::bounded(OUTBOUND_QUEUE_LEN);
+    let (replies_tx, replies_rx) = crossbeam_channel::bounded(OUTBOUND_QUEUE_LEN);
+    {
+        let telemetry = telemetry.clone();
+        let outbound_rx = outbound_rx.clone();
+        std::thread::spawn(move || ws_send_loop(tcpstream, &outbound_rx, &replies_rx, uid, &telemetry));
+    }
+    {
         let wsclients = wsclients.clone();
-        let mut tcpstream = bufread.get_mut().try_clone().unwrap();
+        let telemetry = telemetry.clone();
+        let token = if authenticated { None } else { config.token.clone() };
         std::thread::spawn(move || {
-            std::thread::sleep(Duration::from_millis(10)); //make sure this is in wsclients before recving close
-            loop {
-                let mut wsframebuf = vec![0; 65536]; //max frame size (not spec compliant)
-                // std::thread::sleep(Duration::from_millis(1000)); // force frame coalescing in tcp stream
-                let bytes_peeked = tcpstream.peek(&mut wsframebuf).unwrap();
-                // println!("peeked {} bytes", bytes_peeked);
-                if let Some((bytes_read, wsfr)) = parse_ws_frame_full(&wsframebuf[0..bytes_peeked]) {
-                    tcpstream.read_exact(&mut wsframebuf[0..bytes_read]).unwrap(); //consume bytes
-                    // assert!(wsfr.fin, "fragmented frames (fin=false) not yet implemented");
-                    if !wsfr.fin {
-                        eprintln!("fragmented frames (fin=false) not yet implemented, ignoring...");
-                        continue;
+            let _connection_guard = connection_guard; // counts the connection until the client disconnects
+            let authenticated = token.map_or(Ok(()), |token| ws_auth(&mut reader, &token, uid, &outbound_tx))
+                .and_then(|()| reader.get_ref().1.set_read_timeout(None).map_err(|_| None)); // the read timeout only applies until the client is authenticated
+            let close_frame = match authenticated {
+                Ok(()) => {
+                    // clients only receive messages (and can stop playback by disconnecting) once they are authenticated
+                    {
+                        let mut wsclients = wsclients.lock().unwrap();
+                        wsclients.push(MAHWebsocket { uid, subscriptions: None, outbound_tx: outbound_tx.clone(), outbound_rx });
+                        telemetry.record_websocket_clients(wsclients.len());
                     }
-                    match wsfr.opcode {
-                        WsFrameOpcodes::Continuation => {
-                            eprintln!("continuation frames not yet implemented, ignoring...");
-                            continue;
-                        }
-                        WsFrameOpcodes::Text => patteval_update_tx.send(serde_json::from_slice(&wsfr.payload).unwrap()).unwrap(),
-                        WsFrameOpcodes::Binary => todo!("binary ws frames"),
-                        WsFrameOpcodes::Close => {
-                            println!("closing ws\t'{uid:#X}'");
-                            let mut wsclients = wsclients.lock().unwrap();
-                            wsclients.retain(|pwso| pwso.uid != uid);
-                            if wsclients.len() == 0 {
-                                println!("no more ws clients, stopping playback");
-                                patteval_update_tx.send(PatternEvalUpdate::Playstart { playstart: 0.0, playstart_offset: 0.0 }).unwrap();
-                            }
-                            break;
-                        }
-                        WsFrameOpcodes::Ping => {
-                            let pong_frame = create_ws_frame(WsFrameOpcodes::Pong, &wsfr.payload);
-                            tcpstream.write_all(&pong_frame).unwrap();
-                        }
-                        WsFrameOpcodes::Pong => {}
-                    }
-                }
+                    let close_frame = ws_recv_loop(reader, uid, &wsclients, &outbound_tx, &replies_tx, &patteval_request_tx);
+                    remove_ws_client(&wsclients, uid, &patteval_request_tx, &telemetry);
+                    close_frame
+                },
+                Err(close_frame) => close_frame,
+            };
+            if let Some(close_frame) = close_frame {
+                outbound_tx.send(close_frame).ok(); // ignore send error (if the send thread already exited)
             }
-        })
-    };
-
-    let pws = MAHWebsocket { bufread, uid, _wsrecvjh: wsrecvjh };
-    wsclients.lock().unwrap().push(pws);
+            // the send thread exits after sending the remaining frames, since all senders are dropped
+        });
+    }
+    Ok(())
 }
 
-fn loop_through_send_removing_fails(wsclients: &mut Vec<MAHWebsocket>, msg: &AdapticsWSServerMessage) {
-    let len = wsclients.len();
-    let mut del = 0;
-    {
-        for i in 0..len {
-            if let Err(e) = wsclients[i].send(msg) {
-                println!("removing wsclient: {i} for {e}");
-                del += 1;
-            } else if del > 0 {
-                wsclients.swap(i - del, i);
-            }
+/// Queues `msg` for every client subscribed to its topic
+fn dispatch_to_ws_clients(wsclients: &mut [MAHWebsocket], msg: &AdapticsWSServerMessage, telemetry: &Telemetry) {
+    let Some(topic) = msg.topic() else { return; };
+    let now = Instant::now();
+    let mut frame: Option<Arc<[u8]>> = None; // only serialized if a client wants it
+    let mut dropped = 0;
+    for client in wsclients {
+        if client.wants(topic, now) {
+            let frame = frame.get_or_insert_with(|| text_frame(msg));
+            dropped += client.queue(frame.clone());
         }
     }
-    if del > 0 {
-        wsclients.truncate(len - del);
-    }
+    if dropped > 0 { telemetry.record_dropped_websocket_messages(dropped); }
 }
 
 fn websocket_dispatcher_loop_thread(
     wsclients: &Arc<Mutex<Vec<MAHWebsocket>>>,
     playback_updates_rx: &crossbeam_channel::Receiver<AdapticsWSServerMessage>,
     tracking_data_ws_rx: Option<&crossbeam_channel::Receiver<AdapticsWSServerMessage>>,
+    events_rx: &crossbeam_channel::Receiver<AdapticsEngineEvent>,
+    telemetry: &Telemetry,
 ) {
-    while let Ok(msg) =
-        if let Some(tracking_data_ws_rx) = tracking_data_ws_rx.as_ref() {
-            crossbeam_channel::select! {
-                recv(playback_updates_rx) -> msg => msg,
-                recv(tracking_data_ws_rx) -> msg => msg,
-            }
-        } else {
-            playback_updates_rx.recv()
-        }
-    {
-        loop_through_send_removing_fails(&mut wsclients.lock().unwrap(), &msg);
+    let telemetry_ticker = crossbeam_channel::tick(TELEMETRY_INTERVAL);
+    loop {
+        let mut sel = crossbeam_channel::Select::new();
+        let playback_updates_rx_idx = sel.recv(playback_updates_rx);
+        let events_rx_idx = sel.recv(events_rx);
+        let telemetry_ticker_idx = sel.recv(&telemetry_ticker);
+        let tracking_data_ws_rx_idx = tracking_data_ws_rx.map(|tracking_data_ws_rx| sel.recv(tracking_data_ws_rx));
+        let oper = sel.select();
+        let msg = match oper.index() {
+            i if i == playback_updates_rx_idx => oper.recv(playback_updates_rx),
+            i if i == events_rx_idx => oper.recv(events_rx).map(|event| AdapticsWSServerMessage::Event { event }),
+            i if i == telemetry_ticker_idx => oper.recv(&telemetry_ticker).map(|_| AdapticsWSServerMessage::Telemetry { telemetry: telemetry.snapshot() }),
+            i if Some(i) == tracking_data_ws_rx_idx => oper.recv(tracking_data_ws_rx.unwrap()),
+            _ => unreachable!(),
+        };
+        let Ok(msg) = msg else { break; };
+        dispatch_to_ws_clients(&mut wsclients.lock().unwrap(), &msg, telemetry);
     }
 
     // channel disconnected so we should exit
 }
 
 pub fn start_ws_server(
-    websocket_server_addr: &str,
-    patteval_update_tx: &crossbeam_channel::Sender<PatternEvalUpdate>,
+    config: &WsServerConfig,
+    patteval_request_tx: &crossbeam_channel::Sender<PatternEvalRequest>,
     playback_updates_rx: crossbeam_channel::Receiver<AdapticsWSServerMessage>,
     tracking_data_ws_rx: Option<crossbeam_channel::Receiver<AdapticsWSServerMessage>>,
+    events_rx: crossbeam_channel::Receiver<AdapticsEngineEvent>,
+    telemetry: &Arc<Telemetry>,
 ) {
     let wsclients = Arc::new(Mutex::new(Vec::new()));
     {
         let wsclients = wsclients.clone();
-        std::thread::spawn(move || websocket_dispatcher_loop_thread(&wsclients, &playback_updates_rx, tracking_data_ws_rx.as_ref()));
+        let telemetry = telemetry.clone();
+        std::thread::spawn(move || websocket_dispatcher_loop_thread(&wsclients, &playback_updates_rx, tracking_data_ws_rx.as_ref(), &events_rx, &telemetry));
     }
-    let listener = TcpListener::bind(websocket_server_addr).unwrap();
+    let config = Arc::new(config.clone());
+    let listener = TcpListener::bind(&config.bind_addr).unwrap();
+    let connections = ConnectionCounter::default();
     for stream in listener.incoming() {
         match stream {
-            Ok(stream) => {
-                let mut bufreader = BufReader::new(stream);
-                let mut buf = String::new();
-                bufreader.read_line(&mut buf).unwrap();
-                if buf.starts_with("GET / HTTP/1.1") {
-                    handle_websocket(bufreader, buf, &wsclients, patteval_update_tx.clone());
-                } else {
-                    println!("not websocket");
-                }
+            Ok(mut stream) => {
+                let Some(connection_guard) = connections.try_acquire(MAX_CONNECTIONS) else {
+                    http::write_http_error(&mut stream, 503, "Service Unavailable", &[]).ok(); // ignore write error, the connection is closed anyway
+                    continue;
+                };
+                // a slow client (or REST request) must not delay other connections
+                let config = config.clone();
+                let wsclients = wsclients.clone();
+                let patteval_request_tx = patteval_request_tx.clone();
+                let telemetry = telemetry.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = handle_connection(BufReader::new(stream), connection_guard, &config, &wsclients, patteval_request_tx, &telemetry) {
+                        println!("connection failed: {e}");
+                    }
+                });
             }
             Err(e) => {
                 println!("error: {e}");
@@ -287,4 +908,249 @@ pub fn start_ws_server(
         }
     }
     drop(listener);
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a masked client frame
+    fn client_frame(fin: bool, opcode: WsFrameOpcodes, payload: &[u8]) -> Vec<u8> {
+        let mut frame = create_ws_frame(opcode, &[]);
+        frame.truncate(1);
+        if !fin { frame[0] &= 0b0111_1111; }
+        let masking_key = [1, 2, 3, 4];
+        let server_frame = create_ws_frame(opcode, payload);
+        let header_len = server_frame.len() - payload.len();
+        frame.push(server_frame[1] | 0b1000_0000);
+        frame.extend_from_slice(&server_frame[2..header_len]);
+        frame.extend_from_slice(&masking_key);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ masking_key[i % 4]));
+        frame
+    }
+
+    fn read_messages(stream: &[u8]) -> Vec<Result<WsMessage, WsCloseCode>> {
+        read_messages_limited(stream, MAX_MESSAGE_LEN)
+    }
+
+    fn read_messages_limited(stream: &[u8], max_message_len: usize) -> Vec<Result<WsMessage, WsCloseCode>> {
+        let mut stream = stream;
+        let mut assembler = WsMessageAssembler::new(max_message_len);
+        let mut messages = Vec::new();
+        loop {
+            match assembler.read_message(&mut stream) {
+                Ok(message) => messages.push(Ok(message)),
+                Err(WsRecvError::Close(code, _)) => { messages.push(Err(code)); break; },
+                Err(WsRecvError::Io(_)) => break,
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn test_reassemble_fragmented_messages() {
+        let large_payload = "x".repeat(100_000);
+        let stream = [
+            client_frame(false, WsFrameOpcodes::Text, b"{\"a\":"),
+            client_frame(true, WsFrameOpcodes::Ping, b"ping"),
+            client_frame(false, WsFrameOpcodes::Continuation, b" 1"),
+            client_frame(true, WsFrameOpcodes::Continuation, b"}"),
+            client_frame(true, WsFrameOpcodes::Binary, large_payload.as_bytes()),
+            client_frame(true, WsFrameOpcodes::Close, &1001u16.to_be_bytes()),
+        ].concat();
+        assert_eq!(read_messages(&stream), vec![
+            Ok(WsMessage::Ping(b"ping".to_vec())),
+            Ok(WsMessage::Text("{\"a\": 1}".to_string())),
+            Ok(WsMessage::Binary(large_payload.into_bytes())),
+            Ok(WsMessage::Close(Some(1001))),
+        ]);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let unexpected_continuation = client_frame(true, WsFrameOpcodes::Continuation, b"x");
+        assert_eq!(read_messages(&unexpected_continuation), vec![Err(WsCloseCode::ProtocolError)]);
+
+        let interrupted_message = [client_frame(false, WsFrameOpcodes::Text, b"x"), client_frame(true, WsFrameOpcodes::Text, b"y")].concat();
+        assert_eq!(read_messages(&interrupted_message), vec![Err(WsCloseCode::ProtocolError)]);
+
+        let mut unmasked = client_frame(true, WsFrameOpcodes::Text, b"");
+        unmasked[1] &= 0b0111_1111;
+        assert_eq!(read_messages(&unmasked[..unmasked.len() - 4]), vec![Err(WsCloseCode::ProtocolError)]);
+
+        let fragmented_ping = client_frame(false, WsFrameOpcodes::Ping, b"");
+        assert_eq!(read_messages(&fragmented_ping), vec![Err(WsCloseCode::ProtocolError)]);
+
+        let invalid_utf8 = client_frame(true, WsFrameOpcodes::Text, &[0xff]);
+        assert_eq!(read_messages(&invalid_utf8), vec![Err(WsCloseCode::InvalidPayload)]);
+
+        for code in [999, 1004, 1005, 1006, 1015, 2999, 5000] {
+            let invalid_close_code = client_frame(true, WsFrameOpcodes::Close, &u16::to_be_bytes(code));
+            assert_eq!(read_messages(&invalid_close_code), vec![Err(WsCloseCode::ProtocolError)], "close code {code}");
+        }
+        let invalid_close_reason = client_frame(true, WsFrameOpcodes::Close, &[0x03, 0xe8, 0xff]);
+        assert_eq!(read_messages(&invalid_close_reason), vec![Err(WsCloseCode::InvalidPayload)]);
+        let custom_close_code = client_frame(true, WsFrameOpcodes::Close, &4000u16.to_be_bytes());
+        assert_eq!(read_messages(&custom_close_code), vec![Ok(WsMessage::Close(Some(4000)))]);
+
+        let mut too_big = create_ws_frame(WsFrameOpcodes::Binary, &[]);
+        too_big[1] = 0b1111_1111;
+        too_big.extend_from_slice(&(u64::try_from(MAX_MESSAGE_LEN).unwrap() + 1).to_be_bytes());
+        assert_eq!(read_messages(&too_big), vec![Err(WsCloseCode::MessageTooBig)]);
+
+        // the payload is not allocated up front, a truncated frame just ends the connection
+        let mut truncated = create_ws_frame(WsFrameOpcodes::Binary, &[]);
+        truncated[1] = 0b1111_1111;
+        truncated.extend_from_slice(&u64::try_from(MAX_MESSAGE_LEN).unwrap().to_be_bytes());
+        truncated.extend_from_slice(&[1, 2, 3, 4, 0]);
+        assert_eq!(read_messages(&truncated), vec![]);
+
+        let fragments = [client_frame(false, WsFrameOpcodes::Text, &[b'x'; 3]), client_frame(true, WsFrameOpcodes::Continuation, &[b'x'; 3])].concat();
+        assert_eq!(read_messages_limited(&fragments, 6), vec![Ok(WsMessage::Text("x".repeat(6)))]);
+        assert_eq!(read_messages_limited(&fragments, 5), vec![Err(WsCloseCode::MessageTooBig)]);
+    }
+
+    #[test]
+    fn test_topic_rate_limiter() {
+        let start = Instant::now();
+        let mut decimated = WsTopicRateLimiter::new(WsTopicOptions { max_rate_hz: None, decimation: Some(3) });
+        let sent: Vec<_> = (0..7).map(|_| decimated.should_send(start)).collect();
+        assert_eq!(sent, vec![true, false, false, true, false, false, true]);
+
+        let mut rate_limited = WsTopicRateLimiter::new(WsTopicOptions { max_rate_hz: Some(10.0), decimation: None });
+        let sent: Vec<_> = [0, 50, 99, 100, 150, 210].into_iter().map(|ms| rate_limited.should_send(start + std::time::Duration::from_millis(ms))).collect();
+        assert_eq!(sent, vec![true, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn test_subscriptions_and_drop_oldest() {
+        let (outbound_tx, outbound_rx) = crossbeam_channel::bounded(OUTBOUND_QUEUE_LEN);
+        let mut client = MAHWebsocket { uid: 0, subscriptions: None, outbound_tx, outbound_rx: outbound_rx.clone() };
+        let now = Instant::now();
+        assert!(client.wants(WsTopic::Tracking, now), "clients receive all topics until they subscribe");
+
+        let WsClientRequest::Client(AdapticsWSClientMessage::Subscribe { topics }) = parse_ws_message(br#"{ "cmd": "subscribe", "data": { "topics": { "events": {} } } }"#).unwrap() else { panic!("expected subscribe") };
+        client.subscribe(topics);
+        assert!(client.wants(WsTopic::Events, now));
+        assert!(!client.wants(WsTopic::Tracking, now));
+        assert!(matches!(parse_ws_message(br#"{ "cmd": "stop", "data": {} }"#), Ok(WsClientRequest::Update(PatternEvalUpdate::Stop {}))));
+        assert!(parse_ws_message(br#"{ "cmd": "subscribe", "data": { "topics": { "nope": {} } } }"#).is_err());
+
+        let dropped: usize = (0..OUTBOUND_QUEUE_LEN + 2).map(|i| client.queue(vec![u8::try_from(i).unwrap()].into())).sum();
+        assert_eq!(dropped, 2);
+        assert_eq!(outbound_rx.try_recv().unwrap()[0], 2, "the oldest frames should be dropped");
+    }
+
+    #[test]
+    fn test_parse_requests() {
+        let WsClientRequest::Request { id: 7, body: Ok(AdapticsWSRequestBody::Query(AdapticsWSQuery::GetPlaybackState {})) } = parse_ws_message(br#"{ "v": 1, "id": 7, "request": { "cmd": "get_playback_state", "data": {} } }"#).unwrap() else { panic!("expected query") };
+        let WsClientRequest::Request { id: 8, body: Ok(AdapticsWSRequestBody::Update(PatternEvalUpdate::Seek { .. })) } = parse_ws_message(br#"{ "v": 1, "id": 8, "request": { "cmd": "seek", "data": { "pattern_time": 10 } } }"#).unwrap() else { panic!("expected update") };
+        let WsClientRequest::Request { id: 9, body: Ok(AdapticsWSRequestBody::Client(_)) } = parse_ws_message(br#"{ "v": 1, "id": 9, "request": { "cmd": "subscribe", "data": { "topics": {} } } }"#).unwrap() else { panic!("expected subscribe") };
+        let WsClientRequest::Request { id: 10, body: Err(message) } = parse_ws_message(br#"{ "v": 2, "id": 10, "request": { "cmd": "stop", "data": {} } }"#).unwrap() else { panic!("expected error") };
+        assert!(message.contains("protocol version"), "{message}");
+        let WsClientRequest::Request { id: 11, body: Err(message) } = parse_ws_message(br#"{ "v": 1, "id": 11, "request": { "cmd": "seek", "data": {} } }"#).unwrap() else { panic!("expected error") };
+        assert!(message.contains("pattern_time"), "errors should come from the matching variant: {message}");
+        assert!(parse_ws_message(br#"{ "v": 1, "request": { "cmd": "stop", "data": {} } }"#).is_err(), "requests without an id cannot be answered");
+    }
+
+    #[test]
+    fn test_request_responses() {
+        let wsclients = Mutex::new(Vec::new());
+        let (outbound_tx, _outbound_rx) = crossbeam_channel::unbounded();
+        let (replies_tx, replies_rx) = crossbeam_channel::unbounded();
+        let (patteval_request_tx, patteval_request_rx) = crossbeam_channel::unbounded();
+        let pattern_eval = std::thread::spawn(move || {
+            let mut pending_query: Option<PatternEvalReplyTx> = None;
+            for request in patteval_request_rx {
+                match request {
+                    PatternEvalRequest::Update { update: PatternEvalUpdate::Stop {}, reply_tx } => {
+                        if let Some(reply_tx) = pending_query.take() { reply_tx.send(Err("answered late".to_string())).unwrap(); }
+                        reply_tx.unwrap().send(Ok(AdapticsWSResponse::Ack {})).unwrap();
+                    },
+                    PatternEvalRequest::Update { reply_tx, .. } => { reply_tx.unwrap().send(Err("rejected".to_string())).unwrap(); },
+                    PatternEvalRequest::Query { reply_tx, .. } => pending_query = Some(reply_tx), // answered with the next stop
+                    PatternEvalRequest::TactonsChanged { .. } => unreachable!(),
+                }
+            }
+        });
+        let request = |request: &str| assert!(handle_ws_message(request.as_bytes(), 0, &wsclients, &outbound_tx, &replies_tx, &patteval_request_tx));
+        let reply = || replies_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap();
+
+        request(r#"{ "v": 1, "id": 1, "request": { "cmd": "pause", "data": {} } }"#);
+        assert!(matches!(reply(), (1, Err(message)) if message == "rejected"));
+        request(r#"{ "v": 1, "id": 2, "request": { "cmd": "nope", "data": {} } }"#);
+        assert!(matches!(reply(), (2, Err(_))));
+
+        // the client does not wait for the answer to a request before the next one is handled
+        request(r#"{ "v": 1, "id": 3, "request": { "cmd": "get_version", "data": {} } }"#);
+        request(r#"{ "v": 1, "id": 4, "request": { "cmd": "stop", "data": {} } }"#);
+        assert!(matches!(reply(), (3, Err(message)) if message == "answered late"));
+        assert!(matches!(reply(), (4, Ok(AdapticsWSResponse::Ack {}))));
+
+        drop(patteval_request_tx);
+        pattern_eval.join().unwrap();
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut config = WsServerConfig::new("127.0.0.1:0".to_string());
+        let handshake = |target: &str, extra_headers: &str, config: &WsServerConfig| {
+            let request = format!("GET {target} HTTP/1.1\r\nHost: localhost:8037\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\nSec-WebSocket-Version: 13\r\n{extra_headers}\r\n");
+            check_handshake(&http::read_http_request(&mut request.as_bytes()).unwrap(), config)
+        };
+        let key = "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+
+        assert_eq!(handshake("/", key, &config), Ok(("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(), true)), "example from rfc6455#section-1.3");
+        assert_eq!(handshake("/", "", &config), Err((400, "Bad Request")), "the key is required");
+        assert_eq!(handshake("/", "Sec-WebSocket-Key: c2hvcnQ=\r\n", &config), Err((400, "Bad Request")), "the key must be a 16 byte nonce");
+        assert_eq!(handshake("/other", key, &config), Err((404, "Not Found")));
+
+        for origin in ["https://adaptivehaptics.github.io", "http://localhost:5173", "HTTP://LOCALHOST", "http://127.0.0.1:8080"] {
+            assert!(handshake("/", &format!("{key}Origin: {origin}\r\n"), &config).is_ok(), "{origin} should be allowed");
+        }
+        for origin in ["https://evil.example", "http://localhost.evil.example", "http://localhost:80x", "null"] {
+            assert_eq!(handshake("/", &format!("{key}Origin: {origin}\r\n"), &config), Err((403, "Forbidden")), "{origin} should be rejected");
+        }
+
+        config.token = Some("s3cret".to_string());
+        assert!(matches!(handshake("/", key, &config), Ok((_, false))), "the token may be sent in the first message");
+        assert!(matches!(handshake("/?token=s3cret", key, &config), Ok((_, true))));
+        assert_eq!(handshake("/?token=guess", key, &config), Err((403, "Forbidden")));
+    }
+
+    #[test]
+    fn test_auth_message() {
+        let (outbound_tx, outbound_rx) = crossbeam_channel::unbounded();
+        let auth = |messages: &[&str]| {
+            let stream: Vec<u8> = messages.iter().flat_map(|m| client_frame(true, WsFrameOpcodes::Text, m.as_bytes())).collect();
+            ws_auth(&mut stream.as_slice(), "s3cret", 0, &outbound_tx)
+        };
+
+        assert!(auth(&[r#"{ "cmd": "auth", "data": { "token": "s3cret" } }"#]).is_ok());
+        assert!(auth(&[r#"{ "v": 1, "id": 1, "request": { "cmd": "auth", "data": { "token": "s3cret" } } }"#]).is_ok());
+        let frame = outbound_rx.try_recv().unwrap();
+        assert!(matches!(serde_json::from_slice(&frame[2..]).unwrap(), AdapticsWSServerMessage::Response { id: 1, result: AdapticsWSResult::Ok { .. } }));
+
+        let policy_violation = close_frame(WsCloseCode::PolicyViolation as u16, "authentication required");
+        assert_eq!(auth(&[r#"{ "cmd": "auth", "data": { "token": "guess" } }"#]), Err(Some(policy_violation.clone())));
+        assert_eq!(auth(&[r#"{ "cmd": "stop", "data": {} }"#, r#"{ "cmd": "auth", "data": { "token": "s3cret" } }"#]), Err(Some(policy_violation)), "the first message must authenticate");
+        assert_eq!(auth(&[]), Err(None));
+
+        let message_too_big = close_frame(WsCloseCode::MessageTooBig as u16, "message too big");
+        assert_eq!(auth(&[&"x".repeat(MAX_AUTH_MESSAGE_LEN + 1)]), Err(Some(message_too_big)), "unauthenticated clients cannot send large messages");
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let connections = ConnectionCounter::default();
+        let first = connections.try_acquire(2).unwrap();
+        let second = connections.try_acquire(2).unwrap();
+        assert!(connections.try_acquire(2).is_none());
+        drop(first);
+        let third = connections.try_acquire(2).unwrap();
+        assert!(connections.try_acquire(2).is_none());
+        drop((second, third));
+        assert_eq!(connections.0.load(Ordering::Acquire), 0);
+    }
+}
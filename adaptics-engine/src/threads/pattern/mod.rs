@@ -1 +1,5 @@
-pub(crate) mod playback;
\ No newline at end of file
+pub(crate) mod playback;
+pub(crate) mod queue;
+pub(crate) mod safety;
+pub(crate) mod tracking_bindings;
+pub(crate) mod voice;
@@ -1,20 +1,37 @@
 use std::collections::HashMap;
 use std::ops::Sub;
 use std::time::Instant;
-use pattern_evaluator::{PatternEvaluator, PatternEvaluatorParameters, BrushAtAnimLocalTime, NextEvalParams, MAHTime};
+use pattern_evaluator::{PatternEvaluator, PatternEvaluatorParameters, BrushAtAnimLocalTime, NextEvalParams, MAHTime, UserParameters, UserParameterDefinitions};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::{threads::{common::{ MilSec, instant_add_js_milliseconds }, net::websocket::AdapticsWSServerMessage, tracking::TrackingFrame}, DEBUG_LOG_LAG_EVENTS};
+use crate::{threads::{common::{ MilSec, instant_add_js_milliseconds }, net::websocket::{AdapticsWSServerMessage, AdapticsWSQuery, AdapticsWSResponse, WS_PROTOCOL_VERSION}, tracking::{TrackingFrame, TrackedHand}}, telemetry::Telemetry, tacton_library::TactonLibrary, config::DebugConfig, session_log::{SessionLogger, SessionLogEntry}};
+use super::queue::{PlaybackQueue, QueueEntry, QueueEntryEnd};
+use super::safety::SafetyConfig;
+use super::tracking_bindings::{TrackingBinder, TrackingBinding};
+use super::voice::{allocate_voice_id, Voice, VoiceId, VoiceScheduler, DEFAULT_VOICE_ID};
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "cmd", content = "data")]
 #[serde(rename_all = "snake_case")]
 pub enum PatternEvalUpdate {
 	/// `pattern_json` is a string containing the tacton/pattern in JSON format (see [`pattern_evaluator::MidAirHapticsAnimationFileFormat`])
+	///
+	/// If the pattern is switched during playback, the intensity of the old pattern is crossfaded into the new one over `crossfade_ms`,
+	/// and the position of the old pattern is interpolated towards the new one over `path_interpolation_ms`.
+	/// If neither is given (or both are 0), the pattern is replaced immediately.
 	#[serde(rename="update_pattern")]
-    Pattern{ pattern_json: String },
+    Pattern{
+		pattern_json: String,
+		#[serde(default)]
+		crossfade_ms: Option<MilSec>,
+		#[serde(default)]
+		path_interpolation_ms: Option<MilSec>,
+	},
 
-	/// if playstart is 0.0, then the pattern is stopped. Otherwise, it is started at the time given by `now() + playstart_offset`.
+	/// if playstart is 0.0, then the pattern is stopped (like stopping the default voice, see [`PatternEvalUpdate::VoiceStop`]). Otherwise, it is started at the time given by `now() + playstart_offset`.
+	///
+	/// Kept for the designer interface, prefer [`PatternEvalUpdate::Play`], [`PatternEvalUpdate::Pause`], [`PatternEvalUpdate::Resume`], [`PatternEvalUpdate::Seek`] and [`PatternEvalUpdate::Stop`].
 	///
 	/// I know this is unecessarily complicated. I was not sure how to unify the playback implementations in the designer interface and the engine, causing this mess.
 	// There is not much point in sending playstart, since playback is relative to playstart_offset, which means we could just have Play(at_pattern_time), Pause(), Resume(), Stop() commands and latency would be ignored the same way it is now.
@@ -23,13 +40,103 @@ pub enum PatternEvalUpdate {
 	#[serde(rename="update_playstart")]
     Playstart{ playstart: MilSec, playstart_offset: MilSec },
 
+	/// Starts playback at `pattern_time` (in milliseconds)
+	#[serde(rename="play")]
+	Play{ pattern_time: MAHTime },
+
+	/// Pauses playback, freezing the pattern time (intensity is 0 while paused). Other voices keep playing.
+	///
+	/// A queue entry that is playing stays the current entry while paused, so the queue waits until playback continues
+	/// with [`PatternEvalUpdate::Resume`] or [`PatternEvalUpdate::Play`]. [`PatternEvalUpdate::Stop`] ends it instead.
+	#[serde(rename="pause")]
+	Pause{},
+
+	/// Resumes playback from the pattern time it was paused at, continuing the paused queue entry (if any)
+	#[serde(rename="resume")]
+	Resume{},
+
+	/// Moves playback to `pattern_time` (in milliseconds), without changing whether the pattern is playing or paused
+	#[serde(rename="seek")]
+	Seek{ pattern_time: MAHTime },
+
+	/// Stops playback and rewinds to the start of the pattern.
+	/// Also stops all other voices (reporting [`AdapticsEngineEvent::VoiceFinished`]) and clears the playback queue (the playing entry is reported as not completed).
+	/// Playback updates are sent until one with `stop` set, like when a pattern ends.
+	#[serde(rename="stop")]
+	Stop{},
+
+	/// Loads the tacton `name` from the tacton library and plays it from the start, replacing the current pattern.
+	/// `name` is a file name in the tacton directory (with or without `.adaptics`) or the `name` stored in a tacton.
+	///
+	/// While it is loaded, the pattern is reloaded whenever its file is edited (keeping the pattern time).
+	#[serde(rename="play_tacton")]
+	PlayTacton{ name: String },
+
 	/// See [`PatternEvaluatorParameters`]
 	#[serde(rename="update_parameters")]
     Parameters{ evaluator_params: PatternEvaluatorParameters },
 
-	/// Enable body tracking for haptic playback
+	/// Enable body tracking for haptic playback.
+	/// `hand` selects which hand(s) the focal point follows, it is kept unchanged if not given (see [`PlaybackConfig::tracked_hand`]).
 	#[serde(rename="update_tracking")]
-	Tracking{ enabled: bool },
+	Tracking{
+		enabled: bool,
+		#[serde(default)]
+		hand: Option<TrackedHand>,
+	},
+
+	/// Replaces the tracking bindings, see [`PlaybackConfig::tracking_bindings`]
+	#[serde(rename="update_tracking_bindings")]
+	TrackingBindings{ bindings: Vec<TrackingBinding> },
+
+	/// Automation tracks for user parameters, e.g. loaded from a sidecar file (see [`pattern_evaluator::MAHUserParameterAutomationTrack`]).
+	///
+	/// These are kept across pattern updates, and replace any tracks for the same user parameters embedded in the pattern.
+	/// Send an empty `automation` to remove them again.
+	#[serde(rename="update_user_parameter_automation")]
+	UserParameterAutomation{ automation: pattern_evaluator::UserParameterAutomation },
+
+	/// Adds a tacton to the end of the playback queue.
+	/// Queue entries are played back-to-back, replacing the current pattern, and report [`AdapticsEngineEvent::QueueEntryStarted`] and [`AdapticsEngineEvent::QueueEntryFinished`].
+	///
+	/// Stopping the default voice or loading another pattern on it (e.g. [`PatternEvalUpdate::Pattern`] or [`PatternEvalUpdate::PlayTacton`]) ends the entry that is playing
+	/// (reported as not completed), and the next entry starts.
+	#[serde(rename="queue_enqueue")]
+	QueueEnqueue{ entry: QueueEntry },
+
+	/// Removes all entries from the playback queue and stops the entry that is currently playing (if any).
+	/// Stopping an entry sends playback updates until one with `stop` set, like [`PatternEvalUpdate::Stop`].
+	#[serde(rename="queue_clear")]
+	QueueClear{},
+
+	/// Starts playing a tacton on voice `voice_id`, concurrently with the other voices.
+	/// The focal point is time-multiplexed between all playing voices, weighted by `priority`.
+	///
+	/// Voice 0 is the default voice (controlled by all commands that do not take a `voice_id`), playing on it replaces the current pattern.
+	/// Other voices are removed when their pattern ends, reporting [`AdapticsEngineEvent::VoiceFinished`].
+	/// Starting a voice other than 0 that is still playing is rejected, stop it first (see [`PatternEvalUpdate::VoiceStop`]).
+	///
+	/// If `voice_id` is not given, the engine picks a voice id that is not playing.
+	/// Requests are answered with [`AdapticsWSResponse::VoiceStarted`], containing the id of the voice.
+	#[serde(rename="voice_play")]
+	VoicePlay{
+		#[serde(default)]
+		voice_id: Option<VoiceId>,
+		pattern_json: String,
+		#[serde(default = "default_voice_priority")]
+		priority: u32,
+		#[serde(default)]
+		user_parameters: UserParameters,
+	},
+
+	/// Stops voice `voice_id`.
+	/// Stopping the default voice sends playback updates until one with `stop` set, like [`PatternEvalUpdate::Stop`].
+	#[serde(rename="voice_stop")]
+	VoiceStop{ voice_id: VoiceId },
+
+	/// Updates (merges) the user parameters of voice `voice_id`
+	#[serde(rename="voice_update_user_parameters")]
+	VoiceUserParameters{ voice_id: VoiceId, user_parameters: UserParameters },
 
 	//*** currently not sent over websocket, just for lib ***//
 	ParameterTime { time: MAHTime },
@@ -38,20 +145,214 @@ pub enum PatternEvalUpdate {
 	UserParameter { name: String, value: f64 },
 }
 
+/// Events emitted by the playback thread (e.g. to notify the host application about queue progress)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum AdapticsEngineEvent {
+	QueueEntryStarted{ id: String },
+	/// `completed` is false if the entry was stopped early (queue cleared or pattern failed to load)
+	QueueEntryFinished{ id: String, completed: bool },
+	/// A voice other than the default voice finished playing (or was stopped) and was removed
+	VoiceFinished{ voice_id: VoiceId },
+	/// An update was rejected (e.g. a pattern failed to parse). Playback continues with the previous state.
+	Error{ message: String },
+}
+
+fn default_voice_priority() -> u32 { 1 }
+
+/// The answer to a [`PatternEvalRequest`] (or the reason the update was rejected), with the id of the request
+pub(crate) type PatternEvalReply = (u64, Result<AdapticsWSResponse, String>);
+
+/// Receives the answer to a [`PatternEvalRequest`]. Answers are tagged with the id of their request,
+/// so the answers to all requests of a websocket client can be sent through one channel (without waiting for them).
+pub(crate) struct PatternEvalReplyTx {
+	id: u64,
+	tx: crossbeam_channel::Sender<PatternEvalReply>,
+}
+impl PatternEvalReplyTx {
+	pub fn new(id: u64, tx: crossbeam_channel::Sender<PatternEvalReply>) -> Self {
+		Self { id, tx }
+	}
+
+	/// For requesters that wait for the answer to a single request
+	pub fn oneshot() -> (Self, crossbeam_channel::Receiver<PatternEvalReply>) {
+		let (tx, rx) = crossbeam_channel::bounded(1);
+		(Self::new(0, tx), rx)
+	}
+
+	/// Never blocks the pattern-eval thread, fails if the requester stopped listening or is not keeping up
+	pub fn send(self, result: Result<AdapticsWSResponse, String>) -> Result<(), ()> {
+		self.tx.try_send((self.id, result)).map_err(drop)
+	}
+}
+
+/// Updates and queries that are answered by the pattern-eval thread (e.g. for websocket requests)
+pub(crate) enum PatternEvalRequest {
+	/// Answered with [`AdapticsWSResponse::Ack`] once applied, if `reply_tx` is given
+	Update{ update: PatternEvalUpdate, reply_tx: Option<PatternEvalReplyTx> },
+	Query{ query: AdapticsWSQuery, reply_tx: PatternEvalReplyTx },
+	/// Sent by the tacton library watcher when files in the tacton directory were added, edited or removed
+	TactonsChanged{ file_names: Vec<String> },
+}
+
+/// Playback update options, see [`crate::EngineConfig::playback`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlaybackConfig {
+	/// The number of seconds between each playback update sent to websocket clients (and returned by `adaptics_engine_get_playback_updates`)
+	pub seconds_per_playback_update: f64,
+	/// Sends the evals before the tracking offset is applied in playback updates, instead of the evals sent to the device
+	pub send_untracked_playback_updates: bool,
+	/// Which hand(s) the focal point follows while tracking is enabled, until changed with [`PatternEvalUpdate::Tracking`]
+	pub tracked_hand: TrackedHand,
+	/// User parameters that are set from the hand the focal point follows (the first one with [`TrackedHand::Both`]) whenever a tracking frame arrives,
+	/// until replaced with [`PatternEvalUpdate::TrackingBindings`]. Bindings are applied even while tracking is disabled, and override values set by the host.
+	///
+	/// e.g. `tracking_bindings = [{ parameter = "intensity", quantity = "palm_height", input = [300, 100] }]`
+	pub tracking_bindings: Vec<TrackingBinding>,
+}
+impl Default for PlaybackConfig {
+	fn default() -> Self {
+		Self { seconds_per_playback_update: crate::SECONDS_PER_PLAYBACK_UPDATE, send_untracked_playback_updates: false, tracked_hand: TrackedHand::default(), tracking_bindings: Vec::new() }
+	}
+}
+
+/// Sends events without blocking the pattern-eval thread, events are dropped if the receiver lags
+struct EventSender<'a> {
+	tx: &'a crossbeam_channel::Sender<AdapticsEngineEvent>,
+	log_lag_events: bool,
+	session_log: Option<&'a SessionLogger>,
+}
+
+fn send_error(events_tx: &EventSender, message: String) {
+	eprintln!("error: {message}");
+	send_event(events_tx, AdapticsEngineEvent::Error { message });
+}
+
+fn send_event(events_tx: &EventSender, event: AdapticsEngineEvent) {
+	if let Some(session_log) = events_tx.session_log { session_log.log(SessionLogEntry::Event(event.clone())); }
+	match events_tx.tx.try_send(event) {
+		Err(crossbeam_channel::TrySendError::Full(_)) => { if events_tx.log_lag_events { println!("event receiver lagged [events]"); } },
+		Err(crossbeam_channel::TrySendError::Disconnected(_)) | Ok(()) => {},
+	}
+}
+
+/// Merges the sidecar automation into a newly loaded pattern, and keeps a copy of the pattern's own automation in `pattern_automation`.
+/// Smoothed user parameters start at their target values again, instead of ramping from the values of the previous pattern.
+fn on_pattern_loaded(pattern_eval: &mut PatternEvaluator, pattern_automation: &mut pattern_evaluator::UserParameterAutomation, sidecar_automation: &pattern_evaluator::UserParameterAutomation, user_parameter_smoother: &mut UserParameterSmoother) {
+	user_parameter_smoother.reset();
+	pattern_automation.clone_from(pattern_eval.user_parameter_automation());
+	if !sidecar_automation.is_empty() {
+		pattern_eval.set_user_parameter_automation(merge_automation(pattern_automation, sidecar_automation));
+	}
+}
+
+/// Ends the queue entry that is playing (if any), because the default voice was stopped or replaced by something outside the queue
+fn abort_queue_entry(playback_queue: &mut PlaybackQueue, user_parameters: &mut UserParameters, events_tx: &EventSender) {
+	if let Some(entry) = playback_queue.abort_current(user_parameters) {
+		send_event(events_tx, AdapticsEngineEvent::QueueEntryFinished { id: entry.id, completed: false });
+	}
+}
+
+/// Stops the default voice (and ends the queue entry it was playing, if any).
+/// Playback updates continue until an update with `stop == true` was sent.
+fn stop_default_voice(
+	pattern_playstart: &mut Option<Instant>,
+	send_stopping_updates: &mut bool,
+	next_eval_params: &mut NextEvalParams,
+	playback_queue: &mut PlaybackQueue,
+	user_parameters: &mut UserParameters,
+	events_tx: &EventSender,
+) {
+	*pattern_playstart = None;
+	*send_stopping_updates = true; // continue sending until playback_update_buffer[0].stop == true is sent
+	*next_eval_params = NextEvalParams::default();
+	abort_queue_entry(playback_queue, user_parameters, events_tx);
+}
+
+fn merge_automation(pattern_automation: &pattern_evaluator::UserParameterAutomation, sidecar_automation: &pattern_evaluator::UserParameterAutomation) -> pattern_evaluator::UserParameterAutomation {
+	let mut automation = pattern_automation.clone();
+	automation.extend(sidecar_automation.iter().map(|(name, track)| (name.clone(), track.clone())));
+	automation
+}
+
+/// Slews user parameters that have a `smoothing` time constant towards their target values (exponential smoothing).
+#[derive(Default)]
+struct UserParameterSmoother {
+	values: HashMap<String, f64>,
+	last_instant: Option<Instant>,
+}
+impl UserParameterSmoother {
+	fn reset(&mut self) {
+		self.values.clear();
+		self.last_instant = None;
+	}
+
+	/// Updates `eval_user_parameters` in place with the smoothed values of `target_user_parameters` at `now`
+	fn update(&mut self, now: Instant, target_user_parameters: &UserParameters, definitions: &UserParameterDefinitions, eval_user_parameters: &mut UserParameters) {
+		let dt = self.last_instant.map_or(0.0, |last| now.saturating_duration_since(last).as_secs_f64() * 1000.0);
+		self.last_instant = Some(now);
+		for (name, def) in definitions {
+			let Some(smoothing) = def.smoothing.filter(|s| *s > 0.0) else { continue; };
+			let Some(target) = target_user_parameters.get(name) else { continue; };
+			let value = self.values.entry(name.clone()).or_insert(*target);
+			*value += (target - *value) * (1.0 - (-dt / smoothing).exp());
+			eval_user_parameters.insert(name.clone(), *value);
+		}
+	}
+}
+
+/// The previous pattern while it is being blended into the new one
+struct PatternCrossfade {
+	pattern_eval: PatternEvaluator,
+	next_eval_params: NextEvalParams,
+	/// Instant of the first device sample that was blended, so the fade is timed by the samples (not by when the update arrived)
+	start: Option<Instant>,
+	crossfade_ms: MilSec,
+	path_interpolation_ms: MilSec,
+}
+impl PatternCrossfade {
+	/// Blends `eval` (from the new pattern) with the old pattern at `time`. Returns false once the crossfade is finished.
+	fn blend(&mut self, time: Instant, parameters: &PatternEvaluatorParameters, eval: &mut BrushAtAnimLocalTime) -> bool {
+		let start = *self.start.get_or_insert(time);
+		let elapsed = time.saturating_duration_since(start).as_secs_f64() * 1000.0;
+		let intensity_f = if self.crossfade_ms > 0.0 { (elapsed / self.crossfade_ms).min(1.0) } else { 1.0 };
+		let path_f = if self.path_interpolation_ms > 0.0 { (elapsed / self.path_interpolation_ms).min(1.0) } else { 1.0 };
+		if intensity_f >= 1.0 && path_f >= 1.0 { return false; }
+
+		let old_eval = self.pattern_eval.eval_brush_at_anim_local_time(parameters, &self.next_eval_params);
+		self.next_eval_params = old_eval.next_eval_params;
+		let old_cp = old_eval.ul_control_point;
+		let cp = &mut eval.ul_control_point;
+		cp.intensity = old_cp.intensity * (1.0 - intensity_f) + cp.intensity * intensity_f;
+		cp.coords = &old_cp.coords * (1.0 - path_f) + &cp.coords * path_f;
+		true
+	}
+}
+
 pub(crate) enum PatternEvalCall {
     EvalBatch{ time_arr_instants: Vec<Instant>},
 }
 
-/// if `seconds_per_playback_update` is true, send playback updates prior to applying tracking translation
+/// if `config.send_untracked_playback_updates` is true, send playback updates prior to applying tracking translation
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn pattern_eval_loop(
-	seconds_per_playback_update: f64,
-	send_untracked_playback_updates: bool,
+	config: &PlaybackConfig,
+	safety: &SafetyConfig,
+	debug: DebugConfig,
 	patteval_call_rx: &crossbeam_channel::Receiver<PatternEvalCall>,
 	patteval_update_rx: &crossbeam_channel::Receiver<PatternEvalUpdate>,
+	patteval_request_rx: &crossbeam_channel::Receiver<PatternEvalRequest>,
 	patteval_return_tx: &crossbeam_channel::Sender<Vec<BrushAtAnimLocalTime>>,
 	playback_updates_tx: Option<&crossbeam_channel::Sender<AdapticsWSServerMessage>>,
 	tracking_data_rx: Option<&crossbeam_channel::Receiver<TrackingFrame>>,
+	events_tx: &crossbeam_channel::Sender<AdapticsEngineEvent>,
+	telemetry: &Telemetry,
+	tacton_library: &TactonLibrary,
+	session_log: Option<&SessionLogger>,
 ) -> Result<(), crossbeam_channel::RecvError> {
+	let events_tx = &EventSender { tx: events_tx, log_lag_events: debug.log_lag_events, session_log };
 	let default_pattern = pattern_evaluator::MidAirHapticsAnimationFileFormat {
 		data_format: pattern_evaluator::MidAirHapticsAnimationFileFormatDataFormatName::DataFormat,
 		revision: pattern_evaluator::DataFormatRevision::CurrentRevision,
@@ -59,23 +360,37 @@ pub(crate) fn pattern_eval_loop(
 		keyframes: vec![],
 		pattern_transform: pattern_evaluator::PatternTransformation::default(),
 		user_parameter_definitions: HashMap::new(),
+		user_parameter_automation: HashMap::new(),
 	};
 
 	let mut pattern_eval = PatternEvaluator::new(default_pattern);
+	let mut pattern_automation = pattern_evaluator::UserParameterAutomation::new(); // automation embedded in the current pattern
+	let mut sidecar_automation = pattern_evaluator::UserParameterAutomation::new();
 	let mut pattern_playstart: Option<Instant> = None;
 	let mut parameters = PatternEvaluatorParameters { time: 0.0, user_parameters: HashMap::new(), geometric_transform: Default::default() };
-	let mut tracking_data: TrackingFrame = TrackingFrame { hand: None };
+	let mut tracking_data: TrackingFrame = TrackingFrame::new(None, None);
 	let mut enable_tracking = false;
+	let mut tracked_hand = config.tracked_hand;
+	let mut tracked_hand_sample: usize = 0; // alternates the samples between the hands with TrackedHand::Both
+	let mut tracking_binder = TrackingBinder::new(config.tracking_bindings.clone());
 
 	let mut last_playback_update = Instant::now();
 	let mut playback_update_buffer: Vec<BrushAtAnimLocalTime> = Vec::with_capacity(1024); // 20khz / 60hz = ~333.33 is the number of EvalResults sent in a batch
 
 	let mut next_eval_params = NextEvalParams::default();
+	let mut user_parameter_smoother = UserParameterSmoother::default();
+	let mut pattern_crossfade: Option<PatternCrossfade> = None;
+	let mut playback_queue = PlaybackQueue::default();
+	let mut voices: HashMap<VoiceId, Voice> = HashMap::new();
+	let mut voice_scheduler = VoiceScheduler::default();
+	let mut next_voice_id: VoiceId = 1;
+	let mut default_voice_priority = default_voice_priority();
+	let mut loaded_tacton: Option<String> = None; // file name of the tacton library entry loaded on the default voice, reloaded when edited
 
 	let mut send_stopping_updates = false;
 
 	#[allow(clippy::items_after_statements)]
-	fn send_playback_updates(last_playback_update: &mut Instant, playback_update_buffer: &mut Vec<BrushAtAnimLocalTime>, playback_updates_tx: Option<&crossbeam_channel::Sender<AdapticsWSServerMessage>>) {
+	fn send_playback_updates(last_playback_update: &mut Instant, playback_update_buffer: &mut Vec<BrushAtAnimLocalTime>, playback_updates_tx: Option<&crossbeam_channel::Sender<AdapticsWSServerMessage>>, telemetry: &Telemetry, log_lag_events: bool, session_log: Option<&SessionLogger>) {
 		*last_playback_update = Instant::now();
 		if playback_update_buffer.is_empty() {
 			println!("[warn] skipping network update (no evals)");
@@ -88,10 +403,11 @@ pub(crate) fn pattern_eval_loop(
 		// }
 		if let Some(playback_updates_tx) = &playback_updates_tx {
 			match playback_updates_tx.try_send(AdapticsWSServerMessage::PlaybackUpdate{ evals: playback_update_buffer.clone() }) {
-				Err(crossbeam_channel::TrySendError::Full(_)) => { if DEBUG_LOG_LAG_EVENTS { println!("network thread lagged [playback]"); } },
+				Err(crossbeam_channel::TrySendError::Full(_)) => { telemetry.record_dropped_playback_update(); if log_lag_events { println!("network thread lagged [playback]"); } },
 				res => res.unwrap()
 			}
 		}
+		if let Some(session_log) = session_log { session_log.log_playback_update(playback_update_buffer); }
 		playback_update_buffer.clear();
 	}
 
@@ -100,6 +416,7 @@ pub(crate) fn pattern_eval_loop(
 		let mut sel = crossbeam_channel::Select::new();
 		let patteval_call_rx_idx = sel.recv(patteval_call_rx);
 		let patteval_update_rx_idx = sel.recv(patteval_update_rx);
+		let patteval_request_rx_idx = sel.recv(patteval_request_rx);
 		let tracking_data_rx_idx = tracking_data_rx.as_ref().map(|tracking_data_rx| sel.recv(tracking_data_rx));
 		let oper = sel.select();
 		match oper.index() {
@@ -107,92 +424,343 @@ pub(crate) fn pattern_eval_loop(
 				let call = oper.recv(patteval_call_rx)?;
 				match call {
 					PatternEvalCall::EvalBatch{ time_arr_instants } => {
+						let eval_start = Instant::now();
+						if let Some((now, entry)) = time_arr_instants.first().and_then(|now| Some((*now, playback_queue.start_next(*now, &mut parameters.user_parameters)?.clone()))) {
+							match PatternEvaluator::new_from_json_string(&entry.pattern_json) {
+								Ok(new_pattern_eval) => {
+									pattern_eval = new_pattern_eval;
+									on_pattern_loaded(&mut pattern_eval, &mut pattern_automation, &sidecar_automation, &mut user_parameter_smoother);
+									pattern_crossfade = None;
+									loaded_tacton = None;
+									parameters.time = 0.0;
+									next_eval_params = NextEvalParams::default();
+									last_playback_update = Instant::now();
+									playback_update_buffer.clear();
+									pattern_playstart = Some(now);
+									send_event(events_tx, AdapticsEngineEvent::QueueEntryStarted { id: entry.id });
+								},
+								Err(e) => {
+									send_error(events_tx, format!("failed to parse pattern of queue entry '{}': {e}", entry.id));
+									playback_queue.abort_current(&mut parameters.user_parameters);
+									send_event(events_tx, AdapticsEngineEvent::QueueEntryFinished { id: entry.id, completed: false });
+								},
+							}
+						}
+
+						let mut smoothed_parameters = pattern_eval.user_parameter_definitions().values().any(|def| def.smoothing.is_some()).then(|| parameters.clone());
 						let eval_arr_raw: Vec<_> = time_arr_instants.iter().map(|time| {
 							#[allow(clippy::cast_precision_loss)]
 							if let Some(playstart) = pattern_playstart {
 								parameters.time = time.sub(playstart).as_nanos() as f64 / 1e6;
 							} //else reuse the last parameters.time
-							let mut eval = pattern_eval.eval_brush_at_anim_local_time(&parameters, &next_eval_params);
+							let eval_parameters = if let Some(smoothed_parameters) = &mut smoothed_parameters {
+								smoothed_parameters.time = parameters.time;
+								user_parameter_smoother.update(*time, &parameters.user_parameters, pattern_eval.user_parameter_definitions(), &mut smoothed_parameters.user_parameters);
+								smoothed_parameters
+							} else { &parameters };
+							let mut eval = pattern_eval.eval_brush_at_anim_local_time(eval_parameters, &next_eval_params);
 							next_eval_params = eval.next_eval_params.clone();
-							if eval.stop && pattern_playstart.is_some() {
+							if let Some(crossfade) = &mut pattern_crossfade {
+								if !crossfade.blend(*time, eval_parameters, &mut eval) {
+									pattern_crossfade = None;
+								}
+							}
+							let queue_entry_ended = playback_queue.is_playing() && pattern_playstart.is_some() && (eval.stop || eval.pattern_time >= pattern_eval.end_time());
+							if queue_entry_ended {
+								match playback_queue.end_current(*time, &mut parameters.user_parameters) {
+									Some(QueueEntryEnd::Loop) => {
+										pattern_playstart = Some(*time);
+										parameters.time = 0.0;
+										next_eval_params = NextEvalParams::default();
+									},
+									Some(QueueEntryEnd::Finished(entry)) => {
+										pattern_playstart = None;
+										send_stopping_updates |= eval.stop;
+										send_event(events_tx, AdapticsEngineEvent::QueueEntryFinished { id: entry.id, completed: true });
+									},
+									None => {},
+								}
+							} else if eval.stop && pattern_playstart.is_some() {
 								pattern_playstart = None;
 								send_stopping_updates = true; // continue sending until playback_update_buffer[0].stop == true is sent
 								// println!("send_stopping_updates = true @ {}", parameters.time);
 							}
 							if pattern_playstart.is_none() { // if playback stopped or paused, continue evals but force 0 intensity
 								eval.ul_control_point.intensity = 0.0;
+								eval.stop |= send_stopping_updates; // stopped by a command, the pattern itself may not have reached a stop
+							}
+							if !voices.is_empty() {
+								let candidates = pattern_playstart.map(|_| (DEFAULT_VOICE_ID, default_voice_priority)).into_iter()
+									.chain(voices.iter().map(|(id, voice)| (*id, voice.priority)));
+								if let Some(voice_id) = voice_scheduler.select(candidates).filter(|id| *id != DEFAULT_VOICE_ID) {
+									if let Some(voice) = voices.get_mut(&voice_id) {
+										let (voice_eval, ended) = voice.eval(*time);
+										eval.ul_control_point = voice_eval.ul_control_point; // keep pattern_time/stop of the default voice for playback updates
+										if ended {
+											voices.remove(&voice_id);
+											voice_scheduler.remove(voice_id);
+											send_event(events_tx, AdapticsEngineEvent::VoiceFinished { voice_id });
+										}
+									}
+								}
 							}
 							eval
 						}).collect();
 
 						let eval_arr_tracking_adjusted = {
 							let mut eval_arr_tracking_adjusted = eval_arr_raw.clone();
-							if let (true, Some(hand_pos)) = (enable_tracking, &tracking_data.hand) {
+							let tracked_hands = if enable_tracking { tracking_data.tracked_hands(tracked_hand) } else { Vec::new() };
+							if !tracked_hands.is_empty() {
 								for e in &mut eval_arr_tracking_adjusted {
-									e.ul_control_point.coords.x += hand_pos.palm.position.x;
-									e.ul_control_point.coords.y += hand_pos.palm.position.y;
-									e.ul_control_point.coords.z = hand_pos.palm.position.z;
+									let hand_pos = &tracked_hands[tracked_hand_sample % tracked_hands.len()].palm.position;
+									tracked_hand_sample = tracked_hand_sample.wrapping_add(1);
+									e.ul_control_point.coords.x += hand_pos.x;
+									e.ul_control_point.coords.y += hand_pos.y;
+									e.ul_control_point.coords.z = hand_pos.z;
 								}
 							}
+							safety.apply(&mut eval_arr_tracking_adjusted);
 							eval_arr_tracking_adjusted
 						};
 
 						// send tracked evals to haptic device
+						telemetry.record_eval_time(eval_start.elapsed());
 						patteval_return_tx.send(eval_arr_tracking_adjusted.clone()).unwrap();
 
 
-						let send_updates = pattern_playstart.is_some() || send_stopping_updates;
+						let send_updates = pattern_playstart.is_some() || send_stopping_updates || !voices.is_empty();
 						if send_updates {
-							let playback_update_evals = if send_untracked_playback_updates { &eval_arr_raw } else { &eval_arr_tracking_adjusted };
+							let playback_update_evals = if config.send_untracked_playback_updates { &eval_arr_raw } else { &eval_arr_tracking_adjusted };
 							playback_update_buffer.extend_from_slice(playback_update_evals);
 
-							if last_playback_update.elapsed().as_secs_f64() > seconds_per_playback_update {
+							if last_playback_update.elapsed().as_secs_f64() > config.seconds_per_playback_update {
 								if send_stopping_updates && playback_update_buffer.first().is_some_and(|e| e.stop) {
 									send_stopping_updates = false; // finished sending stop updates
 								}
-								send_playback_updates(&mut last_playback_update, &mut playback_update_buffer, playback_updates_tx);
+								send_playback_updates(&mut last_playback_update, &mut playback_update_buffer, playback_updates_tx, telemetry, debug.log_lag_events, session_log);
 							}
 						}
 
 					},
 				}
 			},
-			i if i == patteval_update_rx_idx => {
-				let update = oper.recv(patteval_update_rx)?;
-				match update {
-					PatternEvalUpdate::Pattern{ pattern_json } => {
-						pattern_eval = PatternEvaluator::new_from_json_string(&pattern_json).unwrap(); //todo: handle error (not sure how to propagate it to calling thread)
-					},
-					PatternEvalUpdate::Parameters{ evaluator_params } => {
-						parameters = evaluator_params;
-					},
-					PatternEvalUpdate::Playstart{ playstart, playstart_offset } => {
-						// println!("playstart: {}, playstart_offset: {}", playstart, playstart_offset);
-						if playstart == 0.0 {
-							pattern_playstart = None;
-						} else {
-							// get current time in milliseconds as f64
+			i if i == patteval_update_rx_idx || i == patteval_request_rx_idx => {
+				let (update, reply_tx) = if i == patteval_update_rx_idx { (oper.recv(patteval_update_rx)?, None) } else {
+					match oper.recv(patteval_request_rx)? {
+						PatternEvalRequest::Update { update, reply_tx } => (update, reply_tx),
+						PatternEvalRequest::Query { query, reply_tx } => {
+							let response = match query {
+								AdapticsWSQuery::GetPattern {} => AdapticsWSResponse::Pattern { pattern_json: serde_json::to_string(pattern_eval.mah_animation()).unwrap() },
+								AdapticsWSQuery::GetParameters {} => AdapticsWSResponse::Parameters { evaluator_params: parameters.clone() },
+								AdapticsWSQuery::GetPlaybackState {} => AdapticsWSResponse::PlaybackState {
+									playing: pattern_playstart.is_some(),
+									pattern_time: parameters.time,
+									queue_entry: playback_queue.current().map(|entry| entry.id.clone()),
+									queue_len: playback_queue.len(),
+									voices: voices.keys().copied().collect(),
+									tracking_enabled: enable_tracking,
+									tracked_hand,
+								},
+								AdapticsWSQuery::GetVersion {} => AdapticsWSResponse::Version { engine_version: env!("CARGO_PKG_VERSION").to_string(), protocol_version: WS_PROTOCOL_VERSION },
+								AdapticsWSQuery::GetTactons {} => AdapticsWSResponse::Tactons { tactons: tacton_library.list() },
+							};
+							reply_tx.send(Ok(response)).ok(); // ignore send error (if the requester stopped waiting)
+							continue;
+						},
+						PatternEvalRequest::TactonsChanged { file_names } => {
+							if let Some(file_name) = loaded_tacton.as_ref().filter(|file_name| file_names.contains(file_name)) {
+								match tacton_library.get_file(file_name).map(|pattern_json| PatternEvaluator::new_from_json_string(&pattern_json)) {
+									Some(Ok(new_pattern_eval)) => {
+										println!("reloaded tacton '{file_name}'");
+										pattern_eval = new_pattern_eval;
+										on_pattern_loaded(&mut pattern_eval, &mut pattern_automation, &sidecar_automation, &mut user_parameter_smoother);
+										pattern_crossfade = None;
+										next_eval_params = NextEvalParams::new(parameters.time, 0.0);
+									},
+									Some(Err(e)) => send_error(events_tx, format!("failed to reload tacton '{file_name}', keeping the current pattern: {e}")),
+									None => send_error(events_tx, format!("tacton '{file_name}' was removed or is invalid, keeping the current pattern")),
+								}
+							}
+							continue;
+						},
+					}
+				};
+				let logged_update = session_log.map(|_| update.clone());
+				let res = 'apply: {
+					match update {
+						PatternEvalUpdate::Pattern{ pattern_json, crossfade_ms, path_interpolation_ms } => {
+							let new_pattern_eval = match PatternEvaluator::new_from_json_string(&pattern_json) {
+								Ok(new_pattern_eval) => new_pattern_eval,
+								Err(e) => break 'apply Err(format!("failed to parse pattern, keeping the current pattern: {e}")),
+							};
+							abort_queue_entry(&mut playback_queue, &mut parameters.user_parameters, events_tx);
+							let old_pattern_eval = std::mem::replace(&mut pattern_eval, new_pattern_eval);
+							let (crossfade_ms, path_interpolation_ms) = (crossfade_ms.unwrap_or(0.0), path_interpolation_ms.unwrap_or(0.0));
+							pattern_crossfade = (pattern_playstart.is_some() && (crossfade_ms > 0.0 || path_interpolation_ms > 0.0)).then(|| PatternCrossfade {
+								pattern_eval: old_pattern_eval,
+								next_eval_params: next_eval_params.clone(),
+								start: None,
+								crossfade_ms,
+								path_interpolation_ms,
+							});
+							on_pattern_loaded(&mut pattern_eval, &mut pattern_automation, &sidecar_automation, &mut user_parameter_smoother);
+							loaded_tacton = None;
+						},
+						PatternEvalUpdate::Parameters{ evaluator_params } => {
+							parameters = evaluator_params;
+						},
+						PatternEvalUpdate::Playstart{ playstart, playstart_offset } => {
+							// println!("playstart: {}, playstart_offset: {}", playstart, playstart_offset);
+							if playstart == 0.0 {
+								stop_default_voice(&mut pattern_playstart, &mut send_stopping_updates, &mut next_eval_params, &mut playback_queue, &mut parameters.user_parameters, events_tx);
+							} else {
+								// get current time in milliseconds as f64
+								last_playback_update = Instant::now();
+								playback_update_buffer.clear();
+								pattern_playstart = Some(instant_add_js_milliseconds(Instant::now(), playstart_offset));
+								next_eval_params = NextEvalParams::new(parameters.time, 0.0);
+							}
+						},
+						PatternEvalUpdate::Play { pattern_time } => {
 							last_playback_update = Instant::now();
 							playback_update_buffer.clear();
-							pattern_playstart = Some(instant_add_js_milliseconds(Instant::now(), playstart_offset));
-							next_eval_params = NextEvalParams::new(parameters.time, 0.0);
-						}
-					},
-					PatternEvalUpdate::Tracking { enabled } => {
-						if tracking_data_rx.is_none() {
-							eprintln!("error: tracking requested but no tracking data channel is connected (tracking was disabled)!");
-						}
-						enable_tracking = enabled;
-					},
+							parameters.time = pattern_time;
+							next_eval_params = NextEvalParams::new(pattern_time, 0.0);
+							pattern_playstart = Some(instant_add_js_milliseconds(Instant::now(), -pattern_time));
+						},
+						PatternEvalUpdate::Pause {} => {
+							pattern_playstart = None; // parameters.time is reused while stopped, freezing the pattern time
+						},
+						PatternEvalUpdate::Resume {} => {
+							if pattern_playstart.is_none() {
+								last_playback_update = Instant::now();
+								playback_update_buffer.clear();
+								// next_eval_params is still valid, since parameters.time continues from where it was paused
+								pattern_playstart = Some(instant_add_js_milliseconds(Instant::now(), -parameters.time));
+							}
+						},
+						PatternEvalUpdate::Seek { pattern_time } => {
+							parameters.time = pattern_time;
+							next_eval_params = NextEvalParams::new(pattern_time, 0.0);
+							if pattern_playstart.is_some() {
+								pattern_playstart = Some(instant_add_js_milliseconds(Instant::now(), -pattern_time));
+							}
+						},
+						PatternEvalUpdate::Stop {} => {
+							stop_default_voice(&mut pattern_playstart, &mut send_stopping_updates, &mut next_eval_params, &mut playback_queue, &mut parameters.user_parameters, events_tx);
+							playback_queue.clear(&mut parameters.user_parameters);
+							for (voice_id, _) in voices.drain() {
+								voice_scheduler.remove(voice_id);
+								send_event(events_tx, AdapticsEngineEvent::VoiceFinished { voice_id });
+							}
+							parameters.time = 0.0;
+						},
+						PatternEvalUpdate::PlayTacton { name } => {
+							let Some((file_name, pattern_json)) = tacton_library.get(&name) else {
+								break 'apply Err(format!("tacton '{name}' not found in the tacton library"));
+							};
+							pattern_eval = match PatternEvaluator::new_from_json_string(&pattern_json) {
+								Ok(new_pattern_eval) => new_pattern_eval,
+								Err(e) => break 'apply Err(format!("failed to parse tacton '{file_name}', keeping the current pattern: {e}")),
+							};
+							abort_queue_entry(&mut playback_queue, &mut parameters.user_parameters, events_tx);
+							on_pattern_loaded(&mut pattern_eval, &mut pattern_automation, &sidecar_automation, &mut user_parameter_smoother);
+							pattern_crossfade = None;
+							loaded_tacton = Some(file_name);
+							parameters.time = 0.0;
+							next_eval_params = NextEvalParams::default();
+							last_playback_update = Instant::now();
+							playback_update_buffer.clear();
+							pattern_playstart = Some(Instant::now());
+						},
+						PatternEvalUpdate::Tracking { enabled, hand } => {
+							if enabled && tracking_data_rx.is_none() {
+								break 'apply Err("tracking requested but no tracking data channel is connected (tracking was disabled)!".to_string());
+							}
+							enable_tracking = enabled;
+							if let Some(hand) = hand { tracked_hand = hand; }
+						},
+						PatternEvalUpdate::TrackingBindings { bindings } => {
+							if let Err(e) = bindings.iter().try_for_each(TrackingBinding::validate) { break 'apply Err(e.to_string()); }
+							tracking_binder.set_bindings(bindings);
+						},
+						PatternEvalUpdate::QueueEnqueue { entry } => {
+							if let Err(e) = playback_queue.enqueue(entry) { break 'apply Err(e); }
+						},
+						PatternEvalUpdate::QueueClear {} => {
+							if playback_queue.is_playing() {
+								stop_default_voice(&mut pattern_playstart, &mut send_stopping_updates, &mut next_eval_params, &mut playback_queue, &mut parameters.user_parameters, events_tx);
+							}
+							playback_queue.clear(&mut parameters.user_parameters);
+						},
+						PatternEvalUpdate::VoicePlay { voice_id, pattern_json, priority, user_parameters } => {
+							let voice_id = voice_id.unwrap_or_else(|| allocate_voice_id(&mut next_voice_id, |id| voices.contains_key(&id)));
+							if voices.contains_key(&voice_id) { break 'apply Err(format!("voice {voice_id} is already playing")); }
+							let new_pattern_eval = match PatternEvaluator::new_from_json_string(&pattern_json) {
+								Ok(new_pattern_eval) => new_pattern_eval,
+								Err(e) => break 'apply Err(format!("failed to parse pattern for voice {voice_id}: {e}")),
+							};
+							if voice_id == DEFAULT_VOICE_ID {
+								abort_queue_entry(&mut playback_queue, &mut parameters.user_parameters, events_tx);
+								pattern_eval = new_pattern_eval;
+								on_pattern_loaded(&mut pattern_eval, &mut pattern_automation, &sidecar_automation, &mut user_parameter_smoother);
+								pattern_crossfade = None;
+								loaded_tacton = None;
+								default_voice_priority = priority;
+								parameters.user_parameters.extend(user_parameters);
+								parameters.time = 0.0;
+								next_eval_params = NextEvalParams::default();
+								last_playback_update = Instant::now();
+								playback_update_buffer.clear();
+								pattern_playstart = Some(Instant::now());
+							} else {
+								voices.insert(voice_id, Voice::new(new_pattern_eval, Instant::now(), user_parameters, priority));
+								voice_scheduler.add(voice_id);
+							}
+							break 'apply Ok(AdapticsWSResponse::VoiceStarted { voice_id });
+						},
+						PatternEvalUpdate::VoiceStop { voice_id } => {
+							if voice_id == DEFAULT_VOICE_ID {
+								stop_default_voice(&mut pattern_playstart, &mut send_stopping_updates, &mut next_eval_params, &mut playback_queue, &mut parameters.user_parameters, events_tx);
+							} else if voices.remove(&voice_id).is_some() {
+								voice_scheduler.remove(voice_id);
+								send_event(events_tx, AdapticsEngineEvent::VoiceFinished { voice_id });
+							}
+						},
+						PatternEvalUpdate::VoiceUserParameters { voice_id, user_parameters } => {
+							if voice_id == DEFAULT_VOICE_ID {
+								parameters.user_parameters.extend(user_parameters);
+							} else if let Some(voice) = voices.get_mut(&voice_id) {
+								voice.update_user_parameters(user_parameters);
+							}
+						},
+						PatternEvalUpdate::UserParameterAutomation { automation } => {
+							sidecar_automation = automation;
+							pattern_eval.set_user_parameter_automation(merge_automation(&pattern_automation, &sidecar_automation));
+						},
 
-					PatternEvalUpdate::ParameterTime { time } => parameters.time = time,
-        			PatternEvalUpdate::UserParameters { user_parameters } => parameters.user_parameters = user_parameters,
-        			PatternEvalUpdate::GeoTransformMatrix { transform } => parameters.geometric_transform = transform,
-        			PatternEvalUpdate::UserParameter { name, value } => { parameters.user_parameters.insert(name, value); },
+						PatternEvalUpdate::ParameterTime { time } => parameters.time = time,
+	        			PatternEvalUpdate::UserParameters { user_parameters } => parameters.user_parameters = user_parameters,
+	        			PatternEvalUpdate::GeoTransformMatrix { transform } => parameters.geometric_transform = transform,
+	        			PatternEvalUpdate::UserParameter { name, value } => { parameters.user_parameters.insert(name, value); },
+					}
+					Ok(AdapticsWSResponse::Ack {})
+				};
+				if let Err(message) = &res {
+					send_error(events_tx, message.clone());
+				} else if let (Some(session_log), Some(update)) = (session_log, logged_update) {
+					session_log.log(SessionLogEntry::Update(update));
+				}
+				if let Some(reply_tx) = reply_tx {
+					reply_tx.send(res).ok(); // ignore send error (if the requester stopped waiting)
 				}
 			},
 			i if Some(i) == tracking_data_rx_idx => {
 				tracking_data = oper.recv(tracking_data_rx.as_ref().unwrap())?;
+				tracking_binder.apply(tracking_data.captured_at, tracking_data.tracked_hands(tracked_hand).first().copied(), &mut parameters.user_parameters, |name, value| {
+					// logged like the updates that set user parameters otherwise, so sessions can be analyzed the same way
+					if let Some(session_log) = session_log { session_log.log(SessionLogEntry::Update(PatternEvalUpdate::UserParameter { name: name.to_string(), value })); }
+				});
 			},
 			_ => unreachable!(),
 		};
@@ -0,0 +1,124 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use schemars::JsonSchema;
+use pattern_evaluator::{PatternEvaluator, UserParameters};
+use serde::{Deserialize, Serialize};
+use crate::threads::common::{MilSec, instant_add_js_milliseconds};
+
+
+/// A tacton to be played by the playback queue, see [`crate::PatternEvalUpdate::QueueEnqueue`]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct QueueEntry {
+	/// Identifies the entry in [`crate::AdapticsEngineEvent`]s
+	pub id: String,
+	/// The tacton/pattern in JSON format (see [`pattern_evaluator::MidAirHapticsAnimationFileFormat`])
+	pub pattern_json: String,
+	/// Pause after this entry has finished, before the next entry is started
+	#[serde(default)]
+	pub gap_ms: MilSec,
+	/// Number of times the pattern is played back-to-back. 0 loops until the queue is cleared.
+	#[serde(default = "default_loops")]
+	pub loops: u32,
+	/// User parameters set while the entry is playing, the previous values are restored when it ends
+	#[serde(default)]
+	pub user_parameters: pattern_evaluator::UserParameters,
+}
+fn default_loops() -> u32 { 1 }
+
+pub(super) enum QueueEntryEnd {
+	/// The entry should be played again
+	Loop,
+	/// The entry has finished, the next entry may start after `gap_ms`
+	Finished(QueueEntry),
+}
+
+struct CurrentEntry {
+	entry: QueueEntry,
+	plays: u32,
+	/// Values of the user parameters overridden by the entry before it started (`None` if they were not set)
+	replaced_user_parameters: HashMap<String, Option<f64>>,
+}
+impl CurrentEntry {
+	/// Restores the user parameters overridden by the entry, unless they were changed while it played
+	fn restore_user_parameters(self, user_parameters: &mut UserParameters) -> QueueEntry {
+		for (name, previous) in self.replaced_user_parameters {
+			if user_parameters.get(&name) != self.entry.user_parameters.get(&name) { continue; }
+			match previous {
+				Some(value) => { user_parameters.insert(name, value); },
+				None => { user_parameters.remove(&name); },
+			}
+		}
+		self.entry
+	}
+}
+
+#[derive(Default)]
+pub(super) struct PlaybackQueue {
+	entries: VecDeque<QueueEntry>,
+	current: Option<CurrentEntry>,
+	next_start: Option<Instant>,
+}
+impl PlaybackQueue {
+	/// Adds `entry` to the end of the queue.
+	/// Entries that would loop forever without playing anything (`loops` is 0 and the pattern has no length) are rejected.
+	pub fn enqueue(&mut self, entry: QueueEntry) -> Result<(), String> {
+		if entry.loops == 0 {
+			let pattern_eval = PatternEvaluator::new_from_json_string(&entry.pattern_json).map_err(|e| format!("failed to parse pattern of queue entry '{}': {e}", entry.id))?;
+			if pattern_eval.end_time() <= 0.0 {
+				return Err(format!("queue entry '{}' loops forever, but its pattern has no length", entry.id));
+			}
+		}
+		self.entries.push_back(entry);
+		Ok(())
+	}
+
+	/// Removes all entries, returns the entry that was playing (if any) after restoring the user parameters it overrode
+	pub fn clear(&mut self, user_parameters: &mut UserParameters) -> Option<QueueEntry> {
+		self.entries.clear();
+		self.next_start = None;
+		self.abort_current(user_parameters)
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.current.is_some()
+	}
+
+	/// The entry that is currently playing (if any)
+	pub fn current(&self) -> Option<&QueueEntry> {
+		self.current.as_ref().map(|current| &current.entry)
+	}
+
+	/// Number of entries waiting to be played
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// If nothing is playing and the gap after the last entry has passed, takes the next entry, applies its user parameters and returns it
+	pub fn start_next(&mut self, now: Instant, user_parameters: &mut UserParameters) -> Option<&QueueEntry> {
+		if self.current.is_some() || self.next_start.is_some_and(|next_start| now < next_start) { return None; }
+		let entry = self.entries.pop_front()?;
+		self.next_start = None;
+		let replaced_user_parameters = entry.user_parameters.iter().map(|(name, value)| (name.clone(), user_parameters.insert(name.clone(), *value))).collect();
+		self.current = Some(CurrentEntry { entry, plays: 1, replaced_user_parameters });
+		self.current()
+	}
+
+	/// Stops the current entry without looping it (e.g. if its pattern failed to parse, or the default voice was stopped).
+	/// The user parameters it overrode are restored.
+	pub fn abort_current(&mut self, user_parameters: &mut UserParameters) -> Option<QueueEntry> {
+		self.current.take().map(|current| current.restore_user_parameters(user_parameters))
+	}
+
+	/// Called when the pattern of the current entry reached its end at `now`.
+	/// The user parameters it overrode are restored once it has finished.
+	pub fn end_current(&mut self, now: Instant, user_parameters: &mut UserParameters) -> Option<QueueEntryEnd> {
+		let current = self.current.as_mut()?;
+		if current.entry.loops == 0 || current.plays < current.entry.loops {
+			current.plays += 1;
+			return Some(QueueEntryEnd::Loop);
+		}
+		let entry = self.current.take()?.restore_user_parameters(user_parameters);
+		self.next_start = Some(instant_add_js_milliseconds(now, entry.gap_ms));
+		Some(QueueEntryEnd::Finished(entry))
+	}
+}
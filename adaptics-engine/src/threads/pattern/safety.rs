@@ -0,0 +1,89 @@
+use pattern_evaluator::{BrushAtAnimLocalTime, MAHCoordsConst};
+use serde::{Deserialize, Serialize};
+
+use crate::util::AdapticsError;
+
+/// Limits applied to every control point (after the tracking offset) before it is sent to the device, see [`crate::EngineConfig::safety`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyConfig {
+	/// Intensities above this are reduced to it (0.0 - 1.0)
+	pub max_intensity: f64,
+	/// Control points outside of this box are muted
+	pub bounds: Option<SafetyBounds>,
+}
+impl Default for SafetyConfig {
+	fn default() -> Self {
+		Self { max_intensity: 1.0, bounds: None }
+	}
+}
+
+/// Box in the haptic coordinate system (in mm), inclusive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SafetyBounds {
+	pub min: MAHCoordsConst,
+	pub max: MAHCoordsConst,
+}
+impl SafetyBounds {
+	fn contains(&self, coords: &MAHCoordsConst) -> bool {
+		(self.min.x..=self.max.x).contains(&coords.x)
+			&& (self.min.y..=self.max.y).contains(&coords.y)
+			&& (self.min.z..=self.max.z).contains(&coords.z)
+	}
+}
+
+impl SafetyConfig {
+	pub(crate) fn validate(&self) -> Result<(), AdapticsError> {
+		if !(0.0..=1.0).contains(&self.max_intensity) {
+			return Err(AdapticsError::new(&format!("safety.max_intensity must be between 0 and 1, got {}", self.max_intensity)));
+		}
+		if let Some(SafetyBounds { min, max }) = &self.bounds {
+			if min.x > max.x || min.y > max.y || min.z > max.z {
+				return Err(AdapticsError::new("safety.bounds.min must be less than or equal to safety.bounds.max"));
+			}
+		}
+		Ok(())
+	}
+
+	pub(crate) fn apply(&self, evals: &mut [BrushAtAnimLocalTime]) {
+		for eval in evals {
+			let cp = &mut eval.ul_control_point;
+			if self.bounds.as_ref().is_some_and(|bounds| !bounds.contains(&cp.coords)) {
+				cp.intensity = 0.0;
+			} else {
+				cp.intensity = cp.intensity.min(self.max_intensity);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn eval_at(x: f64, intensity: f64) -> BrushAtAnimLocalTime {
+		BrushAtAnimLocalTime {
+			ul_control_point: pattern_evaluator::UltraleapControlPoint { coords: MAHCoordsConst { x, y: 0.0, z: 200.0 }, intensity },
+			pattern_time: 0.0,
+			stop: false,
+			next_eval_params: pattern_evaluator::NextEvalParams::default(),
+		}
+	}
+
+	#[test]
+	fn test_safety_limits() {
+		let safety = SafetyConfig {
+			max_intensity: 0.5,
+			bounds: Some(SafetyBounds { min: MAHCoordsConst { x: -50.0, y: -50.0, z: 100.0 }, max: MAHCoordsConst { x: 50.0, y: 50.0, z: 300.0 } }),
+		};
+		safety.validate().unwrap();
+
+		let mut evals = vec![eval_at(0.0, 1.0), eval_at(0.0, 0.25), eval_at(60.0, 1.0)];
+		safety.apply(&mut evals);
+		let intensities: Vec<_> = evals.iter().map(|e| e.ul_control_point.intensity).collect();
+		assert_eq!(intensities, vec![0.5, 0.25, 0.0]);
+
+		assert!(SafetyConfig { max_intensity: 1.5, bounds: None }.validate().is_err());
+	}
+}
@@ -0,0 +1,157 @@
+use std::time::Instant;
+use pattern_evaluator::{MAHCoordsConst, UserParameters};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::threads::tracking::TrackingFrameHand;
+use crate::util::AdapticsError;
+
+/// A quantity measured on the tracked hand, in the haptic coordinate system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingQuantity {
+	/// Height of the palm center above the haptic device (in mm)
+	PalmHeight,
+	/// Distance between the tips of the thumb and the index finger (in mm)
+	PinchDistance,
+	/// How far the fingers are stretched out, from 0 (fist) to 1 (flat hand)
+	GrabOpenness,
+	/// Speed of the palm center (in mm/s)
+	HandSpeed,
+}
+
+/// Sets the user parameter `parameter` from a quantity of the tracked hand on every tracking frame, see [`crate::PlaybackConfig::tracking_bindings`].
+///
+/// e.g. `{ parameter = "intensity", quantity = "palm_height", input = [300, 100] }` goes from 0 to 1 while the palm is lowered from 300mm to 100mm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TrackingBinding {
+	pub parameter: String,
+	pub quantity: TrackingQuantity,
+	/// The range of the quantity that is mapped linearly to `output`, values outside of it are clamped.
+	/// `input[0]` may be greater than `input[1]` to invert the mapping.
+	pub input: [f64; 2],
+	/// The range of the user parameter, `[0, 1]` if not given
+	#[serde(default = "default_output")]
+	pub output: [f64; 2],
+}
+
+fn default_output() -> [f64; 2] { [0.0, 1.0] }
+
+impl TrackingBinding {
+	pub(crate) fn validate(&self) -> Result<(), AdapticsError> {
+		if self.parameter.is_empty() { return Err(AdapticsError::new("tracking binding parameter must not be empty")); }
+		if self.input.iter().chain(&self.output).any(|v| !v.is_finite()) { return Err(AdapticsError::new(&format!("tracking binding '{}' has a non-finite range", self.parameter))); }
+		if (self.input[1] - self.input[0]).abs() < f64::EPSILON { return Err(AdapticsError::new(&format!("tracking binding '{}' has an empty input range", self.parameter))); }
+		Ok(())
+	}
+
+	/// Maps `value` from `input` to `output`
+	fn map(&self, value: f64) -> f64 {
+		let [in_start, in_end] = self.input;
+		let [out_start, out_end] = self.output;
+		let f = ((value - in_start) / (in_end - in_start)).clamp(0.0, 1.0);
+		out_start + (out_end - out_start) * f
+	}
+}
+
+fn distance(a: &MAHCoordsConst, b: &MAHCoordsConst) -> f64 {
+	((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+/// Mean of how far each finger's distal bone points along the palm direction, mapped from [-1, 1] to [0, 1]
+fn grab_openness(hand: &TrackingFrameHand) -> f64 {
+	let dir = &hand.palm.direction;
+	let fingers = &hand.digits[1..]; // without the thumb
+	#[allow(clippy::cast_precision_loss)]
+	let mean_cos = fingers.iter().map(|digit| {
+		let bone = &digit.bones[3];
+		let length = distance(&bone.start, &bone.end);
+		if length <= 0.0 { return 1.0; }
+		((bone.end.x - bone.start.x) * dir.x + (bone.end.y - bone.start.y) * dir.y + (bone.end.z - bone.start.z) * dir.z) / length
+	}).sum::<f64>() / fingers.len() as f64;
+	f64::midpoint(mean_cos, 1.0).clamp(0.0, 1.0)
+}
+
+/// Applies [`TrackingBinding`]s to the user parameters whenever a tracking frame arrives
+pub(super) struct TrackingBinder {
+	bindings: Vec<TrackingBinding>,
+	last_palm: Option<(Instant, MAHCoordsConst)>,
+}
+impl TrackingBinder {
+	pub fn new(bindings: Vec<TrackingBinding>) -> Self {
+		Self { bindings, last_palm: None }
+	}
+
+	pub fn set_bindings(&mut self, bindings: Vec<TrackingBinding>) {
+		self.bindings = bindings;
+	}
+
+	/// Sets the bound user parameters from `hand`, captured at `captured_at`, and calls `on_change` for each parameter whose value changed.
+	/// Parameters keep their values while no hand is tracked.
+	pub fn apply(&mut self, captured_at: Instant, hand: Option<&TrackingFrameHand>, user_parameters: &mut UserParameters, mut on_change: impl FnMut(&str, f64)) {
+		let Some(hand) = hand else { self.last_palm = None; return; };
+		let palm = &hand.palm.position;
+		let speed = self.last_palm.as_ref().and_then(|(last_instant, last_palm)| {
+			let dt = captured_at.saturating_duration_since(*last_instant).as_secs_f64();
+			(dt > 0.0).then(|| distance(palm, last_palm) / dt)
+		});
+		self.last_palm = Some((captured_at, palm.clone()));
+
+		for binding in &self.bindings {
+			let value = match binding.quantity {
+				TrackingQuantity::PalmHeight => palm.z,
+				TrackingQuantity::PinchDistance => distance(&hand.digits[0].bones[3].end, &hand.digits[1].bones[3].end),
+				TrackingQuantity::GrabOpenness => grab_openness(hand),
+				TrackingQuantity::HandSpeed => { let Some(speed) = speed else { continue; }; speed },
+			};
+			let value = binding.map(value);
+			if user_parameters.insert(binding.parameter.clone(), value) != Some(value) {
+				on_change(&binding.parameter, value);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use super::*;
+	use crate::{SyntheticMotion, threads::tracking::synthetic::SyntheticTrackingSource};
+
+	fn binding(parameter: &str, quantity: TrackingQuantity, input: [f64; 2]) -> TrackingBinding {
+		TrackingBinding { parameter: parameter.to_string(), quantity, input, output: default_output() }
+	}
+
+	#[test]
+	fn test_tracking_bindings() {
+		let source = SyntheticTrackingSource::new(SyntheticMotion::ApproachRetreat { near: MAHCoordsConst { x: 0.0, y: 0.0, z: 100.0 }, far: MAHCoordsConst { x: 0.0, y: 0.0, z: 300.0 }, period: 4.0 }, 90.0);
+		let mut binder = TrackingBinder::new(vec![
+			binding("height", TrackingQuantity::PalmHeight, [300.0, 100.0]),
+			binding("open", TrackingQuantity::GrabOpenness, [0.0, 1.0]),
+			TrackingBinding { output: [10.0, 20.0], ..binding("pinch", TrackingQuantity::PinchDistance, [0.0, 100.0]) },
+			binding("speed", TrackingQuantity::HandSpeed, [0.0, 1000.0]),
+		]);
+		let mut user_parameters = UserParameters::new();
+		let start = Instant::now();
+		let mut changed = Vec::new();
+		binder.apply(start, source.frame_at(0.0).hand.as_ref(), &mut user_parameters, |name, _| changed.push(name.to_string()));
+		assert_eq!(changed, vec!["height", "open", "pinch"]);
+		assert!((user_parameters["height"] - 1.0).abs() < 1e-9, "palm at 100mm");
+		assert!(user_parameters["open"] > 0.99, "flat hand");
+		let pinch = distance(&MAHCoordsConst { x: -80.0, y: 52.0, z: 0.0 }, &MAHCoordsConst { x: -29.0, y: 120.0, z: 0.0 });
+		assert!((user_parameters["pinch"] - (10.0 + pinch / 10.0)).abs() < 1e-9);
+		assert!(!user_parameters.contains_key("speed"), "no speed before the second frame");
+
+		changed.clear();
+		binder.apply(start + Duration::from_secs(1), source.frame_at(1.0).hand.as_ref(), &mut user_parameters, |name, _| changed.push(name.to_string()));
+		assert_eq!(changed, vec!["height", "speed"], "only changed parameters are reported");
+		assert!((user_parameters["height"] - 0.5).abs() < 1e-9, "palm at 200mm");
+		assert!((user_parameters["speed"] - 0.1).abs() < 1e-9, "moved 100mm in 1s");
+
+		binder.apply(start + Duration::from_secs(2), None, &mut user_parameters, |name, _| panic!("{name} changed without a hand"));
+		assert!((user_parameters["height"] - 0.5).abs() < 1e-9, "kept while no hand is tracked");
+
+		assert!(binding("height", TrackingQuantity::PalmHeight, [100.0, 100.0]).validate().is_err());
+	}
+}
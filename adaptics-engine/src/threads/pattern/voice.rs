@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use pattern_evaluator::{PatternEvaluator, PatternEvaluatorParameters, BrushAtAnimLocalTime, NextEvalParams, UserParameters};
+
+
+/// Identifies a voice. Voice 0 is the default voice, which is controlled by all commands that do not take a `voice_id`.
+pub type VoiceId = u32;
+pub const DEFAULT_VOICE_ID: VoiceId = 0;
+
+/// An additional tacton playing concurrently with the default voice
+pub(super) struct Voice {
+	pattern_eval: PatternEvaluator,
+	next_eval_params: NextEvalParams,
+	playstart: Instant,
+	parameters: PatternEvaluatorParameters,
+	pub priority: u32,
+}
+impl Voice {
+	pub fn new(pattern_eval: PatternEvaluator, playstart: Instant, user_parameters: UserParameters, priority: u32) -> Self {
+		Self {
+			pattern_eval,
+			next_eval_params: NextEvalParams::default(),
+			playstart,
+			parameters: PatternEvaluatorParameters { time: 0.0, user_parameters, geometric_transform: Default::default() },
+			priority,
+		}
+	}
+
+	pub fn update_user_parameters(&mut self, user_parameters: UserParameters) {
+		self.parameters.user_parameters.extend(user_parameters);
+	}
+
+	/// Returns the evaluation at `time`, and whether the voice has reached the end of its pattern
+	pub fn eval(&mut self, time: Instant) -> (BrushAtAnimLocalTime, bool) {
+		#[allow(clippy::cast_precision_loss)]
+		{ self.parameters.time = time.saturating_duration_since(self.playstart).as_nanos() as f64 / 1e6; }
+		let eval = self.pattern_eval.eval_brush_at_anim_local_time(&self.parameters, &self.next_eval_params);
+		self.next_eval_params = eval.next_eval_params.clone();
+		let ended = eval.stop || eval.pattern_time >= self.pattern_eval.end_time();
+		(eval, ended)
+	}
+}
+
+/// Returns the id for a voice started without one: the first id from `next_id` on that is neither the default voice nor `playing`.
+/// `next_id` is advanced past it, so the ids of stopped voices are not reused right away.
+pub(super) fn allocate_voice_id(next_id: &mut VoiceId, playing: impl Fn(VoiceId) -> bool) -> VoiceId {
+	loop {
+		let id = *next_id;
+		*next_id = next_id.wrapping_add(1);
+		if id != DEFAULT_VOICE_ID && !playing(id) { return id; }
+	}
+}
+
+/// Time-multiplexes the single focal point between the playing voices using smooth weighted round-robin,
+/// so each voice gets a share of the device samples proportional to its priority.
+#[derive(Default)]
+pub(super) struct VoiceScheduler {
+	current_weights: HashMap<VoiceId, i64>,
+}
+impl VoiceScheduler {
+	/// Starts voice `id` without accumulated weight. Call when a voice is (re)started.
+	pub fn add(&mut self, id: VoiceId) {
+		self.current_weights.insert(id, 0);
+	}
+
+	/// Forgets the weight of voice `id`. Call when a voice is removed.
+	pub fn remove(&mut self, id: VoiceId) {
+		self.current_weights.remove(&id);
+	}
+
+	/// `candidates` are (voice id, priority) of all voices that are currently playing
+	pub fn select(&mut self, candidates: impl Iterator<Item = (VoiceId, u32)>) -> Option<VoiceId> {
+		let mut total = 0;
+		let mut selected: Option<(VoiceId, i64)> = None;
+		for (id, priority) in candidates {
+			let priority = i64::from(priority.max(1));
+			total += priority;
+			let current_weight = self.current_weights.entry(id).or_insert(0);
+			*current_weight += priority;
+			match selected {
+				Some((_, selected_weight)) if *current_weight <= selected_weight => {},
+				_ => selected = Some((id, *current_weight)),
+			}
+		}
+		let (id, _) = selected?;
+		if let Some(current_weight) = self.current_weights.get_mut(&id) { *current_weight -= total; }
+		Some(id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_weighted_round_robin() {
+		let mut scheduler = VoiceScheduler::default();
+		let candidates = [(0, 1), (3, 2)];
+		let selected: Vec<_> = (0..6).map(|_| scheduler.select(candidates.iter().copied()).unwrap()).collect();
+		assert_eq!(selected.iter().filter(|id| **id == 3).count(), 4);
+		assert_eq!(selected.iter().filter(|id| **id == 0).count(), 2);
+		assert_ne!(selected[0], selected[1], "voices should be interleaved");
+
+		assert_eq!(scheduler.select(std::iter::empty()), None);
+	}
+
+	#[test]
+	fn test_allocate_voice_id() {
+		let mut next_id = 1;
+		assert_eq!(allocate_voice_id(&mut next_id, |id| id == 2), 1);
+		assert_eq!(allocate_voice_id(&mut next_id, |id| id == 2), 3, "voice 2 is playing");
+		assert_eq!(allocate_voice_id(&mut next_id, |_| false), 4, "ids are not reused right away");
+
+		let mut next_id = VoiceId::MAX;
+		assert_eq!(allocate_voice_id(&mut next_id, |_| false), VoiceId::MAX);
+		assert_eq!(allocate_voice_id(&mut next_id, |_| false), 1, "the default voice is skipped");
+	}
+}
@@ -1,16 +1,49 @@
 mod glovedriver;
-use std::time::{Duration, Instant};
-
 use pattern_evaluator::BrushAtAnimLocalTime;
+use serde::{Deserialize, Serialize};
 
-use crate::{threads::pattern::playback::PatternEvalCall, util::AdapticsError, DEBUG_LOG_LAG_EVENTS};
+use crate::util::AdapticsError;
+use super::output::{OutputBackend, OutputCapabilities, OutputKind, StreamingContext};
 
-pub const USE_THREAD_SLEEP: Option<Duration> = Some(Duration::from_micros(1000)); // spin_sleeper still needs some buffer time (it shouldnt need any). idk if it overtrusts the os sleep, or its some other slowdown?
+pub use glovedriver::{LRAPlacement, LRAPositions, DEFAULT_MAX_DIST};
 
 pub const SAMPLE_RATE: u64 = 10000; // 10khz
 pub const CALLBACK_RATE: f64 = 100.0; // 100hz
-#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-pub const SAMPLES_PER_CALLBACK: usize = (SAMPLE_RATE as f64 / CALLBACK_RATE) as usize;
+
+/// Configuration of the vibrotactile grid device, see [`crate::EngineConfig::glove`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GloveConfig {
+	/// Device samples per second
+	pub sample_rate: u64,
+	/// Packets sent to the device per second
+	pub callback_rate: f64,
+	/// Distance from an actuator (in mm) at which its amplitude reaches 0
+	pub max_dist: f64,
+	/// Position of the actuator on each channel of the device, one per channel.
+	/// Defaults to the layout of the prototype glove (left hand, palm down).
+	pub layout: Option<Vec<LRAPlacement>>,
+}
+impl Default for GloveConfig {
+	fn default() -> Self {
+		Self { sample_rate: SAMPLE_RATE, callback_rate: CALLBACK_RATE, max_dist: DEFAULT_MAX_DIST, layout: None }
+	}
+}
+impl GloveConfig {
+	fn lra_layout(&self) -> Result<glovedriver::LRALayout, AdapticsError> {
+		match &self.layout {
+			Some(placements) => LRAPlacement::layout(placements),
+			None => Ok(glovedriver::DEFAULT_LRA_LAYOUT),
+		}
+	}
+
+	pub(crate) fn validate(&self) -> Result<(), AdapticsError> {
+		if self.sample_rate == 0 || self.callback_rate <= 0.0 { return Err(AdapticsError::new("glove.sample_rate and glove.callback_rate must be greater than 0")); }
+		if self.max_dist <= 0.0 { return Err(AdapticsError::new("glove.max_dist must be greater than 0")); }
+		self.lra_layout()?;
+		Ok(())
+	}
+}
 
 pub enum DeviceType {
 	SerialPort(String),
@@ -22,72 +55,49 @@ pub fn get_possible_serial_ports() -> std::io::Result<Vec<serialport::SerialPort
 	glovedriver::GloveDriver::get_possible_serial_ports()
 }
 
-pub fn start_streaming_emitter(
-	device_type: &DeviceType,
-	patteval_call_tx: &crossbeam_channel::Sender<PatternEvalCall>,
-	patteval_return_rx: &crossbeam_channel::Receiver<Vec<BrushAtAnimLocalTime>>,
-	end_streaming_rx: &crossbeam_channel::Receiver<()>,
-) -> Result<(), AdapticsError> {
-
-	let mut gd = match device_type {
-		DeviceType::SerialPort(port) => glovedriver::GloveDriver::new_for_serial_port(port, glovedriver::DEFAULT_LRA_LAYOUT)?,
-		DeviceType::Mock => glovedriver::GloveDriver::new_mock(glovedriver::DEFAULT_LRA_LAYOUT),
-		DeviceType::Auto => glovedriver::GloveDriver::new_with_auto_serial_port(glovedriver::DEFAULT_LRA_LAYOUT)?,
-	};
-
-	let device_tick_dur = Duration::from_nanos(1_000_000_000/SAMPLE_RATE);
-	let ecallback_tick_dur = Duration::from_secs_f64(1.0/CALLBACK_RATE);
-	let deadline_offset = ecallback_tick_dur * 1;
-	let mut last_tick = Instant::now();
-
-	let spin_sleeper = spin_sleep::SpinSleeper::default();
-
-	assert!(device_tick_dur.as_secs_f64() > 0.0, "device_tick_dur must be > 0");
-	loop {
-		if end_streaming_rx.try_recv().is_ok() {
-			break;
-		}
-
-		let next_tick_at = last_tick + ecallback_tick_dur;
-		// if let Some(bwt) = USE_THREAD_SLEEP { std::thread::sleep(next_tick_at - Instant::now() - Duration::from_micros(bwt)); } // supports windows high resolution sleep since rust 1.75
-		// spin_sleeper.sleep(next_tick_at - Instant::now()); // not accurate enough by itself on windows
-		let sleep_time = next_tick_at.saturating_duration_since(Instant::now());
-		let curr_time = if sleep_time.is_zero() { Instant::now() } else { // if zero we drop a (partial) "frame", so we emit now and then continue with normal intervals, even if we could actually catch up.
-			if let Some(bwt) = USE_THREAD_SLEEP { if sleep_time > bwt { spin_sleeper.sleep(sleep_time.saturating_sub(bwt)); } } // shouldnt need bwt but it does
-			while next_tick_at > Instant::now() {} // busy wait remaining time
-
-			let curr_time = Instant::now();
-			let elapsed = curr_time - last_tick;
-			if DEBUG_LOG_LAG_EVENTS && elapsed > ecallback_tick_dur + Duration::from_millis(1) { println!("[WARN] long sleep (elapsed > ecallback_tick_dur): {elapsed:?} > {ecallback_tick_dur:?}"); }
-			curr_time
-		};
-		last_tick = curr_time; // i need to redo this whole thing at some point, probably use media timers or smth anyway
-
-		let deadline_time = curr_time + deadline_offset;
-
-		let mut time_arr_instants = Vec::with_capacity(SAMPLES_PER_CALLBACK + 2);
-		let mut future_device_tick_instant = deadline_time;
-		while future_device_tick_instant < (deadline_time + ecallback_tick_dur) {
-			time_arr_instants.push(future_device_tick_instant);
-			future_device_tick_instant += device_tick_dur;
-		}
+/// Alpha: vibrotactile grid device (e.g. a vest or glove) connected over a serial port
+pub struct GloveOutput {
+	device_type: DeviceType,
+	config: GloveConfig,
+	driver: Option<glovedriver::GloveDriver>,
+}
+impl GloveOutput {
+	/// The device is connected when streaming starts
+	#[must_use]
+	pub fn new(device_type: DeviceType) -> Self {
+		Self { device_type, config: GloveConfig::default(), driver: None }
+	}
 
-		if patteval_call_tx.send(PatternEvalCall::EvalBatch{ time_arr_instants }).is_ok() {
-			let eval_arr = patteval_return_rx.recv()?;
-			gd.apply_batch(&eval_arr)?;
-		} else {
-			// patt eval thread exited (or panicked),
-			// end_streaming_rx will be called by main thread, could exit here anyway
-			break; // not sure if I want to do this or just loop until end_streaming_rx
-		}
+	/// Uses the rates and layout of `config` instead of the prototype glove's
+	#[must_use]
+	pub fn with_config(mut self, config: GloveConfig) -> Self {
+		self.config = config;
+		self
+	}
+}
+impl OutputBackend for GloveOutput {
+	fn sample_rate(&self) -> u64 { self.config.sample_rate }
+	fn callback_rate(&self) -> f64 { self.config.callback_rate }
+	fn capabilities(&self) -> OutputCapabilities {
+		#[allow(clippy::cast_possible_truncation)]
+		OutputCapabilities { name: "vib-grid".to_string(), kind: OutputKind::VibrotactileGrid, num_actuators: Some(glovedriver::NUM_DRIVERS as u32) }
+	}
 
-		// both are needed because durations are always positive and subtraction saturates
-		let deadline_missed_by = deadline_time.elapsed();
-		if deadline_missed_by > Duration::from_micros(500) {
-			eprintln!("[WARN] GloveDriver.apply_batch missed deadline by {deadline_missed_by:?}");
-		}
+	fn start(&mut self) -> Result<(), AdapticsError> {
+		let lra_layout = self.config.lra_layout()?;
+		let max_dist = self.config.max_dist;
+		self.driver = Some(match &self.device_type {
+			DeviceType::SerialPort(port) => glovedriver::GloveDriver::new_for_serial_port(port, lra_layout, max_dist)?,
+			DeviceType::Mock => glovedriver::GloveDriver::new_mock(lra_layout, max_dist),
+			DeviceType::Auto => glovedriver::GloveDriver::new_with_auto_serial_port(lra_layout, max_dist)?,
+		});
+		Ok(())
 	}
 
-	println!("mock streaming exiting...");
-	Ok(())
-}
\ No newline at end of file
+	fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		let gd = self.driver.as_mut().ok_or(AdapticsError::new("GloveOutput.emit_batch called before start"))?;
+		gd.apply_batch(evals)?;
+		if let Some(rtt) = gd.last_rtt() { ctx.report_round_trip_time(rtt); }
+		Ok(())
+	}
+}
@@ -2,22 +2,25 @@
 use std::{cell::Cell, time::{Duration, Instant}};
 
 use pattern_evaluator::MAHCoordsConst;
+use serde::{Deserialize, Serialize};
 use serialport::{self, SerialPort};
 
-use crate::{AdapticsError, DEBUG_LOG_SERIAL_RTT};
+use crate::AdapticsError;
 
-const NUM_DRIVERS: usize = 16;
+pub(super) const NUM_DRIVERS: usize = 16;
 const COBS_DELIM: u8 = 0x88; // using unlikely byte as delim
 const HEADER_LEN: usize = 1; // 1 byte COBS overhead
 const FOOTER_LEN: usize = 1; // 1 byte delim
 const PACKET_LEN: usize = HEADER_LEN + NUM_DRIVERS + FOOTER_LEN;
 const ACK_PACKET: &[u8] = b"OKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOKOK\r\n";
 
-const MAX_DIST: f64 = 30.0; // mm, distance from LRA where amp is 0%
+pub const DEFAULT_MAX_DIST: f64 = 30.0; // mm, distance from LRA where amp is 0%
 
 const MAX_NUM_TIMEOUTS_BEFORE_RESET: usize = 4;
 
-type LRALayout = [MAHCoordsConst; NUM_DRIVERS];
+pub type LRALayout = [MAHCoordsConst; NUM_DRIVERS];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LRAPositions {
 	PalmTopCenter,
 	PalmTopLeft,
@@ -92,6 +95,22 @@ impl From<LRAPositions> for MAHCoordsConst {
 		pos.get_coords()
 	}
 }
+/// Position of one actuator in a configured layout, either a named position on the hand (e.g. `"palm_top_center"`) or coordinates in mm (`{ x, y, z }`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LRAPlacement {
+	Named(LRAPositions),
+	Coords(MAHCoordsConst),
+}
+impl LRAPlacement {
+	pub fn layout(placements: &[LRAPlacement]) -> Result<LRALayout, AdapticsError> {
+		let coords: Vec<MAHCoordsConst> = placements.iter().map(|p| match p {
+			LRAPlacement::Named(pos) => pos.get_coords(),
+			LRAPlacement::Coords(coords) => coords.clone(),
+		}).collect();
+		coords.try_into().map_err(|coords: Vec<_>| AdapticsError::new(&format!("the glove layout must have one position per driver ({NUM_DRIVERS}), got {}", coords.len())))
+	}
+}
 pub const DEFAULT_LRA_LAYOUT: LRALayout = LRAPositions::pos_to_coords(&[ //left hand palm down
 	//CN1
 	LRAPositions::PalmTopCenter,
@@ -118,11 +137,11 @@ pub const DEFAULT_LRA_LAYOUT: LRALayout = LRAPositions::pos_to_coords(&[ //left
 	LRAPositions::PalmBottomRight,
 ]);
 
-pub trait IoPort: std::io::Write + std::io::Read {
+pub trait IoPort: std::io::Write + std::io::Read + Send {
 	fn clear_rx_buf(&mut self) -> std::io::Result<()>;
 	fn reset_device(&mut self) -> std::io::Result<()>;
 }
-impl<T: AsMut<dyn SerialPort> + std::io::Write + std::io::Read> IoPort for T {
+impl<T: AsMut<dyn SerialPort> + std::io::Write + std::io::Read + Send> IoPort for T {
 	fn clear_rx_buf(&mut self) -> std::io::Result<()> {
 		Ok(self.as_mut().clear(serialport::ClearBuffer::Input)?)
 	}
@@ -184,7 +203,9 @@ pub struct GloveDriver {
 	tx_buf: Vec<u8>,
 	rx_buf: Vec<u8>,
 	lra_layout: LRALayout,
+	max_dist: f64,
 	timeout_count: usize,
+	last_rtt: Option<Duration>,
 }
 pub struct DriverAmplitudes([u8; NUM_DRIVERS]);
 impl DriverAmplitudes {
@@ -198,19 +219,21 @@ impl GloveDriver {
 		Ok(serialport::available_ports()?)
 	}
 
-	pub fn new(io_port: Box<dyn IoPort>, lra_layout: LRALayout) -> Self {
+	pub fn new(io_port: Box<dyn IoPort>, lra_layout: LRALayout, max_dist: f64) -> Self {
 		GloveDriver {
 			io_port,
 			tx_buf: Vec::with_capacity(PACKET_LEN),
 			rx_buf: vec![0; 256],
 			lra_layout,
+			max_dist,
 			timeout_count: 0,
+			last_rtt: None,
 		}
 	}
-	pub fn new_mock(lra_layout: LRALayout) -> Self {
-		GloveDriver::new(Box::new(MockIO::default()), lra_layout)
+	pub fn new_mock(lra_layout: LRALayout, max_dist: f64) -> Self {
+		GloveDriver::new(Box::new(MockIO::default()), lra_layout, max_dist)
 	}
-	pub fn new_for_serial_port(port: &str, lra_layout: LRALayout) -> std::io::Result<Self> {
+	pub fn new_for_serial_port(port: &str, lra_layout: LRALayout, max_dist: f64) -> std::io::Result<Self> {
 		let mut s_port = serialport::new(port, 115_200)
 			.timeout(Duration::from_millis(100))
 			.baud_rate(921_600)
@@ -218,14 +241,14 @@ impl GloveDriver {
 		s_port.write_data_terminal_ready(true)?;
 		s_port.write_request_to_send(true)?;
 		let io_port: Box<dyn IoPort> = Box::new(s_port);
-		Ok(GloveDriver::new(io_port, lra_layout))
+		Ok(GloveDriver::new(io_port, lra_layout, max_dist))
 	}
-	pub fn new_with_auto_serial_port(lra_layout: LRALayout) -> std::io::Result<Self> {
+	pub fn new_with_auto_serial_port(lra_layout: LRALayout, max_dist: f64) -> std::io::Result<Self> {
 		let ports = serialport::available_ports()?;
 		match ports.iter().find(|p| matches!(p.port_type, serialport::SerialPortType::UsbPort(serialport::UsbPortInfo { vid: 4292, pid: 60000, .. }))) { // Silicon Labs CP210x USB to UART Bridge
 			Some(p) => {
 				println!("[INFO] Auto-detected serial port: {p:?}");
-				GloveDriver::new_for_serial_port(&p.port_name, lra_layout)
+				GloveDriver::new_for_serial_port(&p.port_name, lra_layout, max_dist)
 			},
 			None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No serial ports found"))
 		}
@@ -288,7 +311,7 @@ impl GloveDriver {
 				}
 			}
 			if log_if_success { println!("[INFO] Device reset successful"); }
-			if DEBUG_LOG_SERIAL_RTT { println!("[DEBUG] RTT: {:?}", begin_write.elapsed()); }
+			self.last_rtt = Some(begin_write.elapsed());
 
 
 			let ack_buf = &self.rx_buf[0..len_read];
@@ -316,7 +339,7 @@ impl GloveDriver {
 
 			for (i, lra) in self.lra_layout.iter().enumerate() {
 				let dist = ((coords.x - lra.x).powi(2) + (coords.y - lra.y).powi(2)).sqrt(); // ignore z coord
-				let x = dist / MAX_DIST; // at 30 mm func evals to 0. Ease in-out, so 99% at 5mm, 90% at 10mm, 10% at 22mm, 1% at ~25mm
+				let x = dist / self.max_dist; // at 30 mm (by default) func evals to 0. Ease in-out, so 99% at 5mm, 90% at 10mm, 10% at 22mm, 1% at ~25mm
 				let y = x.mul_add((-x * x) * x, 1.0).powi(7); // ease in-out 4th, 7th power: f[dist, MAX_DIST, 4] where f[x_, r_, s_] := (1 - (x/r)^s)^7
 				let driver_amp = y.clamp(0.0, 1.0) * intensity;
 
@@ -327,6 +350,11 @@ impl GloveDriver {
 		DriverAmplitudes(driver_amplitudes)
 	}
 
+	/// Round trip time of the last packet sent to the device (until its ack was received)
+	pub fn last_rtt(&self) -> Option<Duration> {
+		self.last_rtt
+	}
+
 	pub fn apply_batch(&mut self, brush_evals: &[pattern_evaluator::BrushAtAnimLocalTime]) -> Result<(), AdapticsError> {
 		let driver_amplitudes = self.calc_driver_amplitudes_from_brush_evals(brush_evals);
 		self.set_driver_amplitudes(&driver_amplitudes)
@@ -420,7 +448,7 @@ use super::*;
 	#[test]
 	#[ignore = "debug, creates video output"]
 	fn debug_test_calc_driver_amplitudes_from_brush_evals() {
-		let gd = GloveDriver::new_mock(DEFAULT_LRA_LAYOUT);
+		let gd = GloveDriver::new_mock(DEFAULT_LRA_LAYOUT, DEFAULT_MAX_DIST);
 
 		let image_size = 440;
 		let frame_rate = 100; // 100hz
@@ -1,3 +1,7 @@
+pub mod output;
+#[cfg(feature = "ulhaptics")]
 pub mod ulhaptics;
 pub mod mock;
-pub mod hapticglove;
\ No newline at end of file
+pub mod hapticglove;
+pub mod recording;
+pub mod udp;
\ No newline at end of file
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use pattern_evaluator::BrushAtAnimLocalTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{threads::pattern::playback::PatternEvalCall, telemetry::Telemetry, util::AdapticsError, config::DebugConfig};
+
+pub const USE_THREAD_SLEEP: Option<Duration> = Some(Duration::from_micros(1000)); // spin_sleeper still needs some buffer time (it shouldnt need any). idk if it overtrusts the os sleep, or its some other slowdown?
+/// Deadline misses shorter than this are only recorded in telemetry, not logged
+const DEADLINE_MISS_WARN_THRESHOLD: Duration = Duration::from_micros(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputKind {
+	/// A phased array rendering a single focal point at arbitrary positions in 3D space
+	MidAirUltrasound,
+	/// A fixed grid of vibrotactile actuators (e.g. a vest or glove)
+	VibrotactileGrid,
+	/// No physical device (e.g. mock output, recording, forwarding over the network)
+	Virtual,
+}
+
+/// Describes what an output backend is able to render
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OutputCapabilities {
+	/// Human readable name of the device
+	pub name: String,
+	pub kind: OutputKind,
+	/// Number of individually driven actuators, if the device has a fixed set of them
+	pub num_actuators: Option<u32>,
+}
+
+/// Handle given to output backends to request evaluations from the pattern-eval thread and report timing information.
+#[derive(Clone)]
+pub struct StreamingContext {
+	patteval_call_tx: crossbeam_channel::Sender<PatternEvalCall>,
+	patteval_return_rx: crossbeam_channel::Receiver<Vec<BrushAtAnimLocalTime>>,
+	end_streaming_rx: crossbeam_channel::Receiver<()>,
+	telemetry: Arc<Telemetry>,
+	debug: DebugConfig,
+}
+impl StreamingContext {
+	pub(crate) fn new(
+		patteval_call_tx: crossbeam_channel::Sender<PatternEvalCall>,
+		patteval_return_rx: crossbeam_channel::Receiver<Vec<BrushAtAnimLocalTime>>,
+		end_streaming_rx: crossbeam_channel::Receiver<()>,
+		telemetry: Arc<Telemetry>,
+		debug: DebugConfig,
+	) -> Self {
+		Self { patteval_call_tx, patteval_return_rx, end_streaming_rx, telemetry, debug }
+	}
+
+	/// Evaluates the current pattern at each of `time_arr_instants` (one per device sample).
+	///
+	/// Returns `None` if the pattern-eval thread exited, in which case the backend should stop streaming.
+	#[must_use]
+	pub fn eval_batch(&self, time_arr_instants: Vec<Instant>) -> Option<Vec<BrushAtAnimLocalTime>> {
+		self.patteval_call_tx.send(PatternEvalCall::EvalBatch{ time_arr_instants }).ok()?;
+		self.patteval_return_rx.recv().ok()
+	}
+
+	/// Returns true once the engine has asked the output to stop
+	#[must_use]
+	pub fn should_stop(&self) -> bool {
+		self.end_streaming_rx.try_recv().is_ok()
+	}
+	/// Blocks until the engine asks the output to stop, or `timeout` elapses. Returns true if the output should stop.
+	#[must_use]
+	pub fn wait_for_stop(&self, timeout: Duration) -> bool {
+		!matches!(self.end_streaming_rx.recv_timeout(timeout), Err(crossbeam_channel::RecvTimeoutError::Timeout))
+	}
+
+	/// Reports that a batch was not ready by the time the device needed it
+	pub fn report_deadline_miss(&self, missed_by: Duration) {
+		self.telemetry.record_deadline_miss(missed_by);
+	}
+	/// For devices that only report a running total of missed deadlines
+	pub fn report_total_deadline_misses(&self, total: u64) {
+		self.telemetry.record_total_deadline_misses(total);
+	}
+	/// Reports the round trip time of sending a batch to the device (e.g. until the device acknowledged it)
+	pub fn report_round_trip_time(&self, rtt: Duration) {
+		if self.debug.log_serial_rtt { println!("[DEBUG] RTT: {rtt:?}"); }
+		self.telemetry.record_serial_rtt(rtt);
+	}
+}
+
+/// A haptic output device.
+///
+/// By default, the engine calls [`OutputBackend::emit_batch`] [`OutputBackend::callback_rate`] times per second
+/// with one evaluation per device sample, starting one callback period in the future.
+/// Backends whose SDK drives the timing itself can override [`OutputBackend::run`].
+pub trait OutputBackend: Send {
+	/// Device samples per second
+	fn sample_rate(&self) -> u64;
+	/// Batches of samples requested per second
+	fn callback_rate(&self) -> f64;
+	fn capabilities(&self) -> OutputCapabilities;
+
+	/// Called on the streaming thread before streaming starts, e.g. to connect to the device.
+	fn start(&mut self) -> Result<(), AdapticsError> { Ok(()) }
+
+	/// Sends one batch of evaluations to the device
+	fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], ctx: &StreamingContext) -> Result<(), AdapticsError>;
+
+	/// Streams to the device until [`StreamingContext::should_stop`] or the pattern-eval thread exits.
+	fn run(&mut self, ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		run_timed_output_loop(self, ctx)
+	}
+}
+
+/// Device sample times of one batch, from `batch_start` (inclusive) until `batch_start + ecallback_tick_dur` (exclusive)
+pub(super) fn batch_sample_instants(batch_start: Instant, ecallback_tick_dur: Duration, device_tick_dur: Duration, samples_per_callback: usize) -> Vec<Instant> {
+	let mut time_arr_instants = Vec::with_capacity(samples_per_callback + 2);
+	let mut future_device_tick_instant = batch_start;
+	while future_device_tick_instant < (batch_start + ecallback_tick_dur) {
+		time_arr_instants.push(future_device_tick_instant);
+		future_device_tick_instant += device_tick_dur;
+	}
+	time_arr_instants
+}
+
+/// The timing loop shared by all backends that do not override [`OutputBackend::run`]
+///
+/// # Panics
+/// Will panic if the backend's sample rate is 0.
+pub fn run_timed_output_loop<B: OutputBackend + ?Sized>(backend: &mut B, ctx: &StreamingContext) -> Result<(), AdapticsError> {
+	// println!("setting thread priority max");
+	// thread_priority::set_current_thread_priority(thread_priority::ThreadPriority::Max).unwrap();
+
+	let sample_rate = backend.sample_rate();
+	let callback_rate = backend.callback_rate();
+	assert!(sample_rate > 0, "sample_rate must be > 0");
+	let device_tick_dur = Duration::from_nanos(1_000_000_000/sample_rate);
+	let ecallback_tick_dur = Duration::from_secs_f64(1.0/callback_rate);
+	let deadline_offset = ecallback_tick_dur * 1;
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+	let samples_per_callback = (sample_rate as f64 / callback_rate) as usize;
+	let mut last_tick = Instant::now();
+
+	let spin_sleeper = spin_sleep::SpinSleeper::default();
+
+	assert!(device_tick_dur.as_secs_f64() > 0.0, "device_tick_dur must be > 0");
+	loop {
+		if ctx.should_stop() {
+			break;
+		}
+
+		let next_tick_at = last_tick + ecallback_tick_dur;
+		// if let Some(bwt) = USE_THREAD_SLEEP { std::thread::sleep(next_tick_at - Instant::now() - Duration::from_micros(bwt)); } // supports windows high resolution sleep since rust 1.75
+		// spin_sleeper.sleep(next_tick_at - Instant::now()); // not accurate enough by itself on windows
+		let sleep_time = next_tick_at.saturating_duration_since(Instant::now());
+		let curr_time = if sleep_time.is_zero() { Instant::now() } else { // if zero we drop a (partial) "frame", so we emit now and then continue with normal intervals, even if we could actually catch up.
+			if let Some(bwt) = USE_THREAD_SLEEP { if sleep_time > bwt { spin_sleeper.sleep(sleep_time.saturating_sub(bwt)); } } // shouldnt need bwt but it does
+			while next_tick_at > Instant::now() {} // busy wait remaining time
+
+			let curr_time = Instant::now();
+			let elapsed = curr_time - last_tick;
+			if ctx.debug.log_lag_events && elapsed > ecallback_tick_dur + Duration::from_micros(100) { println!("[WARN] long sleep (elapsed > ecallback_tick_dur): {elapsed:?} > {ecallback_tick_dur:?}"); }
+			curr_time
+		};
+		last_tick = curr_time; // i need to redo this whole thing at some point, probably use media timers or smth anyway
+
+		let deadline_time = curr_time + deadline_offset;
+
+		let time_arr_instants = batch_sample_instants(deadline_time, ecallback_tick_dur, device_tick_dur, samples_per_callback);
+
+		if let Some(eval_arr) = ctx.eval_batch(time_arr_instants) {
+			backend.emit_batch(&eval_arr, ctx)?;
+		} else {
+			// patt eval thread exited (or panicked),
+			// end_streaming_rx will be called by main thread, could exit here anyway
+			break; // not sure if I want to do this or just loop until end_streaming_rx
+		}
+
+		let deadline_missed_by = deadline_time.elapsed();
+		if !deadline_missed_by.is_zero() {
+			ctx.report_deadline_miss(deadline_missed_by);
+			if deadline_missed_by > DEADLINE_MISS_WARN_THRESHOLD {
+				eprintln!("[WARN] {} missed deadline by {deadline_missed_by:?}", backend.capabilities().name);
+			}
+		}
+	}
+
+	Ok(())
+}
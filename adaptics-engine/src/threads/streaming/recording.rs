@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use pattern_evaluator::BrushAtAnimLocalTime;
+
+use crate::util::AdapticsError;
+use super::output::{batch_sample_instants, run_timed_output_loop, OutputBackend, OutputCapabilities, OutputKind, StreamingContext};
+
+/// Magic bytes at the start of every [`RecordingFormat::Binary`] file
+pub const RECORDING_BINARY_MAGIC: &[u8; 8] = b"ADAPREC1";
+/// Size in bytes of one [`RecordingFormat::Binary`] record
+pub const RECORDING_BINARY_RECORD_LEN: usize = 8 + 8 * 5 + 1;
+const RECORDING_CSV_HEADER: &str = "sample_index,pattern_time,x,y,z,intensity,stop";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+	/// One line per sample, with the header `sample_index,pattern_time,x,y,z,intensity,stop`
+	Csv,
+	/// [`RECORDING_BINARY_MAGIC`], followed by one little-endian record per sample:
+	/// `sample_index: u64, pattern_time: f64, x: f64, y: f64, z: f64, intensity: f64, stop: u8`
+	Binary,
+}
+impl RecordingFormat {
+	/// [`RecordingFormat::Binary`] for `.bin` files, [`RecordingFormat::Csv`] otherwise
+	#[must_use]
+	pub fn from_path(path: &Path) -> Self {
+		if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bin")) { Self::Binary } else { Self::Csv }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingClock {
+	/// Batches are requested in realtime, like a device would
+	WallClock,
+	/// Batches are requested back to back without sleeping, until `duration` of samples has been recorded.
+	/// Sample times start when streaming starts, so load and play the pattern with [`crate::AdapticsEngineHandle::start_with_updates`] to record it from the first sample.
+	Virtual { duration: Duration },
+}
+
+/// Records every evaluated sample to a file instead of a device.
+/// `sample_index` counts device samples since streaming started, so the sample time is `sample_index / sample_rate`.
+pub struct RecordingOutput {
+	path: PathBuf,
+	format: RecordingFormat,
+	clock: RecordingClock,
+	sample_rate: u64,
+	callback_rate: f64,
+	max_file_bytes: Option<u64>,
+	max_rotated_files: usize,
+
+	writer: Option<BufWriter<File>>,
+	file_bytes: u64,
+	samples_written: u64,
+	capped: bool,
+}
+impl RecordingOutput {
+	#[must_use]
+	pub fn new(path: PathBuf, format: RecordingFormat, sample_rate: u64, callback_rate: f64) -> Self {
+		Self {
+			path, format,
+			clock: RecordingClock::WallClock,
+			sample_rate, callback_rate,
+			max_file_bytes: None,
+			max_rotated_files: 0,
+			writer: None,
+			file_bytes: 0,
+			samples_written: 0,
+			capped: false,
+		}
+	}
+	#[must_use]
+	pub fn with_clock(mut self, clock: RecordingClock) -> Self {
+		self.clock = clock;
+		self
+	}
+	/// Limits the size of the recording file to about `max_file_bytes`.
+	/// Once full, the file is renamed to `<path>.1` (and existing `<path>.<n>` to `<path>.<n+1>`) and a new file is started, keeping at most `max_rotated_files` old files.
+	/// If `max_rotated_files` is 0, recording stops once the file is full.
+	#[must_use]
+	pub fn with_max_file_bytes(mut self, max_file_bytes: u64, max_rotated_files: usize) -> Self {
+		self.max_file_bytes = Some(max_file_bytes);
+		self.max_rotated_files = max_rotated_files;
+		self
+	}
+
+	fn rotated_path(&self, n: usize) -> PathBuf {
+		let mut path = self.path.clone().into_os_string();
+		path.push(format!(".{n}"));
+		path.into()
+	}
+
+	fn open_file(&mut self) -> Result<(), AdapticsError> {
+		let mut writer = BufWriter::new(File::create(&self.path)?);
+		self.file_bytes = match self.format {
+			RecordingFormat::Csv => { writeln!(writer, "{RECORDING_CSV_HEADER}")?; RECORDING_CSV_HEADER.len() as u64 + 1 },
+			RecordingFormat::Binary => { writer.write_all(RECORDING_BINARY_MAGIC)?; RECORDING_BINARY_MAGIC.len() as u64 },
+		};
+		self.writer = Some(writer);
+		Ok(())
+	}
+
+	fn rotate_file(&mut self) -> Result<(), AdapticsError> {
+		if let Some(mut writer) = self.writer.take() { writer.flush()?; }
+		if self.max_rotated_files == 0 {
+			eprintln!("[WARN] recording file {} is full, no longer recording", self.path.display());
+			self.capped = true;
+			return Ok(());
+		}
+		for n in (1..self.max_rotated_files).rev() {
+			let from = self.rotated_path(n);
+			if from.exists() { std::fs::rename(from, self.rotated_path(n + 1))?; }
+		}
+		std::fs::rename(&self.path, self.rotated_path(1))?;
+		self.open_file()
+	}
+
+	fn write_sample(&mut self, eval: &BrushAtAnimLocalTime) -> Result<(), AdapticsError> {
+		let sample_index = self.samples_written;
+		self.samples_written += 1;
+		if self.capped { return Ok(()); }
+		if self.max_file_bytes.is_some_and(|max_file_bytes| self.file_bytes >= max_file_bytes) {
+			self.rotate_file()?;
+			if self.capped { return Ok(()); }
+		}
+		let writer = self.writer.as_mut().ok_or(AdapticsError::new("RecordingOutput.emit_batch called before start"))?;
+		let coords = &eval.ul_control_point.coords;
+		match self.format {
+			RecordingFormat::Csv => {
+				let line = format!("{sample_index},{},{},{},{},{},{}\n", eval.pattern_time, coords.x, coords.y, coords.z, eval.ul_control_point.intensity, u8::from(eval.stop));
+				writer.write_all(line.as_bytes())?;
+				self.file_bytes += line.len() as u64;
+			},
+			RecordingFormat::Binary => {
+				let mut record = [0u8; RECORDING_BINARY_RECORD_LEN];
+				record[0..8].copy_from_slice(&sample_index.to_le_bytes());
+				for (i, value) in [eval.pattern_time, coords.x, coords.y, coords.z, eval.ul_control_point.intensity].into_iter().enumerate() {
+					record[8 + i * 8..16 + i * 8].copy_from_slice(&value.to_le_bytes());
+				}
+				record[RECORDING_BINARY_RECORD_LEN - 1] = u8::from(eval.stop);
+				writer.write_all(&record)?;
+				self.file_bytes += RECORDING_BINARY_RECORD_LEN as u64;
+			},
+		}
+		Ok(())
+	}
+
+	fn run_virtual_clock(&mut self, ctx: &StreamingContext, duration: Duration) -> Result<(), AdapticsError> {
+		let device_tick_dur = Duration::from_nanos(1_000_000_000/self.sample_rate);
+		let ecallback_tick_dur = Duration::from_secs_f64(1.0/self.callback_rate);
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+		let samples_per_callback = (self.sample_rate as f64 / self.callback_rate) as usize;
+		let start = Instant::now();
+		let mut batch_start = start;
+		while batch_start - start < duration {
+			if ctx.should_stop() { return Ok(()); }
+			let Some(eval_arr) = ctx.eval_batch(batch_sample_instants(batch_start, ecallback_tick_dur, device_tick_dur, samples_per_callback)) else { return Ok(()); };
+			self.emit_batch(&eval_arr, ctx)?;
+			batch_start += ecallback_tick_dur;
+		}
+		if let Some(writer) = self.writer.as_mut() { writer.flush()?; }
+		println!("recorded {duration:?} to {}", self.path.display());
+		while !ctx.wait_for_stop(Duration::from_secs(1)) {}
+		Ok(())
+	}
+}
+impl OutputBackend for RecordingOutput {
+	fn sample_rate(&self) -> u64 { self.sample_rate }
+	fn callback_rate(&self) -> f64 { self.callback_rate }
+	fn capabilities(&self) -> OutputCapabilities {
+		OutputCapabilities { name: "recording".to_string(), kind: OutputKind::Virtual, num_actuators: None }
+	}
+
+	fn start(&mut self) -> Result<(), AdapticsError> {
+		self.open_file()
+	}
+
+	fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], _ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		for eval in evals {
+			self.write_sample(eval)?;
+		}
+		Ok(())
+	}
+
+	fn run(&mut self, ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		let res = match self.clock {
+			RecordingClock::WallClock => run_timed_output_loop(self, ctx),
+			RecordingClock::Virtual { duration } => self.run_virtual_clock(ctx, duration),
+		};
+		if let Some(writer) = self.writer.as_mut() { writer.flush()?; }
+		res
+	}
+}
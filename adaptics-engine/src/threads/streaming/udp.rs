@@ -0,0 +1,161 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use pattern_evaluator::BrushAtAnimLocalTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{threads::net::osc::{encode_osc_bundle, OscArg, OscMessage}, util::AdapticsError};
+use super::output::{OutputBackend, OutputCapabilities, OutputKind, StreamingContext};
+
+/// Packets are kept below this size to avoid IP fragmentation
+const MAX_PACKET_LEN: usize = 1400;
+
+/// OSC address of the messages sent by [`UdpFormat::Osc`]
+pub const UDP_OSC_CONTROL_POINT_ADDRESS: &str = "/adaptics/control_point";
+/// Magic bytes at the start of every [`UdpFormat::Raw`] packet
+pub const UDP_RAW_MAGIC: &[u8; 4] = b"ADCP";
+pub const UDP_RAW_VERSION: u16 = 1;
+/// Size in bytes of the [`UdpFormat::Raw`] packet header
+pub const UDP_RAW_HEADER_LEN: usize = 4 + 2 + 2 + 8;
+/// Size in bytes of one [`UdpFormat::Raw`] control point record
+pub const UDP_RAW_RECORD_LEN: usize = 8 + 4 * 4 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UdpFormat {
+	/// One OSC bundle per packet, containing one message per control point:
+	/// `/adaptics/control_point ,ifffffi sample_index pattern_time x y z intensity stop`
+	/// (`sample_index` wraps at `i32::MAX`, coordinates are in millimeters, `stop` is 0 or 1).
+	#[default]
+	Osc,
+	/// Little-endian packets of a 16 byte header
+	/// `magic: [u8; 4] = "ADCP", version: u16 = 1, count: u16, first_sample_index: u64`,
+	/// followed by `count` records of `pattern_time: f64, x: f32, y: f32, z: f32, intensity: f32, flags: u32` (bit 0 is `stop`).
+	/// Consecutive records are `decimation` device samples apart.
+	Raw,
+}
+impl std::str::FromStr for UdpFormat {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"osc" => Ok(Self::Osc),
+			"raw" => Ok(Self::Raw),
+			_ => Err(format!("unknown UDP format '{s}', expected 'osc' or 'raw'")),
+		}
+	}
+}
+
+/// Streams the evaluated control points of every batch to a UDP address,
+/// e.g. for custom phased array controllers or visualization tools (`Max/MSP`, `TouchDesigner`, Unity, etc.).
+pub struct UdpOutput {
+	target: SocketAddr,
+	format: UdpFormat,
+	sample_rate: u64,
+	callback_rate: f64,
+	decimation: u64,
+
+	socket: Option<UdpSocket>,
+	samples_emitted: u64,
+	send_failed: bool,
+}
+impl UdpOutput {
+	#[must_use]
+	pub fn new(target: SocketAddr, format: UdpFormat, sample_rate: u64, callback_rate: f64) -> Self {
+		Self { target, format, sample_rate, callback_rate, decimation: 1, socket: None, samples_emitted: 0, send_failed: false }
+	}
+	/// Only sends every `decimation`th device sample (1 sends every sample)
+	#[must_use]
+	pub fn with_decimation(mut self, decimation: u64) -> Self {
+		self.decimation = decimation.max(1);
+		self
+	}
+
+	fn send_osc(socket: &UdpSocket, samples: &[(u64, &BrushAtAnimLocalTime)]) -> Result<(), AdapticsError> {
+		let mut messages = Vec::new();
+		let mut bundle_len = 16;
+		for (sample_index, eval) in samples {
+			let coords = &eval.ul_control_point.coords;
+			#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+			let message = OscMessage::new(UDP_OSC_CONTROL_POINT_ADDRESS, vec![
+				OscArg::Int((*sample_index % (i32::MAX as u64)) as i32),
+				OscArg::Float(eval.pattern_time as f32),
+				OscArg::Float(coords.x as f32),
+				OscArg::Float(coords.y as f32),
+				OscArg::Float(coords.z as f32),
+				OscArg::Float(eval.ul_control_point.intensity as f32),
+				OscArg::Int(i32::from(eval.stop)),
+			]);
+			let mut message_buf = Vec::new();
+			message.encode(&mut message_buf);
+			if bundle_len + 4 + message_buf.len() > MAX_PACKET_LEN && !messages.is_empty() {
+				socket.send(&encode_osc_bundle(&messages))?;
+				messages.clear();
+				bundle_len = 16;
+			}
+			bundle_len += 4 + message_buf.len();
+			messages.push(message);
+		}
+		if !messages.is_empty() { socket.send(&encode_osc_bundle(&messages))?; }
+		Ok(())
+	}
+
+	fn send_raw(socket: &UdpSocket, samples: &[(u64, &BrushAtAnimLocalTime)]) -> Result<(), AdapticsError> {
+		let max_records = (MAX_PACKET_LEN - UDP_RAW_HEADER_LEN) / UDP_RAW_RECORD_LEN;
+		let mut packet = Vec::with_capacity(MAX_PACKET_LEN);
+		for chunk in samples.chunks(max_records) {
+			packet.clear();
+			packet.extend_from_slice(UDP_RAW_MAGIC);
+			packet.extend_from_slice(&UDP_RAW_VERSION.to_le_bytes());
+			packet.extend_from_slice(&u16::try_from(chunk.len())?.to_le_bytes());
+			packet.extend_from_slice(&chunk[0].0.to_le_bytes());
+			for (_, eval) in chunk {
+				let coords = &eval.ul_control_point.coords;
+				packet.extend_from_slice(&eval.pattern_time.to_le_bytes());
+				#[allow(clippy::cast_possible_truncation)]
+				for value in [coords.x as f32, coords.y as f32, coords.z as f32, eval.ul_control_point.intensity as f32] {
+					packet.extend_from_slice(&value.to_le_bytes());
+				}
+				packet.extend_from_slice(&u32::from(eval.stop).to_le_bytes());
+			}
+			socket.send(&packet)?;
+		}
+		Ok(())
+	}
+}
+impl OutputBackend for UdpOutput {
+	fn sample_rate(&self) -> u64 { self.sample_rate }
+	fn callback_rate(&self) -> f64 { self.callback_rate }
+	fn capabilities(&self) -> OutputCapabilities {
+		OutputCapabilities { name: "udp".to_string(), kind: OutputKind::Virtual, num_actuators: None }
+	}
+
+	fn start(&mut self) -> Result<(), AdapticsError> {
+		let socket = UdpSocket::bind(if self.target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+		socket.connect(self.target)?;
+		self.socket = Some(socket);
+		Ok(())
+	}
+
+	fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], _ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		let socket = self.socket.as_ref().ok_or(AdapticsError::new("UdpOutput.emit_batch called before start"))?;
+		let first_sample_index = self.samples_emitted;
+		self.samples_emitted += evals.len() as u64;
+		let samples: Vec<_> = (first_sample_index..).zip(evals)
+			.filter(|(sample_index, _)| sample_index % self.decimation == 0)
+			.collect();
+		if samples.is_empty() { return Ok(()); }
+		let res = match self.format {
+			UdpFormat::Osc => Self::send_osc(socket, &samples),
+			UdpFormat::Raw => Self::send_raw(socket, &samples),
+		};
+		// e.g. nothing listening on the target (ICMP port unreachable), keep streaming in case it starts listening
+		match res {
+			Err(e) if !self.send_failed => {
+				eprintln!("[WARN] failed to send UDP output to {}: {e}", self.target);
+				self.send_failed = true;
+			},
+			Err(_) => {},
+			Ok(()) => self.send_failed = false,
+		}
+		Ok(())
+	}
+}
@@ -8,7 +8,8 @@ use ffi::*;
 use ffi::cxx_ffi::*;
 use pattern_evaluator::{PatternEvaluator, BrushAtAnimLocalTime};
 
-use crate::{threads::{common::{js_milliseconds_to_duration, MilSec}, pattern::playback::PatternEvalCall}, util::AdapticsError};
+use crate::{threads::common::{js_milliseconds_to_duration, MilSec}, telemetry::TELEMETRY_INTERVAL, util::AdapticsError};
+use super::output::{OutputBackend, OutputCapabilities, OutputKind, StreamingContext};
 
 impl From<BrushAtAnimLocalTime> for EvalResult {
     fn from(be: BrushAtAnimLocalTime) -> EvalResult {
@@ -23,11 +24,41 @@ impl From<BrushAtAnimLocalTime> for EvalResult {
     }
 }
 
-pub fn start_streaming_emitter(
+/// Ultraleap mid-air ultrasound haptic device. The Ultraleap SDK drives the timing of the emission callback.
+pub struct UlhapticsOutput {
 	callback_rate: f32,
-	patteval_call_tx: crossbeam_channel::Sender<PatternEvalCall>,
-	patteval_return_rx: crossbeam_channel::Receiver<Vec<BrushAtAnimLocalTime>>,
-	end_streaming_rx: &crossbeam_channel::Receiver<()>,
+}
+impl UlhapticsOutput {
+	#[must_use]
+	pub fn new(callback_rate: f32) -> Self {
+		Self { callback_rate }
+	}
+}
+/// Identifier of the first Ultraleap haptic device that supports streaming, as reported by the Ultraleap Haptics SDK
+pub fn find_ulhaptics_device() -> Result<String, AdapticsError> {
+	Ok(find_haptic_device()?)
+}
+
+impl OutputBackend for UlhapticsOutput {
+	/// Nominal, the SDK chooses the actual sample times
+	fn sample_rate(&self) -> u64 { 20_000 }
+	fn callback_rate(&self) -> f64 { f64::from(self.callback_rate) }
+	fn capabilities(&self) -> OutputCapabilities {
+		OutputCapabilities { name: "ulhaptics".to_string(), kind: OutputKind::MidAirUltrasound, num_actuators: None }
+	}
+
+	fn emit_batch(&mut self, _evals: &[BrushAtAnimLocalTime], _ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		Err(AdapticsError::new("UlhapticsOutput is driven by the Ultraleap SDK callback, emit_batch is not supported"))
+	}
+
+	fn run(&mut self, ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		start_streaming_emitter(self.callback_rate, ctx)
+	}
+}
+
+fn start_streaming_emitter(
+	callback_rate: f32,
+	ctx: &StreamingContext,
 ) -> Result<(), AdapticsError> {
 	type CallbackFn = Box<dyn Fn(&CxxVector<MilSec>, Pin<&mut CxxVector<EvalResult>>) + Send>;
 	static STATIC_ECALLBACK_MUTEX: Mutex<Option<CallbackFn>> = Mutex::new(None);
@@ -44,11 +75,11 @@ pub fn start_streaming_emitter(
 			f(time_arr_ms, eval_results_arr);
 		}
 	}
+	let callback_ctx = ctx.clone();
 	let streaming_emission_callback = move |time_arr_ms: &CxxVector<MilSec>, eval_results_arr: Pin<&mut CxxVector<EvalResult>> | {
-		if patteval_call_tx.send(PatternEvalCall::EvalBatch{
-			time_arr_instants: time_arr_ms.iter().map(|ms| sync_epoch_instant.add(js_milliseconds_to_duration(ms-sync_epoch_chrono_ms))).collect() // convert from chrono time to Instant using epoch
-		}).is_ok() {
-			let eval_arr = patteval_return_rx.recv().unwrap();
+		if let Some(eval_arr) = callback_ctx.eval_batch(
+			time_arr_ms.iter().map(|ms| sync_epoch_instant.add(js_milliseconds_to_duration(ms-sync_epoch_chrono_ms))).collect() // convert from chrono time to Instant using epoch
+		) {
 			let eval_results_arr = eval_results_arr.as_mut_slice();
 			for (i,eval) in eval_arr.into_iter().enumerate() {
 				eval_results_arr[i] = eval.into();
@@ -67,7 +98,9 @@ pub fn start_streaming_emitter(
 		Ok(mut ulh_streaming_controller) => {
 			ulh_streaming_controller.pin_mut().resume_emitter()?;
 			// println!("getMissedCallbackIterations: {}", ulh_streaming_controller.getMissedCallbackIterations()?); # 0
-			end_streaming_rx.recv()?;
+			while !ctx.wait_for_stop(TELEMETRY_INTERVAL) {
+				ctx.report_total_deadline_misses(ulh_streaming_controller.getMissedCallbackIterations()? as u64);
+			}
 			println!("getMissedCallbackIterations: {}", ulh_streaming_controller.getMissedCallbackIterations()?);
 			drop(ulh_streaming_controller);
 			let cb = STATIC_ECALLBACK_MUTEX.lock().or(Err(AdapticsError::new("STATIC_ECALLBACK_MUTEX poisoned")))?.take();
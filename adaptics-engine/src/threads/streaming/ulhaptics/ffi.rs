@@ -26,5 +26,7 @@ pub(super) mod cxx_ffi {
         fn new_ulh_streaming_controller(callback_rate: f32, cb_func: fn(&CxxVector<f64>, Pin<&mut CxxVector<EvalResult>>)) -> Result<UniquePtr<ULHStreamingController>>;
 
         fn get_current_chrono_time() -> f64;
+
+        fn find_haptic_device() -> Result<String>;
     }
 }
@@ -139,4 +139,16 @@ std::unique_ptr<ULHStreamingController> new_ulh_streaming_controller(float callb
 double get_current_chrono_time() {
 	LocalTimePoint tp = LocalTimeClock::now();
 	return JavascriptMilliseconds(tp.time_since_epoch()).count();
+}
+
+
+rust::String find_haptic_device() {
+	Library lib;
+	unwrap(lib.connect());
+	auto device_result = lib.findDevice(DeviceFeatures::StreamingHaptics);
+	throw_if_error(device_result);
+	auto identifier_result = device_result.value().getIdentifier();
+	throw_if_error(identifier_result);
+	unwrap(lib.disconnect());
+	return rust::String(identifier_result.value());
 }
\ No newline at end of file
@@ -29,3 +29,5 @@ std::unique_ptr<ULHStreamingController> new_ulh_streaming_controller(float callb
 
 double get_current_chrono_time();
 
+rust::String find_haptic_device();
+
@@ -1,10 +1,10 @@
-use crossbeam_channel::TrySendError;
 #[allow(clippy::wildcard_imports)]
 use leapc_dyn_sys::*;
 
-use crate::{threads::net::websocket::AdapticsWSServerMessage, util::AdapticsError, DEBUG_LOG_LAG_EVENTS};
+use crate::util::AdapticsError;
 
-use super::{TrackingFrame, TrackingFrameHand, TrackingFrameHandChirality, TrackingFrameDigit, TrackingFrameBone, TrackingFramePalm};
+use super::source::{TrackingContext, TrackingSource};
+use super::{TrackingStatus, TrackingFrame, TrackingFrameHand, TrackingFrameHandChirality, TrackingFrameDigit, TrackingFrameBone, TrackingFramePalm};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -24,8 +24,9 @@ impl From<_LEAP_VECTOR> for LMCRawTrackingVec3 {
 }
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 struct LMCRawTrackingHand {
-	has_hand: bool,
 	left_hand: bool,
+	/// Microseconds since the hand was first tracked
+	visible_time: u64,
 	palm: LMCRawTrackingPalm,
 	digits: [LMCRawTrackingDigit; 5],
 }
@@ -107,8 +108,11 @@ impl LeapCSafe {
 	}
 }
 
+/// Called with all hands of each tracking frame
+type TrackingCallback<'a> = Box<dyn Fn(&[LMCRawTrackingHand]) + 'a>;
+
 #[allow(clippy::needless_pass_by_value)] // idc
-fn run_loop<'a>(cb_func: Box<dyn Fn(&LMCRawTrackingHand) + 'a>, is_done: Box<dyn Fn() -> bool + 'a>) -> Result<(), AdapticsError> {
+fn run_loop<'a>(cb_func: TrackingCallback<'a>, is_done: Box<dyn Fn() -> bool + 'a>) -> Result<(), AdapticsError> {
 	let leap_c_safe = LeapCSafe::new()?;
 
 	let connection_handle = leap_c_safe.create_connection()?;
@@ -119,11 +123,11 @@ fn run_loop<'a>(cb_func: Box<dyn Fn(&LMCRawTrackingHand) + 'a>, is_done: Box<dyn
 		if let Some(msg) = leap_c_safe.poll_connection(connection_handle, timeout_ms)? {
 			if msg.type_ == _eLeapEventType_eLeapEventType_Tracking {
 				let tracking_event = unsafe { msg.__bindgen_anon_1.tracking_event.as_ref() };
-				// if tracking event is null, or tracking_frame_id is 0, or nHands is 0, call cb with has_hand = false
-				// else, call cb with has_hand = true, and x, y, z from the first hand
-				let lmc_raw_coords = match tracking_event {
-					Some(tracking_event) if tracking_event.tracking_frame_id != 0 && tracking_event.nHands > 0 => {
-						let hand = unsafe { *tracking_event.pHands.offset(0) };
+				// if tracking event is null or tracking_frame_id is 0, call cb without hands
+				// else, call cb with all hands in the frame
+				let lmc_raw_hands: Vec<LMCRawTrackingHand> = match tracking_event {
+					Some(tracking_event) if tracking_event.tracking_frame_id != 0 => (0..tracking_event.nHands as usize).map(|hand_index| {
+						let hand = unsafe { *tracking_event.pHands.add(hand_index) };
 						let digits: [LMCRawTrackingDigit; 5] = (0..5).map(|finger_index| {
 							let bones: [LMCRawTrackingBone; 4] = (0..4).map(|bone_index| {
 								let bone = unsafe { &hand.__bindgen_anon_1.digits[finger_index].__bindgen_anon_1.bones[bone_index] };
@@ -138,8 +142,8 @@ fn run_loop<'a>(cb_func: Box<dyn Fn(&LMCRawTrackingHand) + 'a>, is_done: Box<dyn
 							LMCRawTrackingDigit { bones, }
 						}).collect::<Vec<_>>().try_into().unwrap(); // Converting Vec to fixed-size array
 						LMCRawTrackingHand {
-							has_hand: true,
 							left_hand: hand.type_ == _eLeapHandType_eLeapHandType_Left,
+							visible_time: hand.visible_time,
 							palm: LMCRawTrackingPalm {
 								position: hand.palm.position.into(),
 								width: hand.palm.width.into(),
@@ -148,10 +152,10 @@ fn run_loop<'a>(cb_func: Box<dyn Fn(&LMCRawTrackingHand) + 'a>, is_done: Box<dyn
 							},
 							digits,
 						}
-					},
-					_ => LMCRawTrackingHand::default()
+					}).collect(),
+					_ => Vec::new(),
 				};
-				cb_func(&lmc_raw_coords);
+				cb_func(&lmc_raw_hands);
 			} else {
 				// we don't care about other events
 			}
@@ -165,6 +169,42 @@ fn run_loop<'a>(cb_func: Box<dyn Fn(&LMCRawTrackingHand) + 'a>, is_done: Box<dyn
 	Ok(())
 }
 
+/// Connects to the tracking service for `timeout`, collecting the connection, device and tracking events it sends.
+/// Fails if the `LeapC` library cannot be loaded or the connection cannot be created.
+pub fn tracking_status(timeout: std::time::Duration) -> Result<TrackingStatus, AdapticsError> {
+	let leap_c_safe = LeapCSafe::new()?;
+
+	let connection_handle = leap_c_safe.create_connection()?;
+	leap_c_safe.open_connection(connection_handle)?;
+
+	let mut status = TrackingStatus::default();
+	let deadline = std::time::Instant::now() + timeout;
+	while std::time::Instant::now() < deadline {
+		let timeout_ms = 100;
+		let msg = match leap_c_safe.poll_connection(connection_handle, timeout_ms) {
+			Ok(Some(msg)) => msg,
+			Ok(None) => continue,
+			Err(e) => { leap_c_safe.close_connection(connection_handle); leap_c_safe.destroy_connection(connection_handle); return Err(e); },
+		};
+		if msg.type_ == _eLeapEventType_eLeapEventType_Connection {
+			status.service_connected = true;
+		} else if msg.type_ == _eLeapEventType_eLeapEventType_ConnectionLost {
+			status.service_connected = false;
+		} else if msg.type_ == _eLeapEventType_eLeapEventType_Device {
+			status.devices += 1;
+		} else if msg.type_ == _eLeapEventType_eLeapEventType_DeviceLost {
+			status.devices = status.devices.saturating_sub(1);
+		} else if msg.type_ == _eLeapEventType_eLeapEventType_Tracking {
+			status.streaming = true;
+		}
+	}
+
+	leap_c_safe.close_connection(connection_handle);
+	leap_c_safe.destroy_connection(connection_handle);
+
+	Ok(status)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum ELeapRS {
 	Success,
@@ -235,11 +275,11 @@ fn eleaprs_to_result(res: eLeapRS) -> Result<(), ELeapRS> {
 }
 
 impl LMCRawTrackingVec3 {
-	fn to_mah_as_coords(self) -> pattern_evaluator::MAHCoordsConst {
+	fn to_mah_as_coords(self, origin_offset: &pattern_evaluator::MAHCoordsConst) -> pattern_evaluator::MAHCoordsConst {
 		pattern_evaluator::MAHCoordsConst {
-			x: self.x,
-			y: -self.z + 121.0, // 121mm is the offset from the LMC origin to the haptic origin
-			z: self.y, // flip y and z to match the haptic coordinate system
+			x: self.x + origin_offset.x,
+			y: -self.z + origin_offset.y, // 121mm (by default) is the offset from the LMC origin to the haptic origin
+			z: self.y + origin_offset.z, // flip y and z to match the haptic coordinate system
 		}
 	}
 	fn to_mah_as_vector(self) -> pattern_evaluator::MAHCoordsConst {
@@ -252,58 +292,58 @@ impl LMCRawTrackingVec3 {
 }
 
 
-impl From<&LMCRawTrackingHand> for TrackingFrame {
-	fn from(raw: &LMCRawTrackingHand) -> Self {
-		TrackingFrame {
-			hand: if raw.has_hand {
-				Some(TrackingFrameHand {
-					chirality: if raw.left_hand { TrackingFrameHandChirality::Left } else { TrackingFrameHandChirality::Right },
-					palm: TrackingFramePalm {
-						position: raw.palm.position.to_mah_as_coords(),
-						width: raw.palm.width,
-						normal: raw.palm.normal.to_mah_as_vector(),
-						direction: raw.palm.direction.to_mah_as_vector(),
-					},
-					digits: raw.digits.iter().map(|raw_digit| {
-						TrackingFrameDigit {
-							bones: raw_digit.bones.iter().map(|raw_bone| {
-								TrackingFrameBone {
-									start: raw_bone.start.to_mah_as_coords(),
-									end: raw_bone.end.to_mah_as_coords(),
-									width: raw_bone.width,
-								}
-							}).collect::<Vec<_>>().try_into().unwrap(), // Converting Vec to fixed-size array
+impl LMCRawTrackingHand {
+	fn to_tracking_frame_hand(&self, origin_offset: &pattern_evaluator::MAHCoordsConst) -> TrackingFrameHand {
+		TrackingFrameHand {
+			chirality: if self.left_hand { TrackingFrameHandChirality::Left } else { TrackingFrameHandChirality::Right },
+			palm: TrackingFramePalm {
+				position: self.palm.position.to_mah_as_coords(origin_offset),
+				width: self.palm.width,
+				normal: self.palm.normal.to_mah_as_vector(),
+				direction: self.palm.direction.to_mah_as_vector(),
+			},
+			digits: self.digits.iter().map(|raw_digit| {
+				TrackingFrameDigit {
+					bones: raw_digit.bones.iter().map(|raw_bone| {
+						TrackingFrameBone {
+							start: raw_bone.start.to_mah_as_coords(origin_offset),
+							end: raw_bone.end.to_mah_as_coords(origin_offset),
+							width: raw_bone.width,
 						}
 					}).collect::<Vec<_>>().try_into().unwrap(), // Converting Vec to fixed-size array
-				})
-			} else { None }
+				}
+			}).collect::<Vec<_>>().try_into().unwrap(), // Converting Vec to fixed-size array
 		}
 	}
 }
 
-pub fn start_tracking_loop(
-	tracking_data_tx: crossbeam_channel::Sender<TrackingFrame>,
-	tracking_data_ws_tx: Option<crossbeam_channel::Sender<AdapticsWSServerMessage>>,
-	end_tracking_rx: &crossbeam_channel::Receiver<()>,
-) -> Result<(), AdapticsError> {
-	let tracking_callback = move |raw_coords: &LMCRawTrackingHand| {
-		let tracking_frame: TrackingFrame = raw_coords.into();
-		match tracking_data_tx.try_send(tracking_frame.clone()) {
-			Ok(()) => {},
-			Err(TrySendError::Disconnected(_)) => {}, // is_done() should return true, so the run loop will exit
-			Err(TrySendError::Full(_)) => { if DEBUG_LOG_LAG_EVENTS { println!("playback thread lagged [tracking]"); } }, // we are sending too fast for playback thread, so we can just drop this frame
-		}
-		if let Some(tracking_data_ws_tx) = tracking_data_ws_tx.as_ref() {
-			match tracking_data_ws_tx.try_send(AdapticsWSServerMessage::TrackingData { tracking_frame }) {
-				Ok(()) => {},
-				Err(TrySendError::Disconnected(_)) => {}, // is_done() should return true, so the run loop will exit
-				Err(TrySendError::Full(_)) => { if DEBUG_LOG_LAG_EVENTS { println!("network thread lagged [tracking]"); } }, // we are sending too fast for network thread, so we can just drop this frame
-			}
-		}
-	};
-	let is_done = || end_tracking_rx.try_recv().is_ok();
+/// The two hands that have been tracked the longest, the one tracked the longest first
+fn to_tracking_frame(raw_hands: &[LMCRawTrackingHand], origin_offset: &pattern_evaluator::MAHCoordsConst) -> TrackingFrame {
+	let mut raw_hands: Vec<&LMCRawTrackingHand> = raw_hands.iter().collect();
+	raw_hands.sort_by_key(|raw_hand| std::cmp::Reverse(raw_hand.visible_time));
+	let mut hands = raw_hands.into_iter().map(|raw_hand| raw_hand.to_tracking_frame_hand(origin_offset));
+	TrackingFrame::new(hands.next(), hands.next())
+}
 
-	run_loop(Box::new(tracking_callback), Box::new(is_done))?;
+/// Hand tracking from the Ultraleap tracking service (e.g. a Leap Motion Controller), up to two hands are tracked
+pub struct LeapMotionSource {
+	origin_offset: pattern_evaluator::MAHCoordsConst,
+}
+impl LeapMotionSource {
+	/// `origin_offset` is the position of the tracking device's origin in the haptic coordinate system, see [`super::TrackingConfig::origin_offset`]
+	#[must_use]
+	pub fn new(origin_offset: pattern_evaluator::MAHCoordsConst) -> Self {
+		Self { origin_offset }
+	}
+}
+impl TrackingSource for LeapMotionSource {
+	fn name(&self) -> String { "lmc".to_string() }
 
-	Ok(())
-}
\ No newline at end of file
+	fn run(&mut self, ctx: &TrackingContext) -> Result<(), AdapticsError> {
+		let origin_offset = &self.origin_offset;
+		let tracking_callback = |raw_hands: &[LMCRawTrackingHand]| ctx.send_frame(to_tracking_frame(raw_hands, origin_offset));
+		let is_done = || ctx.should_stop();
+
+		run_loop(Box::new(tracking_callback), Box::new(is_done))
+	}
+}
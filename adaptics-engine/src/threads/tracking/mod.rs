@@ -1,12 +1,150 @@
 #![allow(clippy::module_name_repetitions)]
+use std::path::PathBuf;
+use std::time::Instant;
+
 use serde::{Serialize, Deserialize};
 use schemars::JsonSchema;
 
+#[cfg(feature = "leapmotion")]
 pub mod leapmotion;
+pub mod source;
+pub mod synthetic;
+pub mod replay;
+
+use crate::util::AdapticsError;
+use source::TrackingSource;
+use synthetic::SyntheticMotion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingSourceKind {
+	/// Ultraleap tracking service (leap motion controller), requires the `leapmotion` feature
+	#[default]
+	LeapMotion,
+	/// Scripted hand motion, see [`TrackingConfig::synthetic_motion`]
+	Synthetic,
+	/// Frames recorded in [`TrackingConfig::replay_path`], see [`replay::ReplayTrackingSource`]
+	Replay,
+}
+
+/// Hand tracking options, see [`crate::EngineConfig::tracking`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackingConfig {
+	/// Runs the tracking source
+	pub enabled: bool,
+	pub source: TrackingSourceKind,
+	/// Position of the tracking device's origin in the haptic coordinate system (in mm).
+	/// The default is a Leap Motion Controller mounted at the edge of the haptic device.
+	pub origin_offset: pattern_evaluator::MAHCoordsConst,
+	/// e.g. `{ motion = "circle", center = { x = 0, y = 0, z = 200 }, radius = 40, period = 4 }`
+	pub synthetic_motion: SyntheticMotion,
+	/// Motion of a second (left) hand of the synthetic source, there is no second hand if this is not set
+	pub synthetic_second_hand: Option<SyntheticMotion>,
+	/// Frames per second of the synthetic source
+	pub synthetic_frame_rate: f64,
+	/// JSON lines file with one `{ "time": <seconds>, "tracking_frame": <frame> }` per line
+	pub replay_path: Option<PathBuf>,
+	/// Restarts the replay after the last frame
+	pub replay_loop: bool,
+}
+impl Default for TrackingConfig {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			source: TrackingSourceKind::default(),
+			origin_offset: pattern_evaluator::MAHCoordsConst { x: 0.0, y: 121.0, z: 0.0 },
+			synthetic_motion: SyntheticMotion::default(),
+			synthetic_second_hand: None,
+			synthetic_frame_rate: 90.0,
+			replay_path: None,
+			replay_loop: false,
+		}
+	}
+}
+impl TrackingConfig {
+	pub(crate) fn validate(&self) -> Result<(), AdapticsError> {
+		self.synthetic_motion.validate("tracking.synthetic_motion")?;
+		if let Some(motion) = &self.synthetic_second_hand { motion.validate("tracking.synthetic_second_hand")?; }
+		if self.synthetic_frame_rate <= 0.0 { return Err(AdapticsError::new("tracking.synthetic_frame_rate must be greater than 0")); }
+		if self.source == TrackingSourceKind::Replay && self.replay_path.is_none() { return Err(AdapticsError::new("tracking.replay_path is required for the replay source")); }
+		Ok(())
+	}
+
+	/// Creates the tracking source selected by [`TrackingConfig::source`]
+	pub fn tracking_source(&self) -> Result<Box<dyn TrackingSource>, AdapticsError> {
+		match self.source {
+			#[cfg(feature = "leapmotion")]
+			TrackingSourceKind::LeapMotion => Ok(Box::new(leapmotion::LeapMotionSource::new(self.origin_offset.clone()))),
+			#[cfg(not(feature = "leapmotion"))]
+			TrackingSourceKind::LeapMotion => Err(AdapticsError::new("adaptics-engine was built without the leapmotion feature, use the synthetic or replay tracking source instead")),
+			TrackingSourceKind::Synthetic => Ok(Box::new(synthetic::SyntheticTrackingSource::new(self.synthetic_motion.clone(), self.synthetic_frame_rate).with_second_hand(self.synthetic_second_hand.clone()))),
+			TrackingSourceKind::Replay => {
+				let path = self.replay_path.clone().ok_or(AdapticsError::new("tracking.replay_path is required for the replay source"))?;
+				Ok(Box::new(replay::ReplayTrackingSource::new(path).with_loop(self.replay_loop)))
+			},
+		}
+	}
+}
+
+/// What the Ultraleap tracking service reported while probing it, see [`crate::devices::tracking_status`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrackingStatus {
+	/// The tracking service accepted the connection
+	pub service_connected: bool,
+	/// Number of tracking devices (e.g. Leap Motion Controllers) attached to the service
+	pub devices: usize,
+	/// Tracking frames were received
+	pub streaming: bool,
+}
+
+/// Which tracked hand(s) the focal point follows, see [`crate::PlaybackConfig::tracked_hand`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackedHand {
+	/// The hand that has been tracked the longest ([`TrackingFrame::hand`])
+	#[default]
+	FirstSeen,
+	Left,
+	Right,
+	/// Both hands as separate focal points, alternating the device samples between them
+	Both,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct TrackingFrame {
-	pub hand: Option<TrackingFrameHand>
+	/// The hand that has been tracked the longest
+	pub hand: Option<TrackingFrameHand>,
+	/// The other hand, if two hands are tracked
+	#[serde(default)]
+	pub second_hand: Option<TrackingFrameHand>,
+	/// When the frame was captured by the tracking source, e.g. to measure [`crate::TrackingQuantity::HandSpeed`].
+	/// Not serialized, frames read from JSON are stamped with the time they were read.
+	#[serde(skip, default = "Instant::now")]
+	pub captured_at: Instant,
+}
+impl TrackingFrame {
+	/// A frame captured now
+	#[must_use]
+	pub fn new(hand: Option<TrackingFrameHand>, second_hand: Option<TrackingFrameHand>) -> Self {
+		Self { hand, second_hand, captured_at: Instant::now() }
+	}
+
+	/// All tracked hands, the one tracked the longest first
+	pub fn hands(&self) -> impl Iterator<Item = &TrackingFrameHand> {
+		self.hand.iter().chain(self.second_hand.iter())
+	}
+
+	/// The hands the focal point follows with `tracked_hand`, in the order their samples are alternated
+	#[must_use]
+	pub fn tracked_hands(&self, tracked_hand: TrackedHand) -> Vec<&TrackingFrameHand> {
+		match tracked_hand {
+			TrackedHand::FirstSeen => self.hand.iter().collect(),
+			TrackedHand::Left => self.hands().filter(|hand| hand.chirality == TrackingFrameHandChirality::Left).take(1).collect(),
+			TrackedHand::Right => self.hands().filter(|hand| hand.chirality == TrackingFrameHandChirality::Right).take(1).collect(),
+			TrackedHand::Both => self.hands().collect(),
+		}
+	}
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
@@ -28,7 +166,7 @@ pub struct TrackingFramePalm {
 	pub direction: pattern_evaluator::MAHCoordsConst,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 pub enum TrackingFrameHandChirality {
 	Right = 0,
 	Left = 1,
@@ -45,4 +183,27 @@ pub struct TrackingFrameBone {
 	pub end: pattern_evaluator::MAHCoordsConst,
 	/// The average width of the flesh around the bone in millimeters.
 	pub width: f64,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_tracked_hands() {
+		let position = pattern_evaluator::MAHCoordsConst { x: 0.0, y: 0.0, z: 200.0 };
+		let left = synthetic::SyntheticTrackingSource::new(SyntheticMotion::Static { position: position.clone() }, 90.0)
+			.with_second_hand(Some(SyntheticMotion::Static { position }))
+			.frame_at(0.0).second_hand.unwrap();
+		let frame = TrackingFrame::new(None, Some(left));
+		assert!(frame.tracked_hands(TrackedHand::FirstSeen).is_empty());
+		assert_eq!(frame.tracked_hands(TrackedHand::Left).len(), 1);
+		assert!(frame.tracked_hands(TrackedHand::Right).is_empty());
+
+		let right = synthetic::SyntheticTrackingSource::new(SyntheticMotion::default(), 90.0).frame_at(0.0).hand.unwrap();
+		let frame = TrackingFrame { hand: Some(right), ..frame };
+		let chiralities = |tracked_hand| frame.tracked_hands(tracked_hand).iter().map(|hand| hand.chirality).collect::<Vec<_>>();
+		assert_eq!(chiralities(TrackedHand::FirstSeen), vec![TrackingFrameHandChirality::Right]);
+		assert_eq!(chiralities(TrackedHand::Left), vec![TrackingFrameHandChirality::Left]);
+		assert_eq!(chiralities(TrackedHand::Both), vec![TrackingFrameHandChirality::Right, TrackingFrameHandChirality::Left]);
+	}
+}
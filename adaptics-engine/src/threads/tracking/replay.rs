@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::util::AdapticsError;
+
+use super::source::{TrackingContext, TrackingSource};
+use super::TrackingFrame;
+
+/// One line of a tracking log, e.g. `{ "time": 0.011, "tracking_frame": { "hand": null } }`.
+/// Other fields are ignored, and lines without a `tracking_frame` are skipped, so session logs can be replayed (see [`crate::SessionLogLine`]).
+#[derive(Deserialize)]
+struct TrackingLogLine {
+	/// Seconds since the start of the recording
+	time: f64,
+	#[serde(default)]
+	tracking_frame: Option<TrackingFrame>,
+}
+
+/// Reads the frames of a JSON lines tracking log, with the time of each frame in seconds since the start of the recording
+pub(crate) fn read_tracking_log(reader: impl BufRead) -> Result<Vec<(f64, TrackingFrame)>, AdapticsError> {
+	let mut frames = Vec::new();
+	for (i, line) in reader.lines().enumerate() {
+		let line = line?;
+		if line.trim().is_empty() { continue; }
+		let TrackingLogLine { time, tracking_frame } = serde_json::from_str(&line).map_err(|e| AdapticsError::new(&format!("line {}: {e}", i + 1)))?;
+		if let Some(tracking_frame) = tracking_frame {
+			frames.push((time, tracking_frame));
+		}
+	}
+	Ok(frames)
+}
+
+/// Replays the frames of a JSON lines tracking log at the times they were recorded
+pub struct ReplayTrackingSource {
+	path: PathBuf,
+	looping: bool,
+	frames: Vec<(f64, TrackingFrame)>,
+}
+impl ReplayTrackingSource {
+	#[must_use]
+	pub fn new(path: PathBuf) -> Self {
+		Self { path, looping: false, frames: Vec::new() }
+	}
+	/// Restarts from the first frame after the last one, instead of stopping
+	#[must_use]
+	pub fn with_loop(mut self, looping: bool) -> Self {
+		self.looping = looping;
+		self
+	}
+}
+impl TrackingSource for ReplayTrackingSource {
+	fn name(&self) -> String { "replay".to_string() }
+
+	fn start(&mut self) -> Result<(), AdapticsError> {
+		let file = File::open(&self.path).map_err(|e| AdapticsError::new(&format!("failed to open tracking log '{}': {e}", self.path.display())))?;
+		self.frames = read_tracking_log(BufReader::new(file)).map_err(|e| AdapticsError::new(&format!("invalid tracking log '{}': {e}", self.path.display())))?;
+		let Some((first_time, _)) = self.frames.first() else { return Err(AdapticsError::new(&format!("tracking log '{}' contains no tracking frames", self.path.display()))); };
+		let first_time = *first_time;
+		if self.looping && self.frames.last().is_some_and(|(last_time, _)| *last_time <= first_time) {
+			return Err(AdapticsError::new("looping a tracking log requires frames recorded over more than 0 seconds"));
+		}
+		Ok(())
+	}
+
+	fn run(&mut self, ctx: &TrackingContext) -> Result<(), AdapticsError> {
+		let Some((first_time, _)) = self.frames.first() else { return Ok(()) };
+		let first_time = *first_time;
+		let mut loop_start = Instant::now();
+		loop {
+			for (time, tracking_frame) in &self.frames {
+				let frame_at = loop_start + Duration::from_secs_f64((time - first_time).max(0.0));
+				if ctx.wait_for_stop(frame_at.saturating_duration_since(Instant::now())) { return Ok(()); }
+				ctx.send_frame(TrackingFrame { captured_at: frame_at, ..tracking_frame.clone() });
+			}
+			if !self.looping { break; }
+			let (last_time, _) = self.frames[self.frames.len() - 1];
+			loop_start += Duration::from_secs_f64(last_time - first_time);
+		}
+		println!("tracking replay finished");
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_read_tracking_log() {
+		let log = concat!(
+			r#"{ "time": 0.0, "tracking_frame": { "hand": null } }"#, "\n",
+			r#"{ "time": 0.005, "playback_update": {} }"#, "\n",
+			"\n",
+			r#"{ "time": 0.01, "tracking_frame": { "hand": null } }"#, "\n",
+		);
+		let frames = read_tracking_log(log.as_bytes()).unwrap();
+		assert_eq!(frames.iter().map(|(time, _)| *time).collect::<Vec<_>>(), vec![0.0, 0.01]);
+
+		let err = read_tracking_log(r#"{ "tracking_frame": { "hand": null } }"#.as_bytes()).unwrap_err();
+		assert!(err.to_string().starts_with("line 1:"), "{err}");
+	}
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam_channel::TrySendError;
+
+use crate::{threads::net::websocket::AdapticsWSServerMessage, telemetry::Telemetry, util::AdapticsError, config::DebugConfig, session_log::{SessionLogger, SessionLogEntry}};
+
+use super::TrackingFrame;
+
+/// Handle given to tracking sources to deliver frames to the pattern-eval thread (and websocket clients).
+pub struct TrackingContext {
+	tracking_data_tx: crossbeam_channel::Sender<TrackingFrame>,
+	tracking_data_ws_tx: Option<crossbeam_channel::Sender<AdapticsWSServerMessage>>,
+	end_tracking_rx: crossbeam_channel::Receiver<()>,
+	telemetry: Arc<Telemetry>,
+	debug: DebugConfig,
+	session_log: Option<SessionLogger>,
+}
+impl TrackingContext {
+	pub(crate) fn new(
+		tracking_data_tx: crossbeam_channel::Sender<TrackingFrame>,
+		tracking_data_ws_tx: Option<crossbeam_channel::Sender<AdapticsWSServerMessage>>,
+		end_tracking_rx: crossbeam_channel::Receiver<()>,
+		telemetry: Arc<Telemetry>,
+		debug: DebugConfig,
+		session_log: Option<SessionLogger>,
+	) -> Self {
+		Self { tracking_data_tx, tracking_data_ws_tx, end_tracking_rx, telemetry, debug, session_log }
+	}
+
+	/// Delivers a frame to the pattern-eval thread and websocket clients, and records it in the session log.
+	/// Frames are dropped (and counted in telemetry) if the previous frame was not consumed yet.
+	pub fn send_frame(&self, tracking_frame: TrackingFrame) {
+		self.telemetry.record_tracking_frame();
+		if let Some(session_log) = &self.session_log { session_log.log(SessionLogEntry::TrackingFrame(tracking_frame.clone())); }
+		match self.tracking_data_tx.try_send(tracking_frame.clone()) {
+			Ok(()) => {},
+			Err(TrySendError::Disconnected(_)) => {}, // should_stop() should return true, so the source will exit
+			Err(TrySendError::Full(_)) => { self.telemetry.record_dropped_tracking_frame(); if self.debug.log_lag_events { println!("playback thread lagged [tracking]"); } }, // we are sending too fast for playback thread, so we can just drop this frame
+		}
+		if let Some(tracking_data_ws_tx) = self.tracking_data_ws_tx.as_ref() {
+			match tracking_data_ws_tx.try_send(AdapticsWSServerMessage::TrackingData { tracking_frame }) {
+				Ok(()) => {},
+				Err(TrySendError::Disconnected(_)) => {}, // should_stop() should return true, so the source will exit
+				Err(TrySendError::Full(_)) => { if self.debug.log_lag_events { println!("network thread lagged [tracking]"); } }, // we are sending too fast for network thread, so we can just drop this frame
+			}
+		}
+	}
+
+	/// Returns true once the engine has asked the source to stop
+	#[must_use]
+	pub fn should_stop(&self) -> bool {
+		self.end_tracking_rx.try_recv().is_ok()
+	}
+	/// Blocks until the engine asks the source to stop, or `timeout` elapses. Returns true if the source should stop.
+	#[must_use]
+	pub fn wait_for_stop(&self, timeout: Duration) -> bool {
+		!matches!(self.end_tracking_rx.recv_timeout(timeout), Err(crossbeam_channel::RecvTimeoutError::Timeout))
+	}
+}
+
+/// Produces hand tracking frames, e.g. from a tracking device or a recording.
+pub trait TrackingSource: Send {
+	/// Human readable name of the source, also used to name the tracking thread
+	fn name(&self) -> String;
+
+	/// Called on the tracking thread before [`TrackingSource::run`], e.g. to connect to the device.
+	fn start(&mut self) -> Result<(), AdapticsError> { Ok(()) }
+
+	/// Sends frames with [`TrackingContext::send_frame`] until [`TrackingContext::should_stop`].
+	fn run(&mut self, ctx: &TrackingContext) -> Result<(), AdapticsError>;
+}
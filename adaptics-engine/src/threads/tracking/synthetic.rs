@@ -0,0 +1,165 @@
+use std::f64::consts::TAU;
+use std::time::{Duration, Instant};
+
+use pattern_evaluator::MAHCoordsConst;
+use serde::{Deserialize, Serialize};
+
+use crate::util::AdapticsError;
+
+use super::source::{TrackingContext, TrackingSource};
+use super::{TrackingFrame, TrackingFrameHand, TrackingFrameHandChirality, TrackingFramePalm, TrackingFrameDigit, TrackingFrameBone};
+
+/// Scripted palm motion of the [`SyntheticTrackingSource`], in the haptic coordinate system (in mm). Periods are in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "motion", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SyntheticMotion {
+	/// The palm rests at `position`
+	Static { position: MAHCoordsConst },
+	/// The palm moves along a horizontal circle around `center`
+	Circle { center: MAHCoordsConst, radius: f64, period: f64 },
+	/// The palm moves from `near` to `far` and back
+	ApproachRetreat { near: MAHCoordsConst, far: MAHCoordsConst, period: f64 },
+}
+impl Default for SyntheticMotion {
+	fn default() -> Self {
+		Self::Circle { center: MAHCoordsConst { x: 0.0, y: 0.0, z: 200.0 }, radius: 40.0, period: 4.0 }
+	}
+}
+impl SyntheticMotion {
+	/// `field` is the config key of the motion, used in error messages
+	pub(crate) fn validate(&self, field: &str) -> Result<(), AdapticsError> {
+		match self {
+			Self::Static { .. } => Ok(()),
+			Self::Circle { period, .. } | Self::ApproachRetreat { period, .. } if *period <= 0.0 => Err(AdapticsError::new(&format!("{field}.period must be greater than 0"))),
+			Self::Circle { radius, .. } if *radius < 0.0 => Err(AdapticsError::new(&format!("{field}.radius must not be negative"))),
+			Self::Circle { .. } | Self::ApproachRetreat { .. } => Ok(()),
+		}
+	}
+
+	/// Position of the palm `t` seconds after the motion started
+	#[must_use]
+	pub fn palm_position(&self, t: f64) -> MAHCoordsConst {
+		match self {
+			Self::Static { position } => position.clone(),
+			Self::Circle { center, radius, period } => {
+				let angle = TAU * t / period;
+				MAHCoordsConst { x: center.x + radius * angle.cos(), y: center.y + radius * angle.sin(), z: center.z }
+			},
+			Self::ApproachRetreat { near, far, period } => {
+				let progress = (1.0 - (TAU * t / period).cos()) / 2.0; // 0 at near, 1 at far
+				MAHCoordsConst {
+					x: near.x + (far.x - near.x) * progress,
+					y: near.y + (far.y - near.y) * progress,
+					z: near.z + (far.z - near.z) * progress,
+				}
+			},
+		}
+	}
+}
+
+/// Offsets of each digit's joints (base of the metacarpal, knuckle, two finger joints, tip) from the palm center of a flat right hand, in mm
+const DIGIT_JOINTS: [[(f64, f64); 5]; 5] = [
+	[(-20.0, -30.0), (-40.0, -10.0), (-60.0, 15.0), (-72.0, 35.0), (-80.0, 52.0)], // thumb
+	[(-12.0, -35.0), (-25.0, 35.0), (-27.0, 75.0), (-28.0, 100.0), (-29.0, 120.0)], // index
+	[(-2.0, -35.0), (-5.0, 40.0), (-5.0, 84.0), (-5.0, 112.0), (-5.0, 134.0)], // middle
+	[(8.0, -35.0), (15.0, 36.0), (17.0, 76.0), (18.0, 102.0), (19.0, 123.0)], // ring
+	[(18.0, -33.0), (33.0, 30.0), (37.0, 60.0), (39.0, 78.0), (41.0, 95.0)], // pinky
+];
+
+/// A flat hand with the palm at `position`, facing down towards the haptic device and with the fingers pointing along +y.
+/// A left hand is the mirror image of the right hand along x.
+fn flat_hand(position: &MAHCoordsConst, chirality: TrackingFrameHandChirality) -> TrackingFrameHand {
+	let mirror = if chirality == TrackingFrameHandChirality::Left { -1.0 } else { 1.0 };
+	let joint = |(x, y): (f64, f64)| MAHCoordsConst { x: position.x + mirror * x, y: position.y + y, z: position.z };
+	TrackingFrameHand {
+		chirality,
+		palm: TrackingFramePalm {
+			position: position.clone(),
+			width: 85.0,
+			normal: MAHCoordsConst { x: 0.0, y: 0.0, z: -1.0 },
+			direction: MAHCoordsConst { x: 0.0, y: 1.0, z: 0.0 },
+		},
+		digits: Box::new(DIGIT_JOINTS.map(|joints| TrackingFrameDigit {
+			bones: [0, 1, 2, 3].map(|i| TrackingFrameBone { start: joint(joints[i]), end: joint(joints[i + 1]), width: 18.0 }),
+		})),
+	}
+}
+
+/// Generates frames of a right hand following a [`SyntheticMotion`] (and optionally a left hand following another one), for developing and testing without a tracking device
+pub struct SyntheticTrackingSource {
+	motion: SyntheticMotion,
+	second_hand_motion: Option<SyntheticMotion>,
+	frame_rate: f64,
+}
+impl SyntheticTrackingSource {
+	#[must_use]
+	pub fn new(motion: SyntheticMotion, frame_rate: f64) -> Self {
+		Self { motion, second_hand_motion: None, frame_rate }
+	}
+	/// Adds a left hand following `motion`, as the second hand of each frame
+	#[must_use]
+	pub fn with_second_hand(mut self, motion: Option<SyntheticMotion>) -> Self {
+		self.second_hand_motion = motion;
+		self
+	}
+
+	/// The frame `t` seconds after the source started
+	#[must_use]
+	pub fn frame_at(&self, t: f64) -> TrackingFrame {
+		TrackingFrame::new(
+			Some(flat_hand(&self.motion.palm_position(t), TrackingFrameHandChirality::Right)),
+			self.second_hand_motion.as_ref().map(|motion| flat_hand(&motion.palm_position(t), TrackingFrameHandChirality::Left)),
+		)
+	}
+}
+impl TrackingSource for SyntheticTrackingSource {
+	fn name(&self) -> String { "synthetic".to_string() }
+
+	fn run(&mut self, ctx: &TrackingContext) -> Result<(), AdapticsError> {
+		let frame_dur = Duration::from_secs_f64(1.0 / self.frame_rate);
+		let start = Instant::now();
+		let mut next_frame_at = start;
+		loop {
+			ctx.send_frame(TrackingFrame { captured_at: next_frame_at, ..self.frame_at(next_frame_at.duration_since(start).as_secs_f64()) });
+			next_frame_at += frame_dur;
+			if ctx.wait_for_stop(next_frame_at.saturating_duration_since(Instant::now())) { break; }
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_near(a: &MAHCoordsConst, b: &MAHCoordsConst) {
+		assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9 && (a.z - b.z).abs() < 1e-9, "{a:?} != {b:?}");
+	}
+
+	#[test]
+	fn test_synthetic_motion() {
+		let circle = SyntheticMotion::Circle { center: MAHCoordsConst { x: 0.0, y: 10.0, z: 200.0 }, radius: 50.0, period: 2.0 };
+		assert_near(&circle.palm_position(0.0), &MAHCoordsConst { x: 50.0, y: 10.0, z: 200.0 });
+		assert_near(&circle.palm_position(0.5), &MAHCoordsConst { x: 0.0, y: 60.0, z: 200.0 });
+		assert_near(&circle.palm_position(2.0), &circle.palm_position(0.0));
+
+		let approach = SyntheticMotion::ApproachRetreat { near: MAHCoordsConst { x: 0.0, y: 0.0, z: 100.0 }, far: MAHCoordsConst { x: 0.0, y: 0.0, z: 300.0 }, period: 4.0 };
+		assert_near(&approach.palm_position(0.0), &MAHCoordsConst { x: 0.0, y: 0.0, z: 100.0 });
+		assert_near(&approach.palm_position(2.0), &MAHCoordsConst { x: 0.0, y: 0.0, z: 300.0 });
+		assert_near(&approach.palm_position(3.0), &MAHCoordsConst { x: 0.0, y: 0.0, z: 200.0 });
+
+		let source = SyntheticTrackingSource::new(SyntheticMotion::Static { position: MAHCoordsConst { x: 5.0, y: 5.0, z: 150.0 } }, 100.0);
+		let hand = source.frame_at(1.0).hand.unwrap();
+		assert_near(&hand.palm.position, &MAHCoordsConst { x: 5.0, y: 5.0, z: 150.0 });
+		assert!(hand.digits.iter().all(|digit| digit.bones.iter().all(|bone| (bone.start.z - 150.0).abs() < 1e-9 && bone.end.y > bone.start.y)));
+		assert!(source.frame_at(1.0).second_hand.is_none());
+
+		let source = source.with_second_hand(Some(SyntheticMotion::Static { position: MAHCoordsConst { x: -100.0, y: 5.0, z: 150.0 } }));
+		let frame = source.frame_at(1.0);
+		let left_hand = frame.second_hand.unwrap();
+		assert_eq!(left_hand.chirality, TrackingFrameHandChirality::Left);
+		assert!(left_hand.digits[0].bones[3].end.x > left_hand.palm.position.x, "left thumb points to +x");
+
+		assert!(SyntheticMotion::Circle { center: MAHCoordsConst { x: 0.0, y: 0.0, z: 200.0 }, radius: 50.0, period: 0.0 }.validate("tracking.synthetic_motion").is_err());
+	}
+}
@@ -0,0 +1,63 @@
+//! Test patterns shared by the integration tests, and by the unit tests in `src/lib.rs`
+#![allow(dead_code)] // every test crate uses a different part
+
+use std::collections::HashMap;
+
+use pattern_evaluator::{CoordsWithTransition, DataFormatRevision, IntensityWithTransition, MAHCoordsConst, MAHDynamicF64, MAHIntensity, MAHKeyframe, MAHKeyframeStandard, MAHTime, MAHTransition, MidAirHapticsAnimationFileFormat, MidAirHapticsAnimationFileFormatDataFormatName};
+
+/// A pattern that holds the focal point at one position for `length` milliseconds
+pub struct TestPattern {
+	name: String,
+	length: MAHTime,
+	coords: MAHCoordsConst,
+	intensity: Option<MAHDynamicF64>,
+}
+impl TestPattern {
+	/// An empty pattern (without keyframes) at the origin
+	#[must_use]
+	pub fn new(name: &str) -> Self {
+		Self { name: name.to_string(), length: 0.0, coords: MAHCoordsConst { x: 0.0, y: 0.0, z: 0.0 }, intensity: None }
+	}
+	/// Adds keyframes at 0 and `length` milliseconds (none if `length` is 0)
+	#[must_use]
+	pub fn length(mut self, length: MAHTime) -> Self {
+		self.length = length;
+		self
+	}
+	#[must_use]
+	pub fn coords(mut self, x: f64, y: f64, z: f64) -> Self {
+		self.coords = MAHCoordsConst { x, y, z };
+		self
+	}
+	/// Constant intensity, e.g. `MAHDynamicF64::F64(1.0)` or a user parameter
+	#[must_use]
+	pub fn intensity(mut self, intensity: MAHDynamicF64) -> Self {
+		self.intensity = Some(intensity);
+		self
+	}
+
+	#[must_use]
+	pub fn build(&self) -> MidAirHapticsAnimationFileFormat {
+		let keyframe = |time, intensity: Option<&MAHDynamicF64>| MAHKeyframe::Standard(MAHKeyframeStandard {
+			time,
+			brush: None,
+			intensity: intensity.map(|value| IntensityWithTransition { intensity: MAHIntensity::Constant { value: value.clone() }, transition: MAHTransition::Linear { } }),
+			coords: CoordsWithTransition { coords: self.coords.clone(), transition: MAHTransition::Linear { } },
+			cjumps: vec![],
+		});
+		MidAirHapticsAnimationFileFormat {
+			data_format: MidAirHapticsAnimationFileFormatDataFormatName::DataFormat,
+			revision: DataFormatRevision::CurrentRevision,
+			name: self.name.clone(),
+			keyframes: if self.length > 0.0 { vec![keyframe(0.0, self.intensity.as_ref()), keyframe(self.length, None)] } else { vec![] },
+			pattern_transform: Default::default(),
+			user_parameter_definitions: HashMap::new(),
+			user_parameter_automation: HashMap::new(),
+		}
+	}
+
+	#[must_use]
+	pub fn json(&self) -> String {
+		serde_json::to_string(&self.build()).unwrap()
+	}
+}
@@ -65,9 +65,13 @@ fn bindings_c(path: &Path) {
 fn bindings_websocket(path: &Path) {
 	std::fs::create_dir_all(path).ok();
 
-	use adaptics_engine::AdapticsWSServerMessage;
+	use adaptics_engine::{AdapticsWSServerMessage, AdapticsWSRequest};
 	let schema = schemars::schema_for!(AdapticsWSServerMessage);
 	// let schema_filename = std::env::var("ADAPTICS_ENGINE_CLI_WS_SCHEMA_FILENAME").unwrap();
 	let schema_filename = path.join("AdapticsWSServerMessage.json");
 	std::fs::write(schema_filename, serde_json::to_string_pretty(&schema).unwrap()).unwrap();
+
+	let schema = schemars::schema_for!(AdapticsWSRequest);
+	let schema_filename = path.join("AdapticsWSRequest.json");
+	std::fs::write(schema_filename, serde_json::to_string_pretty(&schema).unwrap()).unwrap();
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use adaptics_engine::output::{MockOutput, OutputBackend, OutputCapabilities, OutputKind, StreamingContext};
+use adaptics_engine::{AdapticsEngineHandle, AdapticsEngineEvent, AdapticsError, PatternEvalUpdate, QueueEntry, CALLBACK_RATE, DEVICE_UPDATE_RATE, DEFAULT_VOICE_ID};
+use pattern_evaluator::*;
+
+mod common;
+use common::TestPattern;
+
+fn entry(id: &str, length: MAHTime, loops: u32) -> QueueEntry {
+	QueueEntry { id: id.to_string(), pattern_json: TestPattern::new(id).length(length).json(), gap_ms: 0.0, loops, user_parameters: HashMap::new() }
+}
+
+fn next_event(engine: &AdapticsEngineHandle) -> String {
+	match engine.recv_event_timeout(Duration::from_secs(1)).expect("no event") {
+		AdapticsEngineEvent::QueueEntryStarted { id } => format!("started {id}"),
+		AdapticsEngineEvent::QueueEntryFinished { id, completed } => format!("finished {id} {completed}"),
+		AdapticsEngineEvent::VoiceFinished { voice_id } => format!("voice finished {voice_id}"),
+		AdapticsEngineEvent::Error { message } => format!("error {message}"),
+	}
+}
+
+/// Sends the intensity of the last evaluation of each batch
+struct IntensityOutput(mpsc::Sender<f64>);
+impl OutputBackend for IntensityOutput {
+	fn sample_rate(&self) -> u64 { DEVICE_UPDATE_RATE }
+	fn callback_rate(&self) -> f64 { CALLBACK_RATE }
+	fn capabilities(&self) -> OutputCapabilities {
+		OutputCapabilities { name: "intensity".to_string(), kind: OutputKind::Virtual, num_actuators: None }
+	}
+	fn emit_batch(&mut self, evals: &[BrushAtAnimLocalTime], _ctx: &StreamingContext) -> Result<(), AdapticsError> {
+		if let Some(eval) = evals.last() { self.0.send(eval.ul_control_point.intensity).ok(); }
+		Ok(())
+	}
+}
+
+#[test]
+fn test_stop_default_voice_during_queue_entry() {
+	let engine = AdapticsEngineHandle::start_with_output(Box::new(MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry("long", 60_000.0, 1) }).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry("next", 0.0, 1) }).unwrap();
+	assert_eq!(next_event(&engine), "started long");
+
+	// stopping the default voice in the middle of the entry ends it, and the queue continues
+	engine.update(PatternEvalUpdate::VoiceStop { voice_id: DEFAULT_VOICE_ID }).unwrap();
+	assert_eq!(next_event(&engine), "finished long false");
+	assert_eq!(next_event(&engine), "started next");
+	assert_eq!(next_event(&engine), "finished next true");
+
+	engine.shutdown().unwrap();
+}
+
+#[test]
+fn test_stop_clears_queue_and_voices() {
+	let engine = AdapticsEngineHandle::start_with_output(Box::new(MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+	engine.update(PatternEvalUpdate::VoicePlay { voice_id: Some(5), pattern_json: TestPattern::new("voice").length(60_000.0).json(), priority: 1, user_parameters: HashMap::new() }).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry("long", 60_000.0, 1) }).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry("next", 0.0, 1) }).unwrap();
+	assert_eq!(next_event(&engine), "started long");
+
+	engine.update(PatternEvalUpdate::Stop {}).unwrap();
+	assert_eq!(next_event(&engine), "finished long false");
+	assert_eq!(next_event(&engine), "voice finished 5");
+	assert!(engine.recv_event_timeout(Duration::from_millis(100)).is_none(), "the queue should be cleared");
+
+	engine.shutdown().unwrap();
+}
+
+#[test]
+fn test_reject_endless_empty_queue_entry() {
+	let engine = AdapticsEngineHandle::start_with_output(Box::new(MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry("empty", 0.0, 0) }).unwrap();
+	assert!(next_event(&engine).starts_with("error"));
+	assert!(engine.recv_event_timeout(Duration::from_millis(100)).is_none(), "the entry should not be started");
+
+	engine.shutdown().unwrap();
+}
+
+#[test]
+fn test_queue_entry_user_parameters_are_restored() {
+	let (intensity_tx, intensity_rx) = mpsc::channel();
+	let engine = AdapticsEngineHandle::start_with_output(Box::new(IntensityOutput(intensity_tx))).unwrap();
+	let latest_intensity = || {
+		while intensity_rx.try_recv().is_ok() {}
+		intensity_rx.recv_timeout(Duration::from_secs(1)).unwrap()
+	};
+	let p = || MAHDynamicF64::Param("p".to_string());
+
+	engine.update(PatternEvalUpdate::UserParameter { name: "p".to_string(), value: 0.3 }).unwrap();
+	let mut entry_a = QueueEntry { id: "a".to_string(), pattern_json: TestPattern::new("a").length(200.0).intensity(p()).json(), gap_ms: 0.0, loops: 1, user_parameters: HashMap::new() };
+	entry_a.user_parameters.insert("p".to_string(), 0.8);
+	let entry_b = QueueEntry { id: "b".to_string(), pattern_json: TestPattern::new("b").length(60_000.0).intensity(p()).json(), gap_ms: 0.0, loops: 1, user_parameters: HashMap::new() };
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry_a }).unwrap();
+	engine.update(PatternEvalUpdate::QueueEnqueue { entry: entry_b }).unwrap();
+
+	assert_eq!(next_event(&engine), "started a");
+	assert!((latest_intensity() - 0.8).abs() < 1e-9, "entry a overrides p");
+	assert_eq!(next_event(&engine), "finished a true");
+	assert_eq!(next_event(&engine), "started b");
+	assert!((latest_intensity() - 0.3).abs() < 1e-9, "entry b does not override p, so the previous value is restored");
+
+	engine.shutdown().unwrap();
+}
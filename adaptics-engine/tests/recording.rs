@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use adaptics_engine::output::{RecordingClock, RecordingFormat, RecordingOutput, RECORDING_BINARY_MAGIC, RECORDING_BINARY_RECORD_LEN};
+use adaptics_engine::{AdapticsEngineHandle, EngineConfig, PatternEvalUpdate, CALLBACK_RATE, DEVICE_UPDATE_RATE};
+use pattern_evaluator::*;
+
+mod common;
+use common::TestPattern;
+
+fn temp_path(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("adaptics-engine-test-{}-{name}", std::process::id()))
+}
+
+/// Records the test pattern, playing from the first sample
+fn record(output: RecordingOutput, record_for: Duration) {
+	let play = vec![
+		PatternEvalUpdate::Pattern { pattern_json: TestPattern::new("recording-test").length(10_000.0).coords(0.0, 0.0, 200.0).intensity(MAHDynamicF64::F64(1.0)).json(), crossfade_ms: None, path_interpolation_ms: None },
+		PatternEvalUpdate::Play { pattern_time: 0.0 },
+	];
+	let engine = AdapticsEngineHandle::start_with_updates(Box::new(output), &EngineConfig::default(), play).unwrap();
+	std::thread::sleep(record_for);
+	engine.shutdown().unwrap();
+}
+
+fn read_csv_rows(path: &Path) -> Vec<Vec<f64>> {
+	let csv = std::fs::read_to_string(path).unwrap();
+	let mut lines = csv.lines();
+	assert_eq!(lines.next(), Some("sample_index,pattern_time,x,y,z,intensity,stop"));
+	lines.map(|l| l.split(',').map(|v| v.parse().unwrap()).collect()).collect()
+}
+
+#[test]
+fn test_record_csv() {
+	let path = temp_path("record.csv");
+	record(RecordingOutput::new(path.clone(), RecordingFormat::Csv, DEVICE_UPDATE_RATE, CALLBACK_RATE), Duration::from_millis(200));
+
+	let rows = read_csv_rows(&path);
+	assert!(!rows.is_empty());
+	#[allow(clippy::cast_precision_loss)]
+	for (i, row) in rows.iter().enumerate() {
+		assert_eq!(row.len(), 7);
+		assert!((row[0] - i as f64).abs() < f64::EPSILON, "sample_index should count every sample");
+	}
+	let playing: Vec<_> = rows.iter().filter(|row| row[5] > 0.0).collect();
+	assert!(!playing.is_empty(), "pattern should have played");
+	for row in &playing {
+		assert_eq!(row[2..5], playing[0][2..5], "pattern has a constant position");
+		assert!((row[5] - 1.0).abs() < 1e-9, "intensity: {}", row[5]);
+	}
+	assert!(playing.windows(2).all(|w| w[1][1] >= w[0][1]), "pattern_time should not decrease");
+
+	std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_record_virtual_clock() {
+	let path = temp_path("virtual.csv");
+	let output = RecordingOutput::new(path.clone(), RecordingFormat::Csv, DEVICE_UPDATE_RATE, CALLBACK_RATE)
+		.with_clock(RecordingClock::Virtual { duration: Duration::from_millis(50) });
+	record(output, Duration::from_millis(100));
+
+	// exactly 50ms of samples, regardless of how long the engine ran
+	let rows = read_csv_rows(&path);
+	assert_eq!(rows.len(), 1000);
+	// the pattern plays from the first sample, since it was started before streaming
+	assert!(rows.iter().all(|row| (row[5] - 1.0).abs() < 1e-9), "intensity should be 1 in every sample");
+	assert!((0.0..20.0).contains(&rows[0][1]), "pattern_time of the first sample: {}", rows[0][1]);
+	#[allow(clippy::cast_precision_loss)]
+	let sample_ms = 1000.0 / DEVICE_UPDATE_RATE as f64;
+	assert!(rows.windows(2).all(|w| (w[1][1] - w[0][1] - sample_ms).abs() < 1e-6), "pattern_time should advance by one sample period per sample");
+
+	std::fs::remove_file(path).ok();
+}
+
+#[test]
+fn test_record_binary_rotation() {
+	let path = temp_path("rotate.bin");
+	let rotated = |n: usize| PathBuf::from(format!("{}.{n}", path.display()));
+	let max_file_bytes = 100 * RECORDING_BINARY_RECORD_LEN as u64;
+	let output = RecordingOutput::new(path.clone(), RecordingFormat::from_path(&path), DEVICE_UPDATE_RATE, CALLBACK_RATE)
+		.with_clock(RecordingClock::Virtual { duration: Duration::from_millis(50) })
+		.with_max_file_bytes(max_file_bytes, 2);
+	record(output, Duration::from_millis(100));
+
+	for file in [path.clone(), rotated(1), rotated(2)] {
+		let bytes = std::fs::read(&file).unwrap();
+		assert_eq!(&bytes[..8], RECORDING_BINARY_MAGIC);
+		assert_eq!((bytes.len() - 8) % RECORDING_BINARY_RECORD_LEN, 0);
+		assert!(bytes.len() as u64 <= max_file_bytes + RECORDING_BINARY_RECORD_LEN as u64);
+	}
+	assert!(!rotated(3).exists(), "only 2 rotated files should be kept");
+
+	let last_record = std::fs::read(&path).unwrap();
+	let last_record = &last_record[last_record.len() - RECORDING_BINARY_RECORD_LEN..];
+	assert_eq!(u64::from_le_bytes(last_record[..8].try_into().unwrap()), 999);
+
+	for file in [path.clone(), rotated(1), rotated(2)] {
+		std::fs::remove_file(file).ok();
+	}
+}
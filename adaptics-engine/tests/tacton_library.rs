@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use adaptics_engine::output::MockOutput;
+use adaptics_engine::{AdapticsEngineHandle, AdapticsEngineEvent, AdapticsWSQuery, AdapticsWSResponse, PatternEvalUpdate, TactonInfo, CALLBACK_RATE, DEVICE_UPDATE_RATE, TACTON_LIBRARY_POLL_INTERVAL};
+use pattern_evaluator::*;
+
+mod common;
+use common::TestPattern;
+
+fn write_tacton(path: &Path, name: &str) {
+	std::fs::write(path, TestPattern::new(name).json()).unwrap();
+}
+
+fn loaded_pattern_name(engine: &AdapticsEngineHandle) -> String {
+	let AdapticsWSResponse::Pattern { pattern_json } = engine.query(AdapticsWSQuery::GetPattern {}).unwrap() else { panic!("expected pattern") };
+	serde_json::from_str::<MidAirHapticsAnimationFileFormat>(&pattern_json).unwrap().name
+}
+
+/// Updates and queries are received on separate channels, so updates are not guaranteed to be applied before the next query
+fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+	let deadline = Instant::now() + timeout;
+	while !condition() {
+		if Instant::now() > deadline { return false; }
+		std::thread::sleep(Duration::from_millis(20));
+	}
+	true
+}
+
+#[test]
+fn test_play_tacton_hot_reload() {
+	let dir = std::env::temp_dir().join(format!("adaptics-engine-test-{}-tactons", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	write_tacton(&dir.join("pulse.adaptics"), "Pulse");
+
+	let mut engine = AdapticsEngineHandle::start_with_output(Box::new(MockOutput::new(DEVICE_UPDATE_RATE, CALLBACK_RATE))).unwrap();
+	engine.set_tacton_dir(dir.clone()).unwrap();
+	assert!(matches!(engine.query(AdapticsWSQuery::GetTactons {}).unwrap(),
+		AdapticsWSResponse::Tactons { tactons } if tactons == [TactonInfo { name: "Pulse".to_string(), file_name: "pulse.adaptics".to_string() }]));
+
+	engine.update(PatternEvalUpdate::PlayTacton { name: "pulse".to_string() }).unwrap();
+	assert!(wait_until(Duration::from_secs(1), || loaded_pattern_name(&engine) == "Pulse"));
+	engine.update(PatternEvalUpdate::PlayTacton { name: "missing".to_string() }).unwrap();
+	assert!(wait_until(Duration::from_secs(1), || matches!(engine.try_recv_event(), Some(AdapticsEngineEvent::Error { .. }))));
+	assert_eq!(loaded_pattern_name(&engine), "Pulse");
+
+	// the playing tacton is reloaded once the watcher notices the edit
+	write_tacton(&dir.join("pulse.adaptics"), "Edited Pulse");
+	assert!(wait_until(TACTON_LIBRARY_POLL_INTERVAL * 5, || loaded_pattern_name(&engine) == "Edited Pulse"), "edited tacton was not reloaded");
+
+	engine.shutdown().unwrap();
+	std::fs::remove_dir_all(dir).ok();
+}
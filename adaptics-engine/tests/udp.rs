@@ -0,0 +1,92 @@
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use adaptics_engine::output::{UdpFormat, UdpOutput, UDP_OSC_CONTROL_POINT_ADDRESS, UDP_RAW_HEADER_LEN, UDP_RAW_MAGIC, UDP_RAW_RECORD_LEN, UDP_RAW_VERSION};
+use adaptics_engine::{AdapticsEngineHandle, PatternEvalUpdate, CALLBACK_RATE, DEVICE_UPDATE_RATE};
+use pattern_evaluator::*;
+
+mod common;
+use common::TestPattern;
+
+/// Streams the test pattern to a local UDP listener and returns every received packet
+fn receive_packets(format: UdpFormat, decimation: u64, stream_for: Duration) -> Vec<Vec<u8>> {
+	let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+	listener.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+	let output = UdpOutput::new(listener.local_addr().unwrap(), format, DEVICE_UPDATE_RATE, CALLBACK_RATE).with_decimation(decimation);
+
+	let engine = AdapticsEngineHandle::start_with_output(Box::new(output)).unwrap();
+	engine.update(PatternEvalUpdate::Pattern { pattern_json: TestPattern::new("udp-test").length(10_000.0).coords(10.0, 20.0, 0.0).intensity(MAHDynamicF64::F64(1.0)).json(), crossfade_ms: None, path_interpolation_ms: None }).unwrap();
+	engine.update(PatternEvalUpdate::Play { pattern_time: 0.0 }).unwrap();
+	std::thread::sleep(stream_for);
+	engine.shutdown().unwrap();
+
+	let mut packets = Vec::new();
+	let mut buf = [0; 65536];
+	while let Ok(len) = listener.recv(&mut buf) {
+		packets.push(buf[..len].to_vec());
+	}
+	packets
+}
+
+fn read_f32_be(bytes: &[u8]) -> f32 { f32::from_be_bytes(bytes[..4].try_into().unwrap()) }
+fn read_i32_be(bytes: &[u8]) -> i32 { i32::from_be_bytes(bytes[..4].try_into().unwrap()) }
+
+#[test]
+fn test_udp_osc() {
+	let packets = receive_packets(UdpFormat::Osc, 1, Duration::from_millis(100));
+	assert!(!packets.is_empty());
+
+	let address = format!("{UDP_OSC_CONTROL_POINT_ADDRESS}\0");
+	let type_tags = ",ifffffi\0";
+	let args_offset = address.len().next_multiple_of(4) + type_tags.len().next_multiple_of(4);
+	let mut sample_indices = Vec::new();
+	let mut playing = 0;
+	for packet in &packets {
+		assert!(packet.len() <= 1400, "packets should not be fragmented");
+		assert_eq!(&packet[..8], b"#bundle\0");
+		let mut rest = &packet[16..];
+		while !rest.is_empty() {
+			let message_len = usize::try_from(read_i32_be(rest)).unwrap();
+			let message = &rest[4..4 + message_len];
+			assert!(message.starts_with(address.as_bytes()));
+			assert!(message[address.len().next_multiple_of(4)..].starts_with(type_tags.as_bytes()));
+			let args = &message[args_offset..];
+			sample_indices.push(read_i32_be(args));
+			if read_f32_be(&args[20..]) > 0.0 {
+				playing += 1;
+				assert!((read_f32_be(&args[8..]) - 10.0).abs() < 1e-3, "x");
+				assert!((read_f32_be(&args[12..]) - 20.0).abs() < 1e-3, "y");
+				assert!((read_f32_be(&args[16..]) - 200.0).abs() < 1e-3, "z (translated by the default pattern transform)");
+			}
+			rest = &rest[4 + message_len..];
+		}
+	}
+	assert!(playing > 0, "pattern should have played");
+	assert!(sample_indices.windows(2).all(|w| w[1] == w[0] + 1), "every sample should be sent in order");
+}
+
+#[test]
+fn test_udp_raw_decimation() {
+	let decimation = 20;
+	let packets = receive_packets(UdpFormat::Raw, decimation, Duration::from_millis(100));
+	assert!(!packets.is_empty());
+
+	let mut sample_indices = Vec::new();
+	for packet in &packets {
+		assert_eq!(&packet[..4], UDP_RAW_MAGIC);
+		assert_eq!(u16::from_le_bytes(packet[4..6].try_into().unwrap()), UDP_RAW_VERSION);
+		let count = usize::from(u16::from_le_bytes(packet[6..8].try_into().unwrap()));
+		assert_eq!(packet.len(), UDP_RAW_HEADER_LEN + count * UDP_RAW_RECORD_LEN);
+		let first_sample_index = u64::from_le_bytes(packet[8..16].try_into().unwrap());
+		sample_indices.extend((0..count as u64).map(|i| first_sample_index + i * decimation));
+
+		for record in packet[UDP_RAW_HEADER_LEN..].chunks(UDP_RAW_RECORD_LEN) {
+			let intensity = f32::from_le_bytes(record[20..24].try_into().unwrap());
+			if intensity > 0.0 {
+				assert!((f32::from_le_bytes(record[8..12].try_into().unwrap()) - 10.0).abs() < 1e-3, "x");
+			}
+		}
+	}
+	assert_eq!(sample_indices[0] % decimation, 0);
+	assert!(sample_indices.windows(2).all(|w| w[1] == w[0] + decimation), "only every {decimation}th sample should be sent");
+}
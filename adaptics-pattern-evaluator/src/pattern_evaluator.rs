@@ -52,6 +52,9 @@ pub fn try_parse_into_latest_version(mah_animation_json: &str) -> Result<String,
 impl PatternEvaluator {
     pub fn new(mut mah_animation: MidAirHapticsAnimationFileFormat) -> Self {
         mah_animation.keyframes.sort_by(|a, b| a.time().total_cmp(b.time()));
+        for track in mah_animation.user_parameter_automation.values_mut() {
+            track.sort_points();
+        }
 
         Self {
             mah_animation,
@@ -63,6 +66,43 @@ impl PatternEvaluator {
         Ok(PatternEvaluator::new(mah_animation))
     }
 
+    /// The pattern being evaluated (including any automation tracks set with [`PatternEvaluator::set_user_parameter_automation`])
+    pub fn mah_animation(&self) -> &MidAirHapticsAnimationFileFormat {
+        &self.mah_animation
+    }
+
+    /// Time of the last keyframe, or 0.0 if the pattern has no keyframes
+    pub fn end_time(&self) -> MAHTime {
+        self.mah_animation.keyframes.last().map_or(0.0, |kf| *kf.time())
+    }
+
+    pub fn user_parameter_definitions(&self) -> &UserParameterDefinitions {
+        &self.mah_animation.user_parameter_definitions
+    }
+
+    pub fn user_parameter_automation(&self) -> &UserParameterAutomation {
+        &self.mah_animation.user_parameter_automation
+    }
+
+    /// Replaces the automation tracks of the pattern (e.g. with tracks loaded from a sidecar file).
+    pub fn set_user_parameter_automation(&mut self, mut automation: UserParameterAutomation) {
+        for track in automation.values_mut() {
+            track.sort_points();
+        }
+        self.mah_animation.user_parameter_automation = automation;
+    }
+
+    /// Returns `user_parameters` with the values of any automated user parameters replaced by their automation value at playback time `time`
+    pub fn apply_user_parameter_automation(&self, time: MAHTime, user_parameters: &UserParameters) -> UserParameters {
+        let mut user_parameters = user_parameters.clone();
+        for (name, track) in &self.mah_animation.user_parameter_automation {
+            if let Some(value) = track.value_at(time) {
+                user_parameters.insert(name.clone(), value);
+            }
+        }
+        user_parameters
+    }
+
     fn get_kf_config_type(&self, t: MAHTime, prev: bool) -> MAHKeyframeConfig {
         let mut kfc = MAHKeyframeConfig::default();
         macro_rules! update_kfc {
@@ -350,7 +390,11 @@ impl PatternEvaluator {
     }
 
     pub fn eval_path_at_anim_local_time(&self, p: &PatternEvaluatorParameters, nep: &NextEvalParams) -> PathAtAnimLocalTime {
-        let dyn_up_info = UserParametersConstrained::from(&p.user_parameters, &self.mah_animation.user_parameter_definitions);
+        let dyn_up_info = if self.mah_animation.user_parameter_automation.is_empty() {
+            UserParametersConstrained::from(&p.user_parameters, &self.mah_animation.user_parameter_definitions)
+        } else {
+            UserParametersConstrained::from(&self.apply_user_parameter_automation(p.time, &p.user_parameters), &self.mah_animation.user_parameter_definitions)
+        };
 
         // apply playback_speed
         let (pattern_time, nep) = {
@@ -661,6 +705,28 @@ impl MAHKeyframe {
     }
 }
 
+impl MAHUserParameterAutomationTrack {
+    fn sort_points(&mut self) {
+        self.points.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// Evaluates the track at playback time `time`. Returns `None` if the track has no points.
+    ///
+    /// Expects `points` to be sorted by time (done by [`PatternEvaluator::new`]).
+    pub fn value_at(&self, time: MAHTime) -> Option<f64> {
+        let next_idx = self.points.partition_point(|point| point.time <= time);
+        match (next_idx.checked_sub(1).and_then(|i| self.points.get(i)), self.points.get(next_idx)) {
+            (Some(prev), Some(next)) => {
+                let (pf, nf) = PatternEvaluator::perform_transition_interp(time, prev.time, next.time, &prev.transition);
+                Some(pf * prev.value + nf * next.value)
+            },
+            (Some(prev), None) => Some(prev.value),
+            (None, Some(next)) => Some(next.value),
+            (None, None) => None,
+        }
+    }
+}
+
 impl MAHCondition {
     fn eval(&self, dyn_up_info: &DynUserParamInfo) -> bool {
         if let Some(user_param_value) = dyn_up_info.0.get(&self.parameter) {
@@ -752,16 +818,27 @@ impl UserParametersConstrained {
     fn from(user_parameters: &UserParameters, definitions: &UserParameterDefinitions) -> Self {
         let mut constrained_user_parameters = user_parameters.clone(); // use user_parameters as base, to keep params that do not have an explicit definition
         for (name, def) in definitions {
-            constrained_user_parameters.insert(name.clone(), user_parameters.get(name)
+            let value = user_parameters.get(name)
                 .unwrap_or(&def.default).clamp(
                     def.min.unwrap_or(f64::NEG_INFINITY),
                     def.max.unwrap_or(f64::INFINITY)
-                )
-            );
+                );
+            constrained_user_parameters.insert(name.clone(), def.param_type.as_ref().map_or(value, |pt| pt.constrain(value)));
         }
         Self(constrained_user_parameters)
     }
 }
+impl MAHUserParameterType {
+    fn constrain(&self, value: f64) -> f64 {
+        match self {
+            MAHUserParameterType::Continuous {} => value,
+            MAHUserParameterType::Integer {} => value.round(),
+            MAHUserParameterType::Boolean {} => if value >= 0.5 { 1.0 } else { 0.0 },
+            #[allow(clippy::cast_precision_loss)]
+            MAHUserParameterType::Enum { labels } => value.round().clamp(0.0, labels.len().saturating_sub(1) as f64),
+        }
+    }
+}
 type DynUserParamInfo = UserParametersConstrained;
 impl MAHDynamicF64 {
     fn to_f64(&self, dyn_up_info: &DynUserParamInfo) -> f64 {
@@ -835,12 +912,13 @@ mod tests {
             ],
             pattern_transform: Default::default(),
             user_parameter_definitions: HashMap::from([
-                ("param1".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: 1.0 }),
-                ("param2".to_string(), MAHUserParameterDefinition { default: 20.0, min: Some(0.0), max: Some(15.0), step: 15.0 }),
-                ("param3".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: -500.0 }),
-                ("param4".to_string(), MAHUserParameterDefinition { default: 75.0, min: Some(-100.0), max: Some(50.0), step: 13.0 }),
-                ("param5".to_string(), MAHUserParameterDefinition { default: 1.0, min: Some(0.0), max: Some(4.0), step: 0.05 }),
+                ("param1".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: 1.0, ..Default::default() }),
+                ("param2".to_string(), MAHUserParameterDefinition { default: 20.0, min: Some(0.0), max: Some(15.0), step: 15.0, ..Default::default() }),
+                ("param3".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: -500.0, ..Default::default() }),
+                ("param4".to_string(), MAHUserParameterDefinition { default: 75.0, min: Some(-100.0), max: Some(50.0), step: 13.0, ..Default::default() }),
+                ("param5".to_string(), MAHUserParameterDefinition { default: 1.0, min: Some(0.0), max: Some(4.0), step: 0.05, ..Default::default() }),
             ]),
+            user_parameter_automation: HashMap::new(),
         }
     }
 
@@ -857,10 +935,10 @@ mod tests {
             ("pD".to_string(), -50.0),
         ]);
         let user_parameter_definitions = HashMap::from_iter(vec![
-            ("pB".to_string(), MAHUserParameterDefinition { default: 20.0, min: Some(0.0), max: Some(15.0), step: 15.0 }),
-            ("pC".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: -500.0 }),
-            ("pD".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: 1.2048790 }),
-            ("pE".to_string(), MAHUserParameterDefinition { default: 12.0001, min: None, max: None, step: 0.05 }),
+            ("pB".to_string(), MAHUserParameterDefinition { default: 20.0, min: Some(0.0), max: Some(15.0), step: 15.0, ..Default::default() }),
+            ("pC".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: -500.0, ..Default::default() }),
+            ("pD".to_string(), MAHUserParameterDefinition { default: 0.0, min: Some(0.0), max: Some(10.0), step: 1.2048790, ..Default::default() }),
+            ("pE".to_string(), MAHUserParameterDefinition { default: 12.0001, min: None, max: None, step: 0.05, ..Default::default() }),
         ]);
         let dyn_up_info = UserParametersConstrained::from(&user_parameters, &user_parameter_definitions);
         // assert_eq!(dyn_up_info.0.len(), 5);
@@ -871,6 +949,27 @@ mod tests {
         assert_eq!(dyn_up_info.0["pE"], 12.0001);
     }
 
+    #[test]
+    fn test_constrain_user_params_by_type() {
+        let user_parameters = HashMap::from_iter(vec![
+            ("pInt".to_string(), 2.6),
+            ("pBool".to_string(), 0.4),
+            ("pEnum".to_string(), 7.0),
+            ("pCont".to_string(), 2.6),
+        ]);
+        let user_parameter_definitions = HashMap::from_iter(vec![
+            ("pInt".to_string(), MAHUserParameterDefinition { default: 0.0, min: None, max: None, step: 1.0, param_type: Some(MAHUserParameterType::Integer {}), ..Default::default() }),
+            ("pBool".to_string(), MAHUserParameterDefinition { default: 0.0, min: None, max: None, step: 1.0, param_type: Some(MAHUserParameterType::Boolean {}), ..Default::default() }),
+            ("pEnum".to_string(), MAHUserParameterDefinition { default: 0.0, min: None, max: None, step: 1.0, param_type: Some(MAHUserParameterType::Enum { labels: vec!["a".to_string(), "b".to_string(), "c".to_string()] }), ..Default::default() }),
+            ("pCont".to_string(), MAHUserParameterDefinition { default: 0.0, min: None, max: None, step: 1.0, param_type: Some(MAHUserParameterType::Continuous {}), ..Default::default() }),
+        ]);
+        let dyn_up_info = UserParametersConstrained::from(&user_parameters, &user_parameter_definitions);
+        assert_eq!(dyn_up_info.0["pInt"], 3.0);
+        assert_eq!(dyn_up_info.0["pBool"], 0.0);
+        assert_eq!(dyn_up_info.0["pEnum"], 2.0);
+        assert_eq!(dyn_up_info.0["pCont"], 2.6);
+    }
+
     #[test]
     fn test_mah_condition_eval() {
         let dyn_up_info = UserParametersConstrained(HashMap::from_iter(vec![("pA".to_string(), 2.0)]));
@@ -923,6 +1022,32 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_user_parameter_automation() {
+        let track = MAHUserParameterAutomationTrack { points: vec![
+            MAHUserParameterAutomationPoint { time: 100.0, value: 1.0, transition: MAHTransition::Linear {} },
+            MAHUserParameterAutomationPoint { time: 200.0, value: 3.0, transition: MAHTransition::Step {} },
+            MAHUserParameterAutomationPoint { time: 300.0, value: 5.0, transition: MAHTransition::Linear {} },
+        ]};
+        assert_eq!(track.value_at(0.0), Some(1.0));
+        assert_eq!(track.value_at(150.0), Some(2.0));
+        assert_eq!(track.value_at(250.0), Some(3.0));
+        assert_eq!(track.value_at(300.0), Some(5.0));
+        assert_eq!(track.value_at(1000.0), Some(5.0));
+        assert_eq!(MAHUserParameterAutomationTrack { points: vec![] }.value_at(0.0), None);
+
+        let mut pattern = create_test_pattern();
+        pattern.user_parameter_automation.insert("param1".to_string(), track);
+        let pattern_eval = PatternEvaluator::new_from_json_string(&serde_json::to_string(&pattern).unwrap()).unwrap();
+        let user_parameters = HashMap::from_iter(vec![
+            ("param1".to_string(), 9.0),
+            ("param2".to_string(), 4.0),
+        ]);
+        let automated = pattern_eval.apply_user_parameter_automation(150.0, &user_parameters);
+        assert_eq!(automated["param1"], 2.0);
+        assert_eq!(automated["param2"], 4.0);
+    }
+
     #[test]
     fn test_atformula_eval() {
         #[allow(non_snake_case)]
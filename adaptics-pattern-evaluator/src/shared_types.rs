@@ -50,15 +50,66 @@ pub struct MidAirHapticsAnimationFileFormat {
     pub pattern_transform: PatternTransformation,
 
     pub user_parameter_definitions: UserParameterDefinitions,
+
+    /// Recorded automation curves for user parameters. See [`MAHUserParameterAutomationTrack`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub user_parameter_automation: UserParameterAutomation,
 }
 
 pub type UserParameterDefinitions = HashMap<String, MAHUserParameterDefinition>;
-#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
 pub struct MAHUserParameterDefinition {
     pub default: f64,
     pub min: Option<f64>,
     pub max: Option<f64>,
     pub step: f64,
+
+    /// How values of this parameter are interpreted. Defaults to [`MAHUserParameterType::Continuous`]
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub param_type: Option<MAHUserParameterType>,
+    /// Display unit (e.g. "mm", "Hz")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Time constant (in milliseconds) used by playback to slew towards new values instead of jumping to them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smoothing: Option<MAHTime>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "name", content = "params")]
+#[serde(rename_all = "snake_case")]
+pub enum MAHUserParameterType {
+    Continuous {},
+    /// Values are rounded to the nearest integer
+    Integer {},
+    /// Values are either 0.0 (false) or 1.0 (true)
+    Boolean {},
+    /// Values are rounded to the nearest index into `labels`
+    Enum { labels: Vec<String> },
+}
+
+/// Automation tracks keyed by user parameter name.
+///
+/// Can be embedded in a pattern ([`MidAirHapticsAnimationFileFormat::user_parameter_automation`]) or stored as a sidecar file containing just this map.
+pub type UserParameterAutomation = HashMap<String, MAHUserParameterAutomationTrack>;
+
+/// A time-stamped curve of values for a single user parameter.
+///
+/// The curve is evaluated against playback time ([`crate::PatternEvaluatorParameters::time`]), not pattern time, so playback speed and conditional jumps do not affect it.
+/// Before the first point the value of the first point is used, after the last point the value of the last point is held.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MAHUserParameterAutomationTrack {
+    pub points: Vec<MAHUserParameterAutomationPoint>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct MAHUserParameterAutomationPoint {
+    pub time: MAHTime,
+    pub value: f64,
+    /// Transition from this point to the next point
+    pub transition: MAHTransition,
 }
 
 